@@ -0,0 +1,335 @@
+//! On-disk cache for downloaded and resized images
+//!
+//! Remote avatars, icons and favicons are stored under a cache directory so
+//! repeated builds don't download them again. Each download is kept with
+//! its `ETag` and `Last-Modified` headers and revalidated with a conditional
+//! request, so a changed image is picked up while an unchanged one costs a
+//! single `304 Not Modified`. In offline mode only the cache is used.
+//!
+//! Resized images are cached separately, keyed by a hash of the source
+//! image, the target size and the output format settings.
+//!
+//! The cache lives in `.genkan-cache/` if the project has one, otherwise in
+//! the user cache directory (`~/.cache/genkan/assets` on Linux). Set
+//! `GENKAN_CACHE_DIR` to use a different location.
+
+use crate::jobs::JobLog;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the project-local cache directory
+pub const PROJECT_CACHE_DIR: &str = ".genkan-cache";
+
+/// Timeout for each download
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Makes temporary file names unique across threads of the same process
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Cache of downloaded and resized images
+#[derive(Debug, Clone)]
+pub struct AssetCache {
+    dir: PathBuf,
+    offline: bool,
+}
+
+/// Metadata stored next to each cached download
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CachedDownload {
+    pub url: String,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    /// Size of the cached body in bytes
+    pub size: u64,
+    /// When the body was last fetched or revalidated (seconds since the Unix epoch)
+    pub fetched_at: u64,
+}
+
+impl AssetCache {
+    /// Opens the cache at `dir`
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            offline: false,
+        }
+    }
+
+    /// Opens the cache used for a project
+    ///
+    /// # Arguments
+    ///
+    /// * `project_root` - Directory containing `config.toml`
+    ///
+    /// # Returns
+    ///
+    /// The cache in `GENKAN_CACHE_DIR`, the project's `.genkan-cache/`, or
+    /// the user cache directory, whichever is found first
+    pub fn for_project(project_root: &Path) -> Self {
+        if let Some(dir) = std::env::var_os("GENKAN_CACHE_DIR") {
+            return Self::new(PathBuf::from(dir));
+        }
+        let project_cache = project_root.join(PROJECT_CACHE_DIR);
+        if project_cache.is_dir() {
+            return Self::new(project_cache);
+        }
+        let base = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        Self::new(base.join("genkan").join("assets"))
+    }
+
+    /// Only use cached downloads; fail instead of touching the network
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Returns the cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Downloads a URL, using the cached copy when it is still current
+    ///
+    /// A cached copy is revalidated with `If-None-Match`/`If-Modified-Since`.
+    /// If the request fails, the cached copy is used and a warning is added
+    /// to `log`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` with the response body
+    /// * `Err(anyhow::Error)` if the URL couldn't be downloaded and isn't
+    ///   cached (or isn't cached in offline mode)
+    pub fn fetch(&self, url: &str, log: &mut JobLog) -> Result<Vec<u8>> {
+        let url = &match url.strip_prefix("//") {
+            Some(rest) => format!("https://{}", rest),
+            None => url.to_string(),
+        };
+        let key = hash_hex(url.as_bytes());
+        let cached = self.load_download(&key);
+
+        if self.offline {
+            return match cached {
+                Some((_, body)) => Ok(body),
+                None => anyhow::bail!("{} is not cached (offline mode)", url),
+            };
+        }
+
+        let mut request = ureq::get(url)
+            .set("User-Agent", "Mozilla/5.0 (compatible; Genkan/1.0)")
+            .timeout(DOWNLOAD_TIMEOUT);
+        if let Some((ref entry, _)) = cached {
+            if let Some(ref etag) = entry.etag {
+                request = request.set("If-None-Match", etag);
+            }
+            if let Some(ref last_modified) = entry.last_modified {
+                request = request.set("If-Modified-Since", last_modified);
+            }
+        }
+
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(e) => {
+                return match cached {
+                    Some((_, body)) => {
+                        log.warn(format!(
+                            "Failed to download {}: {}. Using cached copy.",
+                            url, e
+                        ));
+                        Ok(body)
+                    }
+                    None => {
+                        Err(e).with_context(|| format!("Failed to download image from: {}", url))
+                    }
+                };
+            }
+        };
+
+        if response.status() == 304
+            && let Some((mut entry, body)) = cached
+        {
+            entry.fetched_at = now();
+            self.store_metadata(&key, &entry, log);
+            return Ok(body);
+        }
+
+        let etag = response.header("ETag").map(str::to_string);
+        let last_modified = response.header("Last-Modified").map(str::to_string);
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut body)
+            .with_context(|| format!("Failed to read image data from: {}", url))?;
+
+        let entry = CachedDownload {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            size: body.len() as u64,
+            fetched_at: now(),
+        };
+        if let Err(e) = write_atomic(&self.downloads_dir().join(&key), &body) {
+            log.warn(format!("Failed to cache {}: {}", url, e));
+        } else {
+            self.store_metadata(&key, &entry, log);
+        }
+        Ok(body)
+    }
+
+    /// Returns a cached resized image, or resizes and caches it
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The original image bytes
+    /// * `variant` - Describes the output, e.g. `512-auto-80` for the target
+    ///   size, format and quality; part of the cache key
+    /// * `resize` - Produces the resized image on a cache miss
+    /// * `log` - Receives a warning if the result can't be cached
+    pub fn resized<F>(
+        &self,
+        source: &[u8],
+        variant: &str,
+        resize: F,
+        log: &mut JobLog,
+    ) -> Result<Vec<u8>>
+    where
+        F: FnOnce() -> Result<Vec<u8>>,
+    {
+        let path = self
+            .resized_dir()
+            .join(format!("{}-{}", hash_hex(source), variant));
+        if let Ok(data) = fs::read(&path) {
+            return Ok(data);
+        }
+
+        let data = resize()?;
+        if let Err(e) = write_atomic(&path, &data) {
+            log.warn(format!("Failed to cache resized image: {}", e));
+        }
+        Ok(data)
+    }
+
+    /// Returns every cached download, oldest first
+    pub fn downloads(&self) -> Vec<CachedDownload> {
+        let mut entries: Vec<CachedDownload> = fs::read_dir(self.downloads_dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|e| e == "toml"))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| toml::from_str(&content).ok())
+            .collect();
+        entries.sort_by(|a, b| a.fetched_at.cmp(&b.fetched_at).then(a.url.cmp(&b.url)));
+        entries
+    }
+
+    /// Returns the number and total size in bytes of cached resized images
+    pub fn resized_stats(&self) -> (usize, u64) {
+        fs::read_dir(self.resized_dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .fold((0, 0), |(count, size), metadata| {
+                (count + 1, size + metadata.len())
+            })
+    }
+
+    /// Deletes every cached download and resized image
+    ///
+    /// The cache directory itself is kept, so a project-local
+    /// `.genkan-cache/` stays in use.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` with the number of bytes removed
+    /// * `Err(anyhow::Error)` if an entry couldn't be removed
+    pub fn clean(&self) -> Result<u64> {
+        let mut removed = 0;
+        for dir in [self.downloads_dir(), self.resized_dir()] {
+            if !dir.exists() {
+                continue;
+            }
+            removed += dir_size(&dir);
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        Ok(removed)
+    }
+
+    fn downloads_dir(&self) -> PathBuf {
+        self.dir.join("downloads")
+    }
+
+    fn resized_dir(&self) -> PathBuf {
+        self.dir.join("resized")
+    }
+
+    fn load_download(&self, key: &str) -> Option<(CachedDownload, Vec<u8>)> {
+        let metadata =
+            fs::read_to_string(self.downloads_dir().join(format!("{}.toml", key))).ok()?;
+        let entry: CachedDownload = toml::from_str(&metadata).ok()?;
+        let body = fs::read(self.downloads_dir().join(key)).ok()?;
+        (body.len() as u64 == entry.size).then_some((entry, body))
+    }
+
+    fn store_metadata(&self, key: &str, entry: &CachedDownload, log: &mut JobLog) {
+        let path = self.downloads_dir().join(format!("{}.toml", key));
+        let result = toml::to_string(entry)
+            .context("Failed to serialize cache entry")
+            .and_then(|content| write_atomic(&path, content.as_bytes()));
+        if let Err(e) = result {
+            log.warn(format!("Failed to cache {}: {}", entry.url, e));
+        }
+    }
+}
+
+/// Writes a file through a temporary name, so concurrent builds never read
+/// a half-written entry
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let temp_path = path.with_extension(format!(
+        "tmp-{}-{}",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temp_path, data)
+        .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+    fs::rename(&temp_path, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub(crate) fn hash_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
@@ -4,10 +4,18 @@
 //! static link pages. Configuration is loaded from TOML files and validated
 //! before generation.
 
+use crate::csp;
+use crate::diagnostics::{Diagnostic, Diagnostics, SourceFile};
+use crate::generator;
+use crate::images;
+use crate::pwa;
+use crate::qr::{self, QrStyle};
+use crate::schedule;
+use crate::security;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 /// Root configuration structure for a Genkan site
 ///
@@ -42,6 +50,20 @@ pub struct Config {
     pub dark_mode: DarkMode,
     #[serde(default)]
     pub image: ImageSettings,
+    #[serde(default)]
+    pub urls: UrlPolicy,
+    #[serde(default)]
+    pub csp: CspSettings,
+    #[serde(default)]
+    pub build: BuildSettings,
+    #[serde(default)]
+    pub output: OutputSettings,
+    #[serde(default)]
+    pub qr: QrSettings,
+    #[serde(default)]
+    pub icons: IconSettings,
+    #[serde(default)]
+    pub pwa: PwaSettings,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -72,6 +94,12 @@ pub struct SocialLink {
     pub url: String,
     #[serde(default)]
     pub title: Option<String>,
+    /// Show the link from this moment on
+    #[serde(default, deserialize_with = "deserialize_datetime")]
+    pub visible_from: Option<String>,
+    /// Stop showing the link at this moment
+    #[serde(default, deserialize_with = "deserialize_datetime")]
+    pub visible_until: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -89,6 +117,9 @@ pub struct Theme {
     pub light: ThemeColors,
     #[serde(default)]
     pub dark: ThemeColors,
+    /// Theme-specific options declared in the theme's `theme.toml`
+    #[serde(default)]
+    pub options: toml::Table,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -109,6 +140,21 @@ pub struct ThemeColors {
     pub link_description_color: String,
 }
 
+impl ThemeColors {
+    /// Returns every color with its key name
+    pub fn fields(&self) -> [(&'static str, &str); 7] {
+        [
+            ("primary_color", &self.primary_color),
+            ("secondary_color", &self.secondary_color),
+            ("background_color", &self.background_color),
+            ("header_color", &self.header_color),
+            ("bio_color", &self.bio_color),
+            ("link_title_color", &self.link_title_color),
+            ("link_description_color", &self.link_description_color),
+        ]
+    }
+}
+
 impl Default for ThemeColors {
     fn default() -> Self {
         Self {
@@ -200,6 +246,255 @@ impl Default for Typography {
     }
 }
 
+/// Which link URLs are allowed
+///
+/// `http` and `https` are always allowed. Other schemes must be listed
+/// explicitly; schemes that can run code (`javascript:`, `data:`, ...) are
+/// rejected even when listed.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct UrlPolicy {
+    /// Extra schemes to allow, e.g. `["mailto", "tel"]`
+    #[serde(default)]
+    pub allow_schemes: Vec<String>,
+    /// Allow relative URLs such as `/about` or `#contact`
+    #[serde(default)]
+    pub allow_relative: bool,
+}
+
+/// Settings for how the site is built
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BuildSettings {
+    /// Worker threads for downloading and resizing images (default: one per CPU)
+    #[serde(default)]
+    pub jobs: Option<usize>,
+    /// Fail the build if there are any config, theme or asset warnings
+    #[serde(default)]
+    pub strict: bool,
+    /// Allow images that can't be embedded to be loaded from their remote URL
+    #[serde(default = "default_allow_hotlinks")]
+    pub allow_hotlinks: bool,
+    /// `drop` to leave out links whose `visible_from` is still ahead, or
+    /// `reveal` to write them hidden for the theme to show when it passes
+    #[serde(default = "default_upcoming_links")]
+    pub upcoming_links: String,
+}
+
+impl Default for BuildSettings {
+    fn default() -> Self {
+        Self {
+            jobs: None,
+            strict: false,
+            allow_hotlinks: true,
+            upcoming_links: default_upcoming_links(),
+        }
+    }
+}
+
+fn default_allow_hotlinks() -> bool {
+    true
+}
+
+fn default_upcoming_links() -> String {
+    "drop".to_string()
+}
+
+/// Settings for the files written by a build
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OutputSettings {
+    /// `single` for one self-contained `index.html`, or `multi` for
+    /// `index.html` plus hashed files in `assets/`
+    #[serde(default = "default_output_mode")]
+    pub mode: String,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            mode: default_output_mode(),
+        }
+    }
+}
+
+fn default_output_mode() -> String {
+    "single".to_string()
+}
+
+/// QR code shown by the share button and written by `genkan qr`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct QrSettings {
+    /// `svg` or `png`
+    #[serde(default = "default_qr_format")]
+    pub format: String,
+    /// Width and height in CSS pixels, up to [`qr::MAX_SIZE`]
+    #[serde(default = "default_qr_size")]
+    pub size: u32,
+    /// Blank border around the code, in modules, up to [`qr::MAX_QUIET_ZONE`]
+    #[serde(default = "default_qr_quiet_zone")]
+    pub quiet_zone: u32,
+    /// `low`, `medium`, `quartile` or `high`
+    #[serde(default = "default_qr_error_correction")]
+    pub error_correction: String,
+    /// Show the avatar in the centre of the code
+    #[serde(default)]
+    pub logo: bool,
+    #[serde(default)]
+    pub light: QrColors,
+    /// Colours in dark mode; without them the light mode code is used
+    #[serde(default)]
+    pub dark: Option<QrColors>,
+}
+
+impl Default for QrSettings {
+    fn default() -> Self {
+        Self {
+            format: default_qr_format(),
+            size: default_qr_size(),
+            quiet_zone: default_qr_quiet_zone(),
+            error_correction: default_qr_error_correction(),
+            logo: false,
+            light: QrColors::default(),
+            dark: None,
+        }
+    }
+}
+
+impl QrSettings {
+    /// Returns the style of the light or dark mode code
+    ///
+    /// Dark mode colours that aren't set fall back to the light mode ones.
+    /// Invalid values are reported by [`Config::diagnose`] and replaced by
+    /// black on white here.
+    pub fn style(&self, dark: bool) -> QrStyle {
+        let default = QrStyle::default();
+        let color = |value: Option<&String>| value.and_then(|v| qr::parse_color(v));
+        let light_foreground = color(self.light.foreground.as_ref()).unwrap_or(default.foreground);
+        let light_background = color(self.light.background.as_ref()).unwrap_or(default.background);
+        let dark_colors = self.dark.as_ref().filter(|_| dark);
+        QrStyle {
+            foreground: color(dark_colors.and_then(|c| c.foreground.as_ref()))
+                .unwrap_or(light_foreground),
+            background: color(dark_colors.and_then(|c| c.background.as_ref()))
+                .unwrap_or(light_background),
+            quiet_zone: self.quiet_zone,
+            error_correction: qr::parse_error_correction(&self.error_correction)
+                .unwrap_or(default.error_correction),
+        }
+    }
+}
+
+/// Colours of a QR code as `#rgb` or `#rrggbb`
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct QrColors {
+    /// Colour of the dark modules (default: black)
+    #[serde(default)]
+    pub foreground: Option<String>,
+    /// Colour of the light modules and the quiet zone (default: white)
+    #[serde(default)]
+    pub background: Option<String>,
+}
+
+fn default_qr_format() -> String {
+    "svg".to_string()
+}
+
+fn default_qr_size() -> u32 {
+    200
+}
+
+fn default_qr_quiet_zone() -> u32 {
+    4
+}
+
+fn default_qr_error_correction() -> String {
+    "medium".to_string()
+}
+
+/// Favicons and home screen icons written next to the page
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IconSettings {
+    /// Write `favicon.ico`, PNG icons and `site.webmanifest`
+    #[serde(default = "default_icons_enabled")]
+    pub enabled: bool,
+    /// Raster image to make the icons from (default: `meta.favicon`, or the
+    /// light mode avatar)
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl Default for IconSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            source: None,
+        }
+    }
+}
+
+fn default_icons_enabled() -> bool {
+    true
+}
+
+/// Installing the page as a Progressive Web App
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PwaSettings {
+    /// Write `manifest.webmanifest` and a service worker that caches the
+    /// page for offline use
+    #[serde(default)]
+    pub enabled: bool,
+    /// Name under the home screen icon (default: `profile.name`)
+    #[serde(default)]
+    pub short_name: Option<String>,
+    /// `fullscreen`, `standalone`, `minimal-ui` or `browser`
+    #[serde(default = "default_pwa_display")]
+    pub display: String,
+}
+
+impl Default for PwaSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            short_name: None,
+            display: default_pwa_display(),
+        }
+    }
+}
+
+fn default_pwa_display() -> String {
+    "standalone".to_string()
+}
+
+/// Content-Security-Policy generated for the page
+///
+/// The policy only allows the page's own inline CSS and JS (by hash) and
+/// embedded images. Anything else the page loads, such as an analytics
+/// endpoint, has to be listed under `sources`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CspSettings {
+    /// Add a `<meta http-equiv="Content-Security-Policy">` tag to the page
+    #[serde(default = "default_csp_enabled")]
+    pub enabled: bool,
+    /// Also write the policy to a `_headers` file next to the page
+    #[serde(default)]
+    pub headers_file: bool,
+    /// Extra sources per directive, e.g. `connect-src = ["https://plausible.io"]`
+    #[serde(default)]
+    pub sources: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for CspSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            headers_file: false,
+            sources: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_csp_enabled() -> bool {
+    true
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DarkMode {
     #[serde(default = "default_dark_mode_mode")]
@@ -228,6 +523,27 @@ pub struct ImageSettings {
     pub link_icon_size: u32,
     #[serde(default = "default_favicon_size")]
     pub favicon_size: u32,
+    /// Output format: "auto", "webp", "jpeg" or "png"
+    #[serde(default = "default_image_format")]
+    pub format: String,
+    /// JPEG quality from 1 to 100
+    #[serde(default = "default_image_quality")]
+    pub quality: u8,
+    /// Pixel densities to generate avatars and icons for, e.g. `[1, 2, 3]`
+    #[serde(default = "default_image_densities")]
+    pub densities: Vec<u32>,
+    /// Embed images as data URLs; when false they are written next to the page
+    #[serde(default = "default_image_embed")]
+    pub embed: bool,
+    /// Maximum width of background images in pixels
+    #[serde(default = "default_background_max_width")]
+    pub background_max_width: u32,
+    /// Output format for background images (default: same as `format`)
+    #[serde(default)]
+    pub background_format: Option<String>,
+    /// Inline a tiny blurred copy of background images to show while they load
+    #[serde(default)]
+    pub background_placeholder: bool,
 }
 
 impl Default for ImageSettings {
@@ -237,6 +553,13 @@ impl Default for ImageSettings {
             social_icon_size: 128,
             link_icon_size: 128,
             favicon_size: 64,
+            format: default_image_format(),
+            quality: default_image_quality(),
+            densities: default_image_densities(),
+            embed: true,
+            background_max_width: default_background_max_width(),
+            background_format: None,
+            background_placeholder: false,
         }
     }
 }
@@ -257,6 +580,26 @@ fn default_favicon_size() -> u32 {
     64
 }
 
+fn default_image_format() -> String {
+    "auto".to_string()
+}
+
+fn default_image_quality() -> u8 {
+    80
+}
+
+fn default_image_densities() -> Vec<u32> {
+    vec![1]
+}
+
+fn default_image_embed() -> bool {
+    true
+}
+
+fn default_background_max_width() -> u32 {
+    1920
+}
+
 impl Typography {
     /// Get resolved typography values for a specific element, falling back to defaults
     pub fn resolve(
@@ -327,6 +670,12 @@ pub struct Meta {
     pub show_footer: bool,
     #[serde(default)]
     pub share_title: Option<String>,
+    /// Image shown in link previews; defaults to the light mode avatar
+    #[serde(default)]
+    pub og_image: Option<String>,
+    /// Twitter/X account of the page, e.g. `@alice`
+    #[serde(default)]
+    pub twitter_handle: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -343,12 +692,51 @@ pub struct Link {
     pub link_type: String,
     #[serde(default)]
     pub height: Option<String>,
+    /// Let visitors collapse the links under a `header`
+    #[serde(default)]
+    pub collapsible: bool,
+    /// Start with the links under a `header` collapsed (implies `collapsible`)
+    #[serde(default)]
+    pub collapsed: bool,
+    /// Show the link from this moment on; on a `header`, its whole section
+    #[serde(default, deserialize_with = "deserialize_datetime")]
+    pub visible_from: Option<String>,
+    /// Stop showing the link at this moment; on a `header`, its whole section
+    #[serde(default, deserialize_with = "deserialize_datetime")]
+    pub visible_until: Option<String>,
 }
 
+/// Values accepted for `link_type`
+///
+/// A `header` starts a section: it titles the links after it, up to the
+/// next header.
+pub const LINK_TYPES: &[&str] = &["block", "space", "header"];
+
 fn default_link_type() -> String {
     "block".to_string()
 }
 
+/// Reads a `visible_from` or `visible_until` written either as a string or
+/// as an unquoted TOML date-time, which is kept in its written form
+fn deserialize_datetime<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DateTimeValue {
+        Text(String),
+        Toml(toml::value::Datetime),
+    }
+
+    Ok(
+        Option::<DateTimeValue>::deserialize(deserializer)?.map(|value| match value {
+            DateTimeValue::Text(text) => text,
+            DateTimeValue::Toml(datetime) => datetime.to_string(),
+        }),
+    )
+}
+
 // Default values
 fn default_primary_color() -> String {
     "#000000".to_string()
@@ -394,6 +782,68 @@ fn default_link_description_color() -> String {
     "rgba(0, 0, 0, 0.6)".to_string()
 }
 
+/// Returns the directory that local paths in a config file are relative to
+///
+/// This is the directory containing the config file, or the working
+/// directory when the path has no parent (e.g. plain `config.toml`).
+pub fn project_root<P: AsRef<Path>>(config_path: P) -> PathBuf {
+    config_path
+        .as_ref()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// Keys from older config layouts and the keys that replace them
+///
+/// Early versions had a single avatar/background on `[profile]` and a single
+/// color scheme on `[theme]`. These now live under `profile.light` and
+/// `theme.light`.
+pub const LEGACY_KEYS: &[(&str, &str)] = &[
+    ("profile.avatar", "profile.light.avatar"),
+    ("profile.background", "profile.light.background"),
+    ("profile.background_image", "profile.light.background_image"),
+    ("theme.primary_color", "theme.light.primary_color"),
+    ("theme.secondary_color", "theme.light.secondary_color"),
+    ("theme.background_color", "theme.light.background_color"),
+    ("theme.header_color", "theme.light.header_color"),
+    ("theme.bio_color", "theme.light.bio_color"),
+    ("theme.link_title_color", "theme.light.link_title_color"),
+    (
+        "theme.link_description_color",
+        "theme.light.link_description_color",
+    ),
+];
+
+/// Returns the replacement for a legacy key path, if it is one
+fn legacy_replacement(path: &str) -> Option<&'static str> {
+    LEGACY_KEYS
+        .iter()
+        .find(|(legacy, _)| *legacy == path)
+        .map(|(_, replacement)| *replacement)
+}
+
+/// Formats an ignored key path as `links[2].colour`
+fn ignored_key_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{}]", ignored_key_path(parent), index),
+        Path::Map { parent, key } => {
+            let parent = ignored_key_path(parent);
+            if parent.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", parent, key)
+            }
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => ignored_key_path(parent),
+    }
+}
+
 impl Config {
     /// Loads configuration from a TOML file
     ///
@@ -406,81 +856,662 @@ impl Config {
     /// * `Ok(Config)` if the file was successfully parsed
     /// * `Err(anyhow::Error)` if the file couldn't be read or parsed
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path.as_ref()).context("Failed to read config file")?;
+        let source = SourceFile::load(path)?;
+        Self::from_source(&source)
+            .map(|(config, _)| config)
+            .map_err(|d| anyhow::anyhow!("{}", source.render(&d).trim_end()))
+            .context("Failed to parse TOML config")
+    }
 
-        let config: Config = toml::from_str(&content).context("Failed to parse TOML config")?;
+    /// Parses configuration from an already loaded source file
+    ///
+    /// Keys that don't map to any config field are collected as warnings
+    /// instead of being silently ignored. Keys from older config layouts
+    /// (see [`LEGACY_KEYS`]) get a hint pointing at `genkan migrate`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((Config, Diagnostics))` with warnings for unknown or legacy keys
+    /// * `Err(Diagnostic)` pointing at the location of the syntax or type error
+    pub fn from_source(
+        source: &SourceFile,
+    ) -> std::result::Result<(Self, Diagnostics), Diagnostic> {
+        let mut ignored = Vec::new();
+        let deserializer = toml::Deserializer::new(&source.content);
+        let config: Config =
+            serde_ignored::deserialize(deserializer, |path| ignored.push(ignored_key_path(&path)))
+                .map_err(|e| Diagnostic::error(e.message().trim_end()).with_span(e.span()))?;
 
-        Ok(config)
+        let mut diagnostics = Diagnostics::new();
+        for path in ignored {
+            let diagnostic = match legacy_replacement(&path) {
+                Some(replacement) => Diagnostic::warning(format!(
+                    "Legacy key `{}` is no longer used and will be ignored",
+                    path
+                ))
+                .with_help(format!(
+                    "move it to `{}`, or run `genkan migrate` to update the config",
+                    replacement
+                )),
+                None => Diagnostic::warning(format!("Unknown key `{}` will be ignored", path))
+                    .with_help("check the key for typos; see the README for all options"),
+            };
+            diagnostics.push(diagnostic.with_span(source.key_span(&path)).at(path));
+        }
+
+        Ok((config, diagnostics))
     }
 
     /// Validates the configuration
     ///
+    /// Runs all checks from [`Config::diagnose`] and fails if any of them
+    /// reported an error. Warnings do not cause a failure.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the configuration is valid
+    /// * `Err(anyhow::Error)` listing every validation error
+    pub fn validate(&self) -> Result<()> {
+        let errors: Vec<Diagnostic> = self
+            .diagnose()
+            .into_iter()
+            .filter(Diagnostic::is_error)
+            .collect();
+
+        match errors.as_slice() {
+            [] => Ok(()),
+            [error] => anyhow::bail!("{}", error),
+            errors => {
+                let list: Vec<String> = errors.iter().map(|e| format!("  - {}", e)).collect();
+                anyhow::bail!(
+                    "{} configuration errors:\n{}",
+                    errors.len(),
+                    list.join("\n")
+                )
+            }
+        }
+    }
+
+    /// Checks the configuration and collects every problem found
+    ///
     /// Checks that:
     /// - Profile name is not empty
     /// - At least one link is defined
     /// - Dark mode setting is valid (auto, light, dark, or disable)
-    /// - Link types are valid (block or space)
+    /// - Link types are valid (block, space or header)
     /// - Block-type links have titles
+    /// - Space-type links specify a height (warning only)
     ///
     /// # Returns
     ///
-    /// * `Ok(())` if the configuration is valid
-    /// * `Err(anyhow::Error)` with a descriptive error message if validation fails
-    pub fn validate(&self) -> Result<()> {
+    /// All errors and warnings, each pointing at the offending config key
+    pub fn diagnose(&self) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+
         // Validate that required fields are not empty
         if self.profile.name.is_empty() {
-            anyhow::bail!("Profile name cannot be empty");
+            diagnostics.push(Diagnostic::error("Profile name cannot be empty").at("profile.name"));
         }
 
         if self.links.is_empty() {
-            anyhow::bail!("At least one link must be defined");
+            diagnostics.push(
+                Diagnostic::error("At least one link must be defined")
+                    .at("links")
+                    .with_help("add a [[links]] table with a title and url"),
+            );
         }
 
         // Validate dark mode
         let mode = self.dark_mode.mode.to_lowercase();
         if mode != "auto" && mode != "light" && mode != "dark" && mode != "disable" {
-            anyhow::bail!(
-                "Invalid dark_mode.mode '{}'. Must be 'auto', 'light', 'dark', or 'disable'",
-                self.dark_mode.mode
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid dark_mode.mode '{}'", self.dark_mode.mode))
+                    .at("dark_mode.mode")
+                    .with_help("must be 'auto', 'light', 'dark', or 'disable'"),
+            );
+        }
+
+        // Validate image settings
+        if images::OutputFormat::parse(&self.image.format).is_none() {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid image.format '{}'", self.image.format))
+                    .at("image.format")
+                    .with_help(format!("must be one of: {}", images::FORMATS.join(", "))),
+            );
+        }
+        if let Some(ref format) = self.image.background_format
+            && images::OutputFormat::parse(format).is_none()
+        {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid image.background_format '{}'", format))
+                    .at("image.background_format")
+                    .with_help(format!("must be one of: {}", images::FORMATS.join(", "))),
+            );
+        }
+        if self.image.background_max_width == 0 {
+            diagnostics.push(
+                Diagnostic::error("image.background_max_width must be at least 1")
+                    .at("image.background_max_width"),
+            );
+        }
+        if !(1..=100).contains(&self.image.quality) {
+            diagnostics.push(
+                Diagnostic::error("image.quality must be between 1 and 100").at("image.quality"),
+            );
+        }
+        if self.image.densities.is_empty() {
+            diagnostics.push(
+                Diagnostic::error("image.densities cannot be empty")
+                    .at("image.densities")
+                    .with_help("use [1] for a single image"),
+            );
+        }
+        if let Some(density) = self.image.densities.iter().find(|d| !(1..=4).contains(*d)) {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid image density {}", density))
+                    .at("image.densities")
+                    .with_help("densities must be between 1 and 4"),
             );
         }
 
         // Validate links
         for (idx, link) in self.links.iter().enumerate() {
+            let default_identifier = format!("index {}", idx);
+            let link_identifier = match link.title.as_deref() {
+                Some(title) if !title.is_empty() => title,
+                _ => &default_identifier,
+            };
+
             // Validate link type
             let link_type = link.link_type.to_lowercase();
-            if link_type != "block" && link_type != "space" {
-                let default_identifier = format!("index {}", idx);
-                let link_identifier = link.title.as_deref().unwrap_or(&default_identifier);
-                anyhow::bail!(
-                    "Invalid link_type '{}' for link '{}'. Must be 'block' or 'space'",
-                    link.link_type,
-                    link_identifier
+            if !LINK_TYPES.contains(&link_type.as_str()) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "Invalid link_type '{}' for link '{}'",
+                        link.link_type, link_identifier
+                    ))
+                    .at(format!("links[{}].link_type", idx))
+                    .with_help(format!("must be one of: {}", LINK_TYPES.join(", "))),
                 );
             }
 
-            // For block type, title is required
-            if link_type == "block"
-                && (link.title.is_none()
-                    || link.title.as_ref().map(|t| t.is_empty()).unwrap_or(true))
+            // For block and header types, title is required
+            if (link_type == "block" || link_type == "header")
+                && link.title.as_deref().unwrap_or("").is_empty()
             {
-                anyhow::bail!(
-                    "Link title cannot be empty for block type (link at index {})",
-                    idx
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "Link title cannot be empty for {} type (link at index {})",
+                        link_type, idx
+                    ))
+                    .at(format!("links[{}].title", idx)),
+                );
+            }
+
+            if link_type == "header" && link.url.as_deref().is_some_and(|url| !url.is_empty()) {
+                diagnostics.push(
+                    Diagnostic::warning(format!(
+                        "Header '{}' has a url, which is ignored",
+                        link_identifier
+                    ))
+                    .at(format!("links[{}].url", idx))
+                    .with_help("headers only title the links after them"),
                 );
             }
 
+            if link_type != "header" && (link.collapsible || link.collapsed) {
+                diagnostics.push(
+                    Diagnostic::warning(format!(
+                        "Link '{}' can't be collapsed; only headers can",
+                        link_identifier
+                    ))
+                    .at(format!(
+                        "links[{}].{}",
+                        idx,
+                        if link.collapsed {
+                            "collapsed"
+                        } else {
+                            "collapsible"
+                        }
+                    ))
+                    .with_help("set link_type = \"header\" to start a section"),
+                );
+            }
+
+            if let Some(ref url) = link.url
+                && !url.is_empty()
+            {
+                self.diagnose_url(&mut diagnostics, url, &format!("links[{}].url", idx));
+            }
+
+            if let Some(ref height) = link.height
+                && !security::is_css_length(height)
+            {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "Invalid height '{}' for link '{}'",
+                        height, link_identifier
+                    ))
+                    .at(format!("links[{}].height", idx))
+                    .with_help("must be a CSS length such as \"30px\" or \"2rem\""),
+                );
+            }
+
+            diagnose_schedule(
+                &mut diagnostics,
+                link.visible_from.as_deref(),
+                link.visible_until.as_deref(),
+                &format!("links[{}]", idx),
+            );
+
             // For space type, height should be specified
             if link_type == "space" && link.height.is_none() {
-                let default_identifier = format!("index {}", idx);
-                let link_identifier = link.title.as_deref().unwrap_or(&default_identifier);
-                eprintln!(
-                    "Warning: Space type link '{}' has no height specified, using default",
-                    link_identifier
+                diagnostics.push(
+                    Diagnostic::warning(format!(
+                        "Space type link '{}' has no height specified, using default",
+                        link_identifier
+                    ))
+                    .at(format!("links[{}]", idx))
+                    .with_help("set `height`, e.g. height = \"30px\""),
+                );
+            }
+        }
+
+        for (idx, social_link) in self.profile.social_links.iter().enumerate() {
+            self.diagnose_url(
+                &mut diagnostics,
+                &social_link.url,
+                &format!("profile.social_links[{}].url", idx),
+            );
+            diagnose_schedule(
+                &mut diagnostics,
+                social_link.visible_from.as_deref(),
+                social_link.visible_until.as_deref(),
+                &format!("profile.social_links[{}]", idx),
+            );
+        }
+
+        if let Some(ref page_url) = self.meta.page_url
+            && !page_url.is_empty()
+            && let Err(reason) = security::check_url(page_url, &[], false)
+        {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid page_url '{}': {}", page_url, reason))
+                    .at("meta.page_url")
+                    .with_help("must be an absolute http or https URL"),
+            );
+        }
+
+        let has_page_url = self
+            .meta
+            .page_url
+            .as_deref()
+            .is_some_and(|url| !url.is_empty());
+        if self.meta.og_image.is_some() && !has_page_url {
+            diagnostics.push(
+                Diagnostic::warning("meta.og_image is only used when meta.page_url is set")
+                    .at("meta.og_image")
+                    .with_help("link previews need the absolute URL of the image"),
+            );
+        }
+
+        if let Some(ref handle) = self.meta.twitter_handle
+            && (!handle.starts_with('@')
+                || handle.len() < 2
+                || handle.contains(char::is_whitespace))
+        {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid twitter_handle '{}'", handle))
+                    .at("meta.twitter_handle")
+                    .with_help("must be an account name such as \"@alice\""),
+            );
+        }
+
+        for scheme in &self.urls.allow_schemes {
+            if security::FORBIDDEN_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) {
+                diagnostics.push(
+                    Diagnostic::error(format!("The `{}:` scheme can't be allowed", scheme))
+                        .at("urls.allow_schemes")
+                        .with_help("these URLs can run code in the page"),
+                );
+            }
+        }
+
+        self.diagnose_css(&mut diagnostics);
+
+        if self.build.jobs == Some(0) {
+            diagnostics.push(
+                Diagnostic::error("build.jobs must be at least 1")
+                    .at("build.jobs")
+                    .with_help("remove it to use one worker per CPU"),
+            );
+        }
+
+        if generator::OutputMode::parse(&self.output.mode).is_none() {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid output.mode '{}'", self.output.mode))
+                    .at("output.mode")
+                    .with_help(format!(
+                        "must be one of: {}",
+                        generator::OUTPUT_MODES.join(", ")
+                    )),
+            );
+        }
+
+        if schedule::UpcomingLinks::parse(&self.build.upcoming_links).is_none() {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "Invalid build.upcoming_links '{}'",
+                    self.build.upcoming_links
+                ))
+                .at("build.upcoming_links")
+                .with_help(format!(
+                    "must be one of: {}",
+                    schedule::UPCOMING_LINK_MODES.join(", ")
+                )),
+            );
+        }
+
+        self.diagnose_qr(&mut diagnostics);
+
+        if let Some(ref source) = self.icons.source
+            && (source.ends_with(".svg") || source.starts_with("data:"))
+        {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid icons.source '{}'", source))
+                    .at("icons.source")
+                    .with_help("must be a PNG, JPEG, WebP or GIF file or URL"),
+            );
+        }
+
+        if !pwa::DISPLAY_MODES.contains(&self.pwa.display.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid pwa.display '{}'", self.pwa.display))
+                    .at("pwa.display")
+                    .with_help(format!("must be one of: {}", pwa::DISPLAY_MODES.join(", "))),
+            );
+        }
+
+        for (directive, sources) in &self.csp.sources {
+            let path = format!("csp.sources.{}", directive);
+            if !csp::CONFIGURABLE_DIRECTIVES.contains(&directive.as_str()) {
+                diagnostics.push(
+                    Diagnostic::error(format!("Unknown CSP directive '{}'", directive))
+                        .at(path)
+                        .with_help(format!(
+                            "expected one of: {}",
+                            csp::CONFIGURABLE_DIRECTIVES.join(", ")
+                        )),
+                );
+                continue;
+            }
+            for source in sources {
+                if let Err(reason) = csp::check_source(source) {
+                    diagnostics.push(
+                        Diagnostic::error(format!("Invalid CSP source '{}': {}", source, reason))
+                            .at(path.clone()),
+                    );
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Checks a link URL against `[urls]`
+    fn diagnose_url(&self, diagnostics: &mut Diagnostics, url: &str, path: &str) {
+        let Err(reason) =
+            security::check_url(url, &self.urls.allow_schemes, self.urls.allow_relative)
+        else {
+            return;
+        };
+
+        let mut diagnostic =
+            Diagnostic::error(format!("Invalid URL '{}': {}", url, reason)).at(path);
+        match security::url_scheme(url) {
+            Some(scheme)
+                if !scheme.is_empty()
+                    && !security::FORBIDDEN_SCHEMES.contains(&scheme.as_str()) =>
+            {
+                diagnostic = diagnostic.with_help(format!(
+                    "add \"{}\" to `urls.allow_schemes` to allow it",
+                    scheme
+                ));
+            }
+            None => {
+                diagnostic = diagnostic
+                    .with_help("set `urls.allow_relative = true` to allow relative links");
+            }
+            _ => {}
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    /// Checks the `[qr]` section
+    fn diagnose_qr(&self, diagnostics: &mut Diagnostics) {
+        let settings = &self.qr;
+        if !qr::FORMATS.contains(&settings.format.to_ascii_lowercase().as_str()) {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid qr.format '{}'", settings.format))
+                    .at("qr.format")
+                    .with_help(format!("must be one of: {}", qr::FORMATS.join(", "))),
+            );
+        }
+        if qr::parse_error_correction(&settings.error_correction).is_none() {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "Invalid qr.error_correction '{}'",
+                    settings.error_correction
+                ))
+                .at("qr.error_correction")
+                .with_help(format!(
+                    "must be one of: {}",
+                    qr::ERROR_CORRECTION_LEVELS.join(", ")
+                )),
+            );
+        }
+        if !(1..=qr::MAX_SIZE).contains(&settings.size) {
+            diagnostics.push(
+                Diagnostic::error(format!("qr.size must be between 1 and {}", qr::MAX_SIZE))
+                    .at("qr.size"),
+            );
+        }
+        if settings.quiet_zone > qr::MAX_QUIET_ZONE {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "qr.quiet_zone must be at most {}",
+                    qr::MAX_QUIET_ZONE
+                ))
+                .at("qr.quiet_zone")
+                .with_help("scanners need a quiet zone of 4 modules"),
+            );
+        }
+
+        let mut schemes = vec![("light", &settings.light)];
+        schemes.extend(settings.dark.as_ref().map(|colors| ("dark", colors)));
+        let mut valid = true;
+        for (scheme, colors) in &schemes {
+            for (key, value) in [
+                ("foreground", &colors.foreground),
+                ("background", &colors.background),
+            ] {
+                if let Some(value) = value
+                    && qr::parse_color(value).is_none()
+                {
+                    valid = false;
+                    diagnostics.push(
+                        Diagnostic::error(format!("Invalid QR code color '{}'", value))
+                            .at(format!("qr.{}.{}", scheme, key))
+                            .with_help("must be a hex color such as \"#1a1a2e\""),
+                    );
+                }
+            }
+        }
+
+        // Scanners need a clear difference between the two colours
+        if valid {
+            for (scheme, _) in &schemes {
+                let style = settings.style(*scheme == "dark");
+                if qr::contrast_ratio(style.foreground, style.background) < 4.0 {
+                    diagnostics.push(
+                        Diagnostic::warning(format!(
+                            "The {} mode QR code has too little contrast to scan reliably",
+                            scheme
+                        ))
+                        .at(format!("qr.{}", scheme))
+                        .with_help(
+                            "use a foreground and background with a contrast of at least 4:1",
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Checks values that are written into the stylesheet
+    fn diagnose_css(&self, diagnostics: &mut Diagnostics) {
+        let theme = &self.theme;
+
+        if !security::is_css_length(&theme.link_spacing) {
+            diagnostics.push(
+                Diagnostic::error(format!("Invalid link_spacing '{}'", theme.link_spacing))
+                    .at("theme.link_spacing")
+                    .with_help("must be a CSS length such as \"24px\" or \"1.5rem\""),
+            );
+        }
+
+        for (scheme, colors) in [("light", &theme.light), ("dark", &theme.dark)] {
+            for (key, value) in colors.fields() {
+                if !security::is_css_color(value) {
+                    diagnostics.push(
+                        Diagnostic::error(format!("Invalid color '{}'", value))
+                            .at(format!("theme.{}.{}", scheme, key))
+                            .with_help("use a hex color, a color name, or rgb()/hsl()"),
+                    );
+                }
+            }
+        }
+
+        let typography = &theme.typography;
+        for (element, style) in [
+            ("default", &typography.default),
+            ("header", &typography.header),
+            ("bio", &typography.bio),
+            ("link_title", &typography.link_title),
+            ("link_description", &typography.link_description),
+        ] {
+            for (key, color) in [("color", &style.color), ("color_dark", &style.color_dark)] {
+                if let Some(color) = color
+                    && !security::is_css_color(color)
+                {
+                    diagnostics.push(
+                        Diagnostic::error(format!("Invalid color '{}'", color))
+                            .at(format!("theme.typography.{}.{}", element, key))
+                            .with_help("use a hex color, a color name, or rgb()/hsl()"),
+                    );
+                }
+            }
+            for (key, value) in [
+                ("size", &style.size),
+                ("font", &style.font),
+                ("weight", &style.weight),
+                ("style", &style.style),
+            ] {
+                if let Some(value) = value {
+                    push_css_value_error(
+                        diagnostics,
+                        value,
+                        format!("theme.typography.{}.{}", element, key),
+                    );
+                }
+            }
+        }
+
+        push_css_value_error(diagnostics, &theme.font_family, "theme.font_family".into());
+        push_css_value_error(
+            diagnostics,
+            &theme.button_style,
+            "theme.button_style".into(),
+        );
+
+        for (scheme, assets) in [("light", &self.profile.light), ("dark", &self.profile.dark)] {
+            if let Some(ref background) = assets.background {
+                push_css_value_error(
+                    diagnostics,
+                    background,
+                    format!("profile.{}.background", scheme),
                 );
             }
+            // Written into url('...'), so quotes and parentheses would end it
+            for (key, value) in [
+                ("avatar", Some(&assets.avatar)),
+                ("background_image", assets.background_image.as_ref()),
+            ] {
+                if let Some(value) = value
+                    && let Some(c) = value.chars().find(|c| {
+                        matches!(c, '\'' | '"' | '(' | ')' | '\\' | '<' | '>') || c.is_whitespace()
+                    })
+                {
+                    diagnostics.push(
+                        Diagnostic::error(format!(
+                            "`{}` is not allowed in image paths or URLs",
+                            c.escape_default()
+                        ))
+                        .at(format!("profile.{}.{}", scheme, key))
+                        .with_help("percent-encode it, e.g. %20 for a space"),
+                    );
+                }
+            }
         }
+    }
+}
 
-        Ok(())
+/// Reports a CSS value that could break out of its declaration
+fn push_css_value_error(diagnostics: &mut Diagnostics, value: &str, path: String) {
+    if let Err(reason) = security::check_css_value(value) {
+        diagnostics
+            .push(Diagnostic::error(format!("Invalid CSS value '{}': {}", value, reason)).at(path));
+    }
+}
+
+/// Reports `visible_from` and `visible_until` values that aren't dates, or
+/// that leave the link no time to be shown
+///
+/// # Arguments
+///
+/// * `path` - Config key path of the link, e.g. `links[3]`
+fn diagnose_schedule(
+    diagnostics: &mut Diagnostics,
+    from: Option<&str>,
+    until: Option<&str>,
+    path: &str,
+) {
+    let mut parse = |value: Option<&str>, key: &str| {
+        let value = value?;
+        schedule::parse_datetime(value)
+            .map_err(|reason| {
+                diagnostics.push(
+                    Diagnostic::error(format!("Invalid {}: {}", key, reason))
+                        .at(format!("{}.{}", path, key))
+                        .with_help(
+                            "use a date like 2025-12-24, or a date and time like 2025-12-24T18:00:00+01:00",
+                        ),
+                );
+            })
+            .ok()
+    };
+    let from_time = parse(from, "visible_from");
+    let until_time = parse(until, "visible_until");
+    if let (Some(from_time), Some(until_time)) = (from_time, until_time)
+        && until_time <= from_time
+    {
+        diagnostics.push(
+            Diagnostic::error(format!(
+                "visible_until ({}) must be later than visible_from ({})",
+                until.unwrap_or_default(),
+                from.unwrap_or_default()
+            ))
+            .at(format!("{}.visible_until", path)),
+        );
     }
 }
@@ -0,0 +1,521 @@
+//! Content-Security-Policy generation
+//!
+//! The generated page is a single HTML file with its CSS, JS and images
+//! inlined, so a strict policy is possible: nothing is allowed by default,
+//! inline `<style>` and `<script>` elements are allowed by their SHA-256
+//! hash, and images may only be `data:` URLs. The policy is computed from the
+//! minified output, so the hashes always match what is served.
+//!
+//! Anything the page loads from elsewhere (an analytics script, a web font)
+//! is detected where possible and otherwise has to be listed under
+//! `[csp.sources]` in the config or `[csp]` in the theme manifest.
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Directives whose sources can be extended from the config or a theme
+pub const CONFIGURABLE_DIRECTIVES: &[&str] = &[
+    "script-src",
+    "style-src",
+    "img-src",
+    "font-src",
+    "connect-src",
+    "media-src",
+    "frame-src",
+    "worker-src",
+    "manifest-src",
+];
+
+/// Prefix of the `<meta>` tag written by [`insert_meta`]
+const META_PREFIX: &str = r#"<meta http-equiv=Content-Security-Policy content=""#;
+
+/// A Content-Security-Policy as an ordered list of directives
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentSecurityPolicy {
+    directives: Vec<(String, Vec<String>)>,
+    /// Inline code in the page that the policy will block, such as `onclick`
+    /// handlers or `javascript:` links
+    pub blocked: Vec<String>,
+}
+
+impl ContentSecurityPolicy {
+    /// Returns the strictest policy that still allows everything in a page
+    ///
+    /// # Arguments
+    ///
+    /// * `html` - The final (minified) HTML of the page
+    pub fn for_page(html: &str) -> Self {
+        let mut directives = vec![("default-src".to_string(), vec!["'none'".to_string()])];
+        directives.extend(
+            CONFIGURABLE_DIRECTIVES
+                .iter()
+                .map(|directive| (directive.to_string(), Vec::new())),
+        );
+        directives.push(("base-uri".to_string(), vec!["'none'".to_string()]));
+        directives.push(("form-action".to_string(), vec!["'none'".to_string()]));
+
+        let mut policy = Self {
+            directives,
+            blocked: Vec::new(),
+        };
+        policy.allow("img-src", "data:");
+        policy.scan_html(html);
+        policy
+    }
+
+    /// Allows everything a linked stylesheet loads through `url()` and `@import`
+    ///
+    /// Relative URLs in the stylesheet map to `'self'` like those in the page.
+    pub fn allow_stylesheet(&mut self, css: &str) {
+        self.scan_css(css);
+    }
+
+    /// Adds a source to a directive, creating the directive if needed
+    pub fn allow(&mut self, directive: &str, source: &str) {
+        let sources = match self.directives.iter().position(|(d, _)| d == directive) {
+            Some(idx) => &mut self.directives[idx].1,
+            None => {
+                // Keep base-uri and form-action last, after the fetch directives
+                let idx = self
+                    .directives
+                    .iter()
+                    .position(|(d, _)| d == "base-uri")
+                    .unwrap_or(self.directives.len());
+                self.directives
+                    .insert(idx, (directive.to_string(), Vec::new()));
+                &mut self.directives[idx].1
+            }
+        };
+        if !sources.iter().any(|s| s == source) {
+            sources.push(source.to_string());
+        }
+    }
+
+    /// Adds every source from a `directive = [sources]` table
+    pub fn allow_all(&mut self, sources: &BTreeMap<String, Vec<String>>) {
+        for (directive, directive_sources) in sources {
+            for source in directive_sources {
+                self.allow(directive, source);
+            }
+        }
+    }
+
+    /// Returns the sources allowed by a directive
+    pub fn sources(&self, directive: &str) -> &[String] {
+        self.directives
+            .iter()
+            .find(|(d, _)| d == directive)
+            .map_or(&[], |(_, sources)| sources)
+    }
+
+    /// Returns the policy for an HTTP header
+    ///
+    /// Unlike the `<meta>` tag, a header can also stop other sites from
+    /// framing the page.
+    pub fn header_value(&self) -> String {
+        format!("{}; frame-ancestors 'none'", self)
+    }
+
+    /// Parses a policy previously written with [`Display`](fmt::Display)
+    fn parse(policy: &str) -> Self {
+        let directives = policy
+            .split(';')
+            .filter_map(|directive| {
+                let mut parts = directive.split_whitespace();
+                let name = parts.next()?;
+                Some((name.to_string(), parts.map(str::to_string).collect()))
+            })
+            .collect();
+        Self {
+            directives,
+            blocked: Vec::new(),
+        }
+    }
+
+    fn scan_html(&mut self, html: &str) {
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            let after = &rest[start + 1..];
+            if let Some(comment) = after.strip_prefix("!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            // Closing tags, doctypes and stray `<` have no element name
+            let name_len = after
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(after.len());
+            if name_len == 0 {
+                rest = after;
+                continue;
+            }
+            let name = after[..name_len].to_ascii_lowercase();
+            let (attributes, remaining) = parse_attributes(&after[name_len..]);
+            rest = remaining;
+
+            let mut content = "";
+            if name == "script" || name == "style" {
+                let end = find_ignore_case(rest, &format!("</{}", name)).unwrap_or(rest.len());
+                content = &rest[..end];
+                rest = &rest[end..];
+            }
+            self.add_element(&name, &attributes, content);
+        }
+    }
+
+    fn add_element(&mut self, name: &str, attributes: &[(String, String)], content: &str) {
+        let attribute = |key: &str| {
+            attributes
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+
+        for (key, value) in attributes {
+            if key.len() > 2 && key.starts_with("on") {
+                self.blocked
+                    .push(format!("The `{}` handler on <{}>", key, name));
+            } else if key == "style" && !value.is_empty() {
+                self.allow("style-src", "'unsafe-hashes'");
+                self.allow("style-src", &hash_source(value));
+            } else if matches!(key.as_str(), "href" | "src" | "action")
+                && crate::security::url_scheme(value).as_deref() == Some("javascript")
+            {
+                self.blocked
+                    .push(format!("The `javascript:` URL on <{}>", name));
+            }
+        }
+
+        match name {
+            "script" => match attribute("src") {
+                Some(src) => self.allow_url("script-src", src),
+                // JSON and other data blocks are never executed
+                None if attribute("type").is_none_or(is_javascript_type) => {
+                    self.allow("script-src", &hash_source(content));
+                }
+                None => {}
+            },
+            "style" => {
+                self.allow("style-src", &hash_source(content));
+                self.scan_css(content);
+            }
+            "img" | "source" => {
+                if let Some(src) = attribute("src") {
+                    self.allow_url("img-src", src);
+                }
+                for url in srcset_urls(attribute("srcset").unwrap_or("")) {
+                    self.allow_url("img-src", url);
+                }
+            }
+            "iframe" => {
+                if let Some(src) = attribute("src") {
+                    self.allow_url("frame-src", src);
+                }
+            }
+            "link" => {
+                let rel = attribute("rel").unwrap_or("").to_ascii_lowercase();
+                let directive = if rel.contains("stylesheet") {
+                    "style-src"
+                } else if rel.contains("icon") {
+                    "img-src"
+                } else if rel.contains("manifest") {
+                    "manifest-src"
+                } else {
+                    return;
+                };
+                if let Some(href) = attribute("href") {
+                    self.allow_url(directive, href);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Allows `@import`ed stylesheets, fonts and background images
+    fn scan_css(&mut self, css: &str) {
+        let mut offset = 0;
+        while let Some(found) = css[offset..].find("url(") {
+            let start = offset + found + 4;
+            let Some(end) = css[start..].find(')').map(|end| start + end) else {
+                break;
+            };
+            let url = css[start..end].trim().trim_matches(['\'', '"']);
+            let directive = css_context(css, offset + found);
+            self.allow_url(directive, url);
+            offset = end;
+        }
+
+        // `@import "https://..."` without url()
+        let mut rest = css;
+        while let Some(found) = rest.find("@import") {
+            rest = rest[found + 7..].trim_start();
+            if let Some(quote @ ('"' | '\'')) = rest.chars().next()
+                && let Some(end) = rest[1..].find(quote)
+            {
+                let url = rest[1..end + 1].to_string();
+                self.allow_url("style-src", &url);
+            }
+        }
+    }
+
+    fn allow_url(&mut self, directive: &str, url: &str) {
+        if let Some(source) = source_for_url(url) {
+            self.allow(directive, &source);
+        }
+    }
+}
+
+impl fmt::Display for ContentSecurityPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let directives: Vec<String> = self
+            .directives
+            .iter()
+            .filter(|(_, sources)| !sources.is_empty())
+            .map(|(directive, sources)| format!("{} {}", directive, sources.join(" ")))
+            .collect();
+        write!(f, "{}", directives.join("; "))
+    }
+}
+
+/// Returns the CSP hash source (`'sha256-...'`) for inline content
+pub fn hash_source(content: &str) -> String {
+    format!(
+        "'sha256-{}'",
+        BASE64_STANDARD.encode(Sha256::digest(content.as_bytes()))
+    )
+}
+
+/// Checks a source from the config before it is written into the policy
+///
+/// # Returns
+///
+/// * `Ok(())` for host, scheme and quoted keyword sources
+/// * `Err(String)` explaining why the source can't be used
+pub fn check_source(source: &str) -> Result<(), String> {
+    if source.is_empty() {
+        return Err("sources can't be empty".to_string());
+    }
+    if source.eq_ignore_ascii_case("'unsafe-inline'") {
+        return Err("'unsafe-inline' would allow any injected script or style".to_string());
+    }
+    if let Some(keyword) = source.strip_prefix('\'') {
+        let valid = keyword
+            .strip_suffix('\'')
+            .is_some_and(|k| !k.is_empty() && !k.contains('\''));
+        return if valid {
+            Ok(())
+        } else {
+            Err("keywords must be quoted, e.g. 'self'".to_string())
+        };
+    }
+    match source.chars().find(|c| {
+        c.is_whitespace() || c.is_control() || matches!(c, ';' | ',' | '\'' | '"' | '<' | '>')
+    }) {
+        Some(c) => Err(format!(
+            "`{}` is not allowed in sources",
+            c.escape_default()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Inserts a CSP `<meta>` tag into the page
+///
+/// The tag is placed right after `<meta charset>` (or `<head>`), since a
+/// policy only applies to content that comes after it.
+pub fn insert_meta(html: &str, policy: &ContentSecurityPolicy) -> String {
+    let lower = html.to_ascii_lowercase();
+    let position = lower
+        .find("<meta charset")
+        .or_else(|| lower.find("<head>"))
+        .and_then(|start| lower[start..].find('>').map(|end| start + end + 1))
+        .or_else(|| {
+            ["<title", "<style", "<script", "<link", "<body"]
+                .iter()
+                .filter_map(|tag| lower.find(tag))
+                .min()
+        })
+        .unwrap_or(0);
+    format!(
+        "{}{}{}\">{}",
+        &html[..position],
+        META_PREFIX,
+        policy,
+        &html[position..]
+    )
+}
+
+/// Adds sources to the CSP `<meta>` tag written by [`insert_meta`]
+///
+/// Used by `genkan serve` to allow its live reload script. Pages without a
+/// CSP tag are returned unchanged.
+pub fn extend_page_policy(html: &str, additions: &[(&str, &str)]) -> String {
+    let Some(start) = html.find(META_PREFIX).map(|idx| idx + META_PREFIX.len()) else {
+        return html.to_string();
+    };
+    let Some(end) = html[start..].find('"').map(|end| start + end) else {
+        return html.to_string();
+    };
+    let mut policy = ContentSecurityPolicy::parse(&html[start..end]);
+    for (directive, source) in additions {
+        policy.allow(directive, source);
+    }
+    format!("{}{}{}", &html[..start], policy, &html[end..])
+}
+
+/// Returns the contents of a `_headers` file applying the policy to every path
+///
+/// The format is understood by Netlify and Cloudflare Pages.
+pub fn headers_file(policy: &ContentSecurityPolicy) -> String {
+    format!("/*\n  Content-Security-Policy: {}\n", policy.header_value())
+}
+
+/// Parses the attributes of a start tag, returning them and the rest of the
+/// document after the tag
+fn parse_attributes(tag: &str) -> (Vec<(String, String)>, &str) {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if rest.is_empty() {
+            return (attributes, rest);
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return (attributes, after);
+        }
+
+        let name_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || matches!(c, '=' | '>' | '/'))
+            .unwrap_or(rest.len());
+        if name_end == 0 {
+            // A stray `=`
+            rest = &rest[1..];
+            continue;
+        }
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start_matches(|c: char| c.is_ascii_whitespace());
+            match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                    value = &after[1..end];
+                    rest = after.get(end + 1..).unwrap_or("");
+                }
+                _ => {
+                    let end = after
+                        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                        .unwrap_or(after.len());
+                    value = &after[..end];
+                    rest = &after[end..];
+                }
+            }
+        }
+        attributes.push((name, decode_entities(value)));
+    }
+}
+
+/// Decodes the character references minifiers and Tera produce in attributes
+fn decode_entities(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+    value
+        .replace("&quot;", "\"")
+        .replace("&#34;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.to_ascii_lowercase().find(needle)
+}
+
+fn is_javascript_type(script_type: &str) -> bool {
+    let script_type = script_type.trim().to_ascii_lowercase();
+    script_type.is_empty()
+        || script_type == "module"
+        || script_type == "text/javascript"
+        || script_type == "application/javascript"
+}
+
+/// Returns the directive a `url()` at `position` in a stylesheet is fetched under
+fn css_context(css: &str, position: usize) -> &'static str {
+    let before = &css[..position];
+    if before
+        .rfind(';')
+        .map_or(before, |idx| &before[idx..])
+        .contains("@import")
+    {
+        return "style-src";
+    }
+    let block_start = before.rfind('{').unwrap_or(0);
+    let rule_start = css[..block_start].rfind('}').map_or(0, |idx| idx + 1);
+    if css[rule_start..block_start]
+        .trim_start()
+        .starts_with("@font-face")
+    {
+        "font-src"
+    } else {
+        "img-src"
+    }
+}
+
+/// Returns the URLs in a `srcset` attribute
+///
+/// URLs may contain commas (e.g. data URLs), so candidates are split the way
+/// browsers do: a URL runs to the next whitespace and its descriptors run to
+/// the next comma.
+fn srcset_urls(srcset: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            return urls;
+        }
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let url = &rest[..end];
+        urls.push(url.trim_end_matches(','));
+        rest = &rest[end..];
+        // A URL ending in a comma has no descriptors
+        if !url.ends_with(',') {
+            rest = rest.find(',').map_or("", |idx| &rest[idx + 1..]);
+        }
+    }
+}
+
+/// Returns the CSP source that allows loading `url`
+///
+/// Remote URLs map to their origin, relative URLs to `'self'` and `data:`
+/// URLs to `data:`. Fragment-only references (`url(#clip)`) need no source.
+fn source_for_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    if url.is_empty() || url.starts_with('#') {
+        return None;
+    }
+    if let Some(host) = url.strip_prefix("//") {
+        let end = host.find(['/', '?', '#']).unwrap_or(host.len());
+        return Some(host[..end].to_string());
+    }
+    match crate::security::url_scheme(url).as_deref() {
+        None => Some("'self'".to_string()),
+        Some("data") => Some("data:".to_string()),
+        Some(scheme @ ("http" | "https")) => {
+            let after = &url[scheme.len() + 1..];
+            let host = after.trim_start_matches('/');
+            let end = host.find(['/', '?', '#']).unwrap_or(host.len());
+            Some(format!("{}://{}", scheme, &host[..end]))
+        }
+        Some(_) => None,
+    }
+}
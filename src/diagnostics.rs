@@ -0,0 +1,366 @@
+//! Configuration diagnostics with source locations
+//!
+//! Validation collects every problem it finds as a [`Diagnostic`] instead of
+//! stopping at the first one. Each diagnostic can point at a key path inside
+//! the config (for example `links[3].link_type`), which is resolved to a byte
+//! span in the TOML source and rendered as a rustc-style snippet:
+//!
+//! ```text
+//! error: Invalid link_type 'buttn' for link 'GitHub'
+//!   --> config.toml:42:13
+//!    |
+//! 42 | link_type = "buttn"
+//!    |             ^^^^^^^
+//!    = help: must be 'block' or 'space'
+//! ```
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use toml_edit::{ImDocument, Item, Table, Value};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single problem found in a configuration file
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Key path inside the config, e.g. `links[3].link_type`
+    pub path: Option<String>,
+    /// Byte range in the source; resolved from `path` when not set explicitly
+    pub span: Option<Range<usize>>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Creates an error diagnostic
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    /// Creates a warning diagnostic
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            path: None,
+            span: None,
+            help: None,
+        }
+    }
+
+    /// Attaches the config key path this diagnostic refers to
+    pub fn at(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Attaches an explicit byte span in the source
+    pub fn with_span(mut self, span: Option<Range<usize>>) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Attaches a hint on how to fix the problem
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(ref path) = self.path {
+            write!(f, " (at {})", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// An ordered collection of diagnostics
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.items.push(diagnostic);
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.items.extend(other.items);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter().filter(|d| d.is_error())
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter().filter(|d| !d.is_error())
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Renders all diagnostics as snippets against the given source
+    pub fn render(&self, source: &SourceFile) -> String {
+        let mut out = String::new();
+        for diagnostic in &self.items {
+            out.push_str(&source.render(diagnostic));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Returns a one-line summary such as `2 errors, 1 warning`
+    pub fn summary(&self) -> String {
+        let errors = self.errors().count();
+        let warnings = self.warnings().count();
+        let plural =
+            |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+        match (errors, warnings) {
+            (0, w) => plural(w, "warning"),
+            (e, 0) => plural(e, "error"),
+            (e, w) => format!("{}, {}", plural(e, "error"), plural(w, "warning")),
+        }
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+/// A config file's text, used to resolve key paths to source locations
+pub struct SourceFile {
+    pub name: String,
+    pub content: String,
+    document: Option<ImDocument<String>>,
+}
+
+impl SourceFile {
+    /// Creates a source file from in-memory text
+    pub fn new(name: impl Into<String>, content: impl Into<String>) -> Self {
+        let content = content.into();
+        let document = ImDocument::parse(content.clone()).ok();
+        Self {
+            name: name.into(),
+            content,
+            document,
+        }
+    }
+
+    /// Reads a source file from disk
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path.as_ref()).context("Failed to read config file")?;
+        Ok(Self::new(path.as_ref().display().to_string(), content))
+    }
+
+    /// Returns the span of the value at `path`
+    ///
+    /// If the path cannot be fully resolved (for example because the key is
+    /// missing), the span of the closest existing parent is returned instead.
+    pub fn value_span(&self, path: &str) -> Option<Range<usize>> {
+        self.resolve(path).map(|(_, value)| value)
+    }
+
+    /// Returns the span of the key at the end of `path`
+    pub fn key_span(&self, path: &str) -> Option<Range<usize>> {
+        self.resolve(path).and_then(|(key, _)| key)
+    }
+
+    fn resolve(&self, path: &str) -> Option<(Option<Range<usize>>, Range<usize>)> {
+        let document = self.document.as_ref()?;
+        let mut node = Node::Item(document.as_item());
+        let mut best: Option<(Option<Range<usize>>, Range<usize>)> = None;
+
+        for segment in parse_path(path) {
+            let next = match segment {
+                Segment::Key(key) => node.child(&key),
+                Segment::Index(idx) => node.index(idx).map(|n| (None, n)),
+            };
+            let Some((key_span, child)) = next else {
+                break;
+            };
+            if let Some(span) = child.span().or_else(|| key_span.clone()) {
+                best = Some((key_span, span));
+            }
+            node = child;
+        }
+
+        best
+    }
+
+    /// Converts a byte offset into a 1-based line and column
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.content.len());
+        let before = &self.content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let col = self.content[line_start..offset].chars().count() + 1;
+        (line, col)
+    }
+
+    /// Renders one diagnostic as a rustc-style snippet
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = format!("{}: {}\n", diagnostic.severity, diagnostic.message);
+
+        let span = diagnostic
+            .span
+            .clone()
+            .or_else(|| diagnostic.path.as_deref().and_then(|p| self.value_span(p)));
+
+        match span {
+            Some(span) => {
+                let (line, col) = self.line_col(span.start);
+                let line_text = self.content.lines().nth(line - 1).unwrap_or("");
+                let gutter = " ".repeat(line.to_string().len());
+
+                // Underline up to the end of the first line of the span
+                let remaining = line_text.chars().count().saturating_sub(col - 1);
+                let span_len = self
+                    .content
+                    .get(span.clone())
+                    .map(|s| s.lines().next().unwrap_or("").chars().count())
+                    .unwrap_or(1);
+                let underline = "^".repeat(span_len.clamp(1, remaining.max(1)));
+
+                out.push_str(&format!("{}--> {}:{}:{}\n", gutter, self.name, line, col));
+                out.push_str(&format!("{} |\n", gutter));
+                out.push_str(&format!("{} | {}\n", line, line_text));
+                out.push_str(&format!(
+                    "{} | {}{}\n",
+                    gutter,
+                    " ".repeat(col - 1),
+                    underline
+                ));
+                if let Some(ref help) = diagnostic.help {
+                    out.push_str(&format!("{} = help: {}\n", gutter, help));
+                }
+            }
+            None => {
+                let location = match diagnostic.path {
+                    Some(ref path) => format!("{} ({})", self.name, path),
+                    None => self.name.clone(),
+                };
+                out.push_str(&format!(" --> {}\n", location));
+                if let Some(ref help) = diagnostic.help {
+                    out.push_str(&format!("  = help: {}\n", help));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Splits a path like `links[3].link_type` into keys and indices
+fn parse_path(path: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    for part in path.split('.').filter(|p| !p.is_empty()) {
+        let mut rest = part;
+        if let Some(bracket) = rest.find('[') {
+            if bracket > 0 {
+                segments.push(Segment::Key(rest[..bracket].to_string()));
+            }
+            rest = &rest[bracket..];
+            while let Some(stripped) = rest.strip_prefix('[') {
+                let Some(end) = stripped.find(']') else {
+                    break;
+                };
+                if let Ok(idx) = stripped[..end].parse() {
+                    segments.push(Segment::Index(idx));
+                }
+                rest = &stripped[end + 1..];
+            }
+        } else {
+            segments.push(Segment::Key(rest.to_string()));
+        }
+    }
+    segments
+}
+
+/// A position in a parsed TOML document
+enum Node<'a> {
+    Item(&'a Item),
+    Table(&'a Table),
+    Value(&'a Value),
+}
+
+impl<'a> Node<'a> {
+    fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Node::Item(item) => item.span(),
+            Node::Table(table) => table.span(),
+            Node::Value(value) => value.span(),
+        }
+    }
+
+    fn child(&self, key: &str) -> Option<(Option<Range<usize>>, Node<'a>)> {
+        let (key, item) = match self {
+            Node::Item(item) => item.as_table_like()?.get_key_value(key)?,
+            Node::Table(table) => table.get_key_value(key)?,
+            Node::Value(value) => value.as_inline_table()?.get_key_value(key)?,
+        };
+        Some((key.span(), Node::Item(item)))
+    }
+
+    fn index(&self, idx: usize) -> Option<Node<'a>> {
+        match self {
+            Node::Item(Item::ArrayOfTables(tables)) => tables.get(idx).map(Node::Table),
+            Node::Item(Item::Value(Value::Array(array))) | Node::Value(Value::Array(array)) => {
+                array.get(idx).map(Node::Value)
+            }
+            _ => None,
+        }
+    }
+}
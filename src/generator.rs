@@ -3,22 +3,75 @@
 //! This module handles the core generation logic for Genkan, including:
 //! - Template rendering (HTML and CSS)
 //! - Image downloading, compression, and embedding
-//! - SVG color processing for dark mode compatibility
+//! - SVG sanitising and color processing for dark mode compatibility
 //! - QR code generation
+//! - Favicons, home screen icons and the web app manifest
+//! - The service worker for installing the page as a PWA
+//! - Scheduled links, left out or hidden outside their time window
+//! - Content-Security-Policy generation for the final page
 //! - Theme file loading
 
-use crate::config::Config;
+use crate::cache::{self, AssetCache};
+use crate::config::{Config, Link};
+use crate::csp::{self, ContentSecurityPolicy};
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use crate::icons;
+use crate::images::{self, OutputFormat};
+use crate::jobs::{self, JobLog};
+use crate::pwa;
+use crate::qr::{self, QrFormat};
+use crate::schedule::{Schedule, UpcomingLinks, Visibility};
+use crate::themes::ResolvedTheme;
 use anyhow::{Context, Result};
-use image::Luma;
+use chrono::{DateTime, Utc};
 use minify_html::{Cfg, minify};
-use qrcode::QrCode;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tera::{Context as TeraContext, Tera};
 
-/// Default theme repository URL
-const DEFAULT_THEME_REPO: &str = "https://github.com/dephilia/genkan.git";
+pub use crate::themes::find_theme_path;
+
+/// Directory next to the page that asset files are written to
+pub const ASSETS_DIR: &str = "assets";
+
+/// File next to the page that maps logical asset names to asset files
+pub const ASSET_MANIFEST: &str = "asset-manifest.json";
+
+/// Pixel density PNG QR codes are rendered at, so they stay sharp on
+/// high-DPI screens
+const QR_PNG_DENSITY: u32 = 3;
+
+/// Maximum width and height of the image shown in link previews
+const SHARE_IMAGE_SIZE: u32 = 1200;
+
+/// Values accepted for `output.mode`
+pub const OUTPUT_MODES: &[&str] = &["single", "multi"];
+
+/// How the generated page is split into files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// One self-contained `index.html` with everything inlined
+    Single,
+    /// `index.html` plus hashed CSS, JS and image files in `assets/`
+    Multi,
+}
+
+impl OutputMode {
+    /// Parses an `output.mode` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "single" => Some(Self::Single),
+            "multi" => Some(Self::Multi),
+            _ => None,
+        }
+    }
+}
+
+/// Decides from the build report whether the output may be written
+pub type BuildCheck = Box<dyn Fn(&BuildReport) -> Result<()> + Send + Sync>;
 
 /// Main site generator
 ///
@@ -28,6 +81,21 @@ pub struct Generator {
     config: Config,
     pub theme_path: PathBuf,
     output_path: PathBuf,
+    /// Directory that local asset paths in the config are relative to
+    pub project_root: PathBuf,
+    /// Only use cached downloads
+    offline: bool,
+    /// Worker threads for asset processing, overriding `[build] jobs`
+    jobs: Option<usize>,
+    /// Output mode, overriding `[output] mode`
+    output_mode: Option<OutputMode>,
+    /// Moment to build the page for instead of the current time
+    at: Option<DateTime<Utc>>,
+    /// Runs before anything is written; an error leaves the output as it was
+    check: Option<BuildCheck>,
+    /// Asset and icon files made during the build, written once it passes
+    /// the check
+    pending: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
 }
 
 impl Generator {
@@ -43,85 +111,394 @@ impl Generator {
             config,
             theme_path,
             output_path,
+            project_root: PathBuf::new(),
+            offline: false,
+            jobs: None,
+            output_mode: None,
+            at: None,
+            check: None,
+            pending: Mutex::default(),
         }
     }
 
+    /// Sets the directory that local asset paths are resolved against
+    ///
+    /// This is normally the directory containing `config.toml`, so that
+    /// `./avatar.png` refers to a file next to the config regardless of the
+    /// working directory. Defaults to the working directory.
+    pub fn with_project_root(mut self, project_root: PathBuf) -> Self {
+        self.project_root = project_root;
+        self
+    }
+
+    /// Builds from the asset cache only, without network access
+    ///
+    /// Remote images that aren't cached are left as URLs with a warning.
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Sets the number of worker threads used to download and resize images
+    ///
+    /// Defaults to `[build] jobs` from the config, or one per CPU.
+    pub fn with_jobs(mut self, jobs: Option<usize>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Sets whether to write a single file or split assets into files
+    ///
+    /// Defaults to `[output] mode` from the config.
+    pub fn with_output_mode(mut self, output_mode: Option<OutputMode>) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    /// Builds the page as it looks at a given moment instead of now
+    ///
+    /// Links are picked for that moment only: upcoming links are left out
+    /// even with `build.upcoming_links = "reveal"`, and the page doesn't
+    /// show or hide any links in the browser later.
+    pub fn with_at(mut self, at: Option<DateTime<Utc>>) -> Self {
+        self.at = at;
+        self
+    }
+
+    /// Sets a check that can fail the build before anything is written
+    ///
+    /// Used for strict builds, so a failed build doesn't leave a page that
+    /// a later deploy step could publish.
+    pub fn with_check(mut self, check: BuildCheck) -> Self {
+        self.check = Some(check);
+        self
+    }
+
+    /// Returns the output mode in effect
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode
+            .or_else(|| OutputMode::parse(&self.config.output.mode))
+            .unwrap_or(OutputMode::Single)
+    }
+
+    /// Returns the cache used for downloaded and resized images
+    pub fn cache(&self) -> AssetCache {
+        AssetCache::for_project(&self.project_root).offline(self.offline)
+    }
+
+    /// Resolves a local asset path from the config against the project root
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        self.project_root.join(path)
+    }
+
     /// Generates the static site
     ///
     /// This is the main entry point for site generation. It:
-    /// 1. Validates the configuration
+    /// 1. Validates the configuration against itself and the theme manifest
     /// 2. Loads and renders theme files
-    /// 3. Processes all images (download, resize, embed as base64)
+    /// 3. Processes all images in parallel (download, resize, embed as base64)
     /// 4. Generates QR codes if configured
     /// 5. Renders the final HTML with all assets embedded
     /// 6. Writes the output file
     ///
     /// # Returns
     ///
-    /// * `Ok(())` if generation was successful
+    /// * `Ok(BuildReport)` with the warnings printed during the build and any
+    ///   images that couldn't be embedded
     /// * `Err(anyhow::Error)` if any step failed
-    pub fn generate(&self) -> Result<()> {
+    pub fn generate(&self) -> Result<BuildReport> {
         // Validate config
         self.config
             .validate()
             .context("Configuration validation failed")?;
 
+        // Resolve the theme and the themes it extends
+        let theme = self.resolve_theme()?;
+        let manifest = &theme.manifest;
+        let features = manifest.features();
+
+        // Check the config against the theme's manifest
+        manifest
+            .validate(&self.config)
+            .with_context(|| format!("Config is not compatible with theme '{}'", manifest.name))?;
+        let theme_options = manifest.resolve_options(&self.config);
+
         // Load theme files
-        let html_template = self.load_theme_file("template.html")?;
-        let css_template = self.load_theme_file("style.css")?;
-        let js_content = self.load_theme_file("script.js").unwrap_or_default();
+        let stylesheets = theme.stylesheets()?;
+        let js_content = theme.script()?;
 
         // Create Tera instance for CSS (which has template variables)
         let mut tera = Tera::default();
-        tera.add_raw_template("style.css", &css_template)
+        tera.add_raw_templates(stylesheets.clone())
             .context("Failed to add CSS template")?;
 
+        // Leave out links outside their time window. Indices into the
+        // config are kept for warnings and for the `schedule` values.
+        let now = self.at.unwrap_or_else(Utc::now);
+        let link_schedules: Vec<_> = self
+            .config
+            .links
+            .iter()
+            .map(|link| Schedule::new(link.visible_from.as_deref(), link.visible_until.as_deref()))
+            .collect();
+        let social_schedules: Vec<_> = self
+            .config
+            .profile
+            .social_links
+            .iter()
+            .map(|social_link| {
+                Schedule::new(
+                    social_link.visible_from.as_deref(),
+                    social_link.visible_until.as_deref(),
+                )
+            })
+            .collect();
+        let link_indices = self.scheduled_links(&self.config.links, &link_schedules, now);
+        let mut social_indices: Vec<usize> = (0..social_schedules.len())
+            .filter(|&idx| self.is_shown(&social_schedules[idx], now))
+            .collect();
+        let left_out = self.config.links.len() - link_indices.len() + social_schedules.len()
+            - social_indices.len();
+        if left_out > 0 {
+            println!(
+                "Left out {} scheduled link(s) that expired or aren't shown yet",
+                left_out
+            );
+        }
+
         // Process profile assets (download and embed external images)
         let mut processed_profile = self.config.profile.clone();
+        let mut processed_links: Vec<_> = link_indices
+            .iter()
+            .map(|&idx| self.config.links[idx].clone())
+            .collect();
+
+        // Leave out features the theme can't render
+        if !features.social_links {
+            social_indices.clear();
+        }
+        processed_profile.social_links = social_indices
+            .iter()
+            .map(|&idx| self.config.profile.social_links[idx].clone())
+            .collect();
 
         // Get target sizes from config
-        let avatar_size = Some(self.config.image.avatar_size);
-        let social_icon_size = Some(self.config.image.social_icon_size);
-        let link_icon_size = Some(self.config.image.link_icon_size);
+        let image = &self.config.image;
+        let avatar_size = Some(image.avatar_size);
+        let social_icon_size = Some(image.social_icon_size);
+        let link_icon_size = Some(image.link_icon_size);
+        let favicon_size = Some(image.favicon_size);
+
+        // Every image is one job; identical images are only processed once
+        let mut graph = AssetGraph::default();
+        let light_avatar = graph.add(
+            AssetKind::Icon,
+            &processed_profile.light.avatar,
+            avatar_size,
+            "profile.light.avatar".to_string(),
+            "light mode avatar",
+        );
+        let dark_avatar = graph.add(
+            AssetKind::Icon,
+            &processed_profile.dark.avatar,
+            avatar_size,
+            "profile.dark.avatar".to_string(),
+            "dark mode avatar",
+        );
+        let social_icons: Vec<_> = processed_profile
+            .social_links
+            .iter()
+            .zip(&social_indices)
+            .map(|(social_link, idx)| {
+                graph.add(
+                    AssetKind::Icon,
+                    &social_link.icon,
+                    social_icon_size,
+                    format!("profile.social_links[{}].icon", idx),
+                    "social link icon",
+                )
+            })
+            .collect();
+        // Embed background images so they work wherever the output is deployed
+        let backgrounds = [
+            ("light", &processed_profile.light),
+            ("dark", &processed_profile.dark),
+        ]
+        .map(|(scheme, assets)| {
+            graph.add(
+                AssetKind::Background,
+                assets.background_image.as_deref().unwrap_or(""),
+                None,
+                format!("profile.{}.background_image", scheme),
+                "background image",
+            )
+        });
+        let link_icons: Vec<_> = processed_links
+            .iter()
+            .zip(&link_indices)
+            .map(|(link, idx)| {
+                let is_header = link.link_type.eq_ignore_ascii_case("header");
+                graph.add(
+                    AssetKind::Icon,
+                    if is_header && !features.link_sections {
+                        ""
+                    } else {
+                        link.icon.as_deref().unwrap_or("")
+                    },
+                    link_icon_size,
+                    format!("links[{}].icon", idx),
+                    "link icon",
+                )
+            })
+            .collect();
+        // Process favicon (convert local files to data URLs)
+        let favicon = graph.add(
+            AssetKind::Favicon,
+            self.config.meta.favicon.as_deref().unwrap_or(""),
+            favicon_size,
+            "meta.favicon".to_string(),
+            "favicon",
+        );
+        // Link previews need an absolute image URL, so only with a page URL
+        let page_url = self
+            .config
+            .meta
+            .page_url
+            .as_deref()
+            .filter(|url| !url.is_empty());
+        let share_image = graph.add(
+            AssetKind::ShareImage,
+            if page_url.is_some() {
+                self.share_image_source()
+            } else {
+                ""
+            },
+            Some(SHARE_IMAGE_SIZE),
+            "meta.og_image".to_string(),
+            "share image",
+        );
+        let app_icon_source = if self.config.icons.enabled {
+            self.app_icon_source()
+        } else {
+            ""
+        };
+        let app_icons = graph.add(
+            AssetKind::AppIcons,
+            app_icon_source,
+            None,
+            "icons.source".to_string(),
+            "app icons",
+        );
 
-        // Process light mode avatar
-        if !processed_profile.light.avatar.is_empty() {
-            match self.process_icon(&processed_profile.light.avatar, avatar_size) {
-                Ok(processed) => processed_profile.light.avatar = processed,
-                Err(e) => eprintln!("Warning: Failed to process light mode avatar: {}", e),
+        let mut report = BuildReport::default();
+        let mut assets = self.process_assets(&graph, &mut report)?;
+        let mut asset_files = std::mem::take(&mut assets.files);
+        let output_mode = self.output_mode();
+
+        // Raster avatars and icons are also exposed as `*_image` objects with
+        // a `srcset` for templates
+        let mut avatar_images = [None, None];
+        for ((profile_assets, slot), image) in
+            [&mut processed_profile.light, &mut processed_profile.dark]
+                .into_iter()
+                .zip([light_avatar, dark_avatar])
+                .zip(&mut avatar_images)
+        {
+            if let Some(processed) = assets.take(slot) {
+                profile_assets.avatar = processed.src;
+                *image = processed.image;
             }
         }
-
-        // Process dark mode avatar
-        if !processed_profile.dark.avatar.is_empty() {
-            match self.process_icon(&processed_profile.dark.avatar, avatar_size) {
-                Ok(processed) => processed_profile.dark.avatar = processed,
-                Err(e) => eprintln!("Warning: Failed to process dark mode avatar: {}", e),
+        let mut social_icon_images = Vec::with_capacity(social_icons.len());
+        for (social_link, slot) in processed_profile.social_links.iter_mut().zip(social_icons) {
+            let processed = assets.take(slot);
+            if let Some(ref processed) = processed {
+                social_link.icon = processed.src.clone();
             }
+            social_icon_images.push(processed.and_then(|p| p.image));
         }
-
-        // Process social link icons
-        for social_link in &mut processed_profile.social_links {
-            if !social_link.icon.is_empty() {
-                match self.process_icon(&social_link.icon, social_icon_size) {
-                    Ok(processed) => social_link.icon = processed,
-                    Err(e) => eprintln!("Warning: Failed to process social link icon: {}", e),
-                }
+        let mut background_placeholders = [None, None];
+        for ((profile_assets, slot), placeholder) in
+            [&mut processed_profile.light, &mut processed_profile.dark]
+                .into_iter()
+                .zip(backgrounds)
+                .zip(&mut background_placeholders)
+        {
+            if let Some(processed) = assets.take(slot) {
+                profile_assets.background_image = Some(processed.src);
+                *placeholder = processed.placeholder;
+            }
+        }
+        let mut link_icon_images = Vec::with_capacity(link_icons.len());
+        for (link, slot) in processed_links.iter_mut().zip(link_icons) {
+            let processed = assets.take(slot);
+            if let Some(ref processed) = processed {
+                link.icon = Some(processed.src.clone());
             }
+            link_icon_images.push(processed.and_then(|p| p.image));
         }
+        let processed_favicon = assets.take(favicon).map(|processed| processed.src);
+        let app_icons = assets.take(app_icons).map(|_| app_icons_value());
 
-        // Process regular link icons (download and embed external images)
-        let mut processed_links = self.config.links.clone();
-        for link in &mut processed_links {
-            if let Some(ref icon) = link.icon
-                && !icon.is_empty()
-            {
-                match self.process_icon(icon, link_icon_size) {
-                    Ok(processed) => link.icon = Some(processed),
-                    Err(e) => eprintln!("Warning: Failed to process link icon: {}", e),
-                }
+        // The manifest lists the icons, and with `[pwa]` makes the page installable
+        let pwa_settings = &self.config.pwa;
+        let web_manifest = if pwa_settings.enabled {
+            Some(pwa::MANIFEST)
+        } else {
+            app_icons.is_some().then_some(icons::WEB_MANIFEST)
+        };
+        if pwa_settings.enabled && app_icons.is_none() {
+            let warning =
+                Diagnostic::warning("The page has no icons, so browsers won't offer to install it")
+                    .at("pwa.enabled")
+                    .with_help("set `icons.source` to a PNG, JPEG, WebP or GIF image");
+            eprintln!("Warning: {}", warning.message);
+            report.warnings.push(warning);
+        }
+        let share_image = assets.take(share_image).map(|processed| {
+            let variant = processed
+                .image
+                .and_then(|image| image.variants.into_iter().next());
+            match (variant, page_url) {
+                (Some(variant), Some(page_url)) => serde_json::json!({
+                    "url": absolute_url(page_url, &variant.url),
+                    "width": variant.width,
+                    "height": variant.height,
+                }),
+                _ => serde_json::json!({ "url": processed.src }),
             }
+        });
+
+        let mut profile_value =
+            tera::to_value(&processed_profile).context("Failed to serialize profile")?;
+        for (scheme, image) in ["light", "dark"].into_iter().zip(avatar_images) {
+            profile_value[scheme]["avatar_image"] = tera::to_value(image)?;
         }
+        for (scheme, placeholder) in ["light", "dark"].into_iter().zip(background_placeholders) {
+            profile_value[scheme]["background_placeholder"] = tera::to_value(placeholder)?;
+        }
+        for (idx, (image, &social_idx)) in social_icon_images
+            .into_iter()
+            .zip(&social_indices)
+            .enumerate()
+        {
+            let social_value = &mut profile_value["social_links"][idx];
+            social_value["icon_image"] = tera::to_value(image)?;
+            social_value["schedule"] = self.schedule_value(&social_schedules[social_idx], now);
+        }
+        let mut links_value =
+            tera::to_value(&processed_links).context("Failed to serialize links")?;
+        for (idx, (image, &link_idx)) in link_icon_images.into_iter().zip(&link_indices).enumerate()
+        {
+            links_value[idx]["icon_image"] = tera::to_value(image)?;
+            links_value[idx]["schedule"] = self.schedule_value(&link_schedules[link_idx], now);
+        }
+        if let Some(links) = links_value.as_array_mut()
+            && !features.link_sections
+        {
+            links.retain(|link| !is_header(link));
+        }
+        let sections_value = link_sections(&links_value);
 
         // Resolve typography values with theme colors
         let resolved_header = self.config.theme.typography.resolve(
@@ -148,57 +525,166 @@ impl Generator {
         // Create context for CSS rendering
         let mut css_context = TeraContext::new();
         css_context.insert("theme", &self.config.theme);
-        css_context.insert("profile", &processed_profile);
+        css_context.insert("theme_options", &theme_options);
+        if output_mode == OutputMode::Multi {
+            css_context.insert("profile", &stylesheet_profile(&profile_value));
+        } else {
+            css_context.insert("profile", &profile_value);
+        }
         css_context.insert("typography_header", &resolved_header);
         css_context.insert("typography_bio", &resolved_bio);
         css_context.insert("typography_link_title", &resolved_link_title);
         css_context.insert("typography_link_description", &resolved_link_description);
 
-        // Render CSS with variables
-        let rendered_css = tera
-            .render("style.css", &css_context)
-            .context("Failed to render CSS template")?;
+        // Render CSS with variables, parent theme first
+        let rendered_css = stylesheets
+            .iter()
+            .map(|(name, _)| {
+                tera.render(name, &css_context)
+                    .with_context(|| format!("Failed to render CSS template {}", name))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join("\n");
+
+        let cfg = Cfg {
+            minify_css: true,
+            minify_js: true,
+            ..Cfg::default()
+        };
 
-        // Create Tera instance for HTML
+        // In multi-file mode the CSS and JS are linked instead of inlined
+        let mut external_css = None;
+        let mut asset_urls = HashMap::new();
+        if output_mode == OutputMode::Multi {
+            let css = minify_asset("style", &rendered_css, &cfg);
+            let css_url = self.write_asset("css", css.as_bytes())?;
+            asset_files.insert("style.css".to_string(), css_url.clone());
+            asset_urls.insert("css_url", css_url);
+            external_css = Some(css);
+
+            if !js_content.trim().is_empty() {
+                let js = minify_asset("script", &js_content, &cfg);
+                let js_url = self.write_asset("js", js.as_bytes())?;
+                asset_files.insert("script.js".to_string(), js_url.clone());
+                asset_urls.insert("js_url", js_url);
+            }
+        }
+
+        // Create Tera instance for HTML with every template in the theme chain,
+        // so themes can use {% extends %} and {% include %}
         let mut html_tera = Tera::default();
         html_tera
-            .add_raw_template("template.html", &html_template)
+            .add_raw_templates(theme.templates(&self.project_root)?)
             .context("Failed to add HTML template")?;
 
-        // Generate QR code if page_url is provided
-        let qr_code_data = if let Some(ref page_url) = self.config.meta.page_url {
-            if !page_url.is_empty() {
-                Some(self.generate_qr_code(page_url)?)
-            } else {
-                None
+        // Themes compare the mode with lowercase names
+        let mut dark_mode = self.config.dark_mode.clone();
+        dark_mode.mode = dark_mode.mode.to_lowercase();
+        if !features.dark_mode {
+            dark_mode.mode = "disable".to_string();
+        }
+
+        // Generate QR codes if page_url is provided, one per color scheme
+        let mut qr_codes = Vec::new();
+        if let Some(page_url) = page_url
+            && features.qr_share
+        {
+            let settings = &self.config.qr;
+            let logo = settings
+                .logo
+                .then(|| self.image_data(&processed_profile.light.avatar))
+                .flatten();
+            if settings.logo && logo.is_none() {
+                let warning = Diagnostic::warning(
+                    "The QR code logo needs a raster avatar; the QR code has no logo",
+                )
+                .at("qr.logo");
+                eprintln!("Warning: {}", warning.message);
+                report.warnings.push(warning);
             }
-        } else {
-            None
-        };
 
-        // Process favicon (convert local files to data URLs)
-        let favicon_size = Some(self.config.image.favicon_size);
-        let processed_favicon = self.process_favicon(favicon_size)?;
+            let format = QrFormat::parse(&settings.format).unwrap_or(QrFormat::Svg);
+            let size = match format {
+                QrFormat::Svg => settings.size,
+                QrFormat::Png => settings
+                    .size
+                    .checked_mul(QR_PNG_DENSITY)
+                    .context("qr.size is too large")?,
+            };
+            let mut schemes = vec![(false, "qr_code")];
+            if settings.dark.is_some() && dark_mode.mode != "disable" {
+                schemes.push((true, "qr_code.dark"));
+            }
+            for (dark, name) in schemes {
+                let data = qr::render(
+                    page_url,
+                    &settings.style(dark),
+                    format,
+                    size,
+                    logo.as_deref(),
+                )?;
+                let url = self.image_url(format.mime_type(), &data)?;
+                if is_asset_file(&url) {
+                    asset_files.insert(name.to_string(), url.clone());
+                }
+                qr_codes.push(url);
+            }
+        }
 
         // Create context for HTML rendering
         let mut html_context = TeraContext::new();
-        html_context.insert("profile", &processed_profile);
+        html_context.insert("profile", &profile_value);
         html_context.insert("theme", &self.config.theme);
-        html_context.insert("dark_mode", &self.config.dark_mode);
+        html_context.insert("theme_options", &theme_options);
+        html_context.insert("dark_mode", &dark_mode);
 
         // Create a modified meta object with processed favicon
         let mut meta_with_favicon = self.config.meta.clone();
         if let Some(ref favicon_data) = processed_favicon {
             meta_with_favicon.favicon = Some(favicon_data.clone());
         }
+        // A raster favicon is replaced by the icons made from it
+        if app_icons.is_some() && self.config.meta.favicon.as_deref() == Some(app_icon_source) {
+            meta_with_favicon.favicon = None;
+        }
         html_context.insert("meta", &meta_with_favicon);
 
-        html_context.insert("links", &processed_links);
+        html_context.insert("links", &links_value);
+        html_context.insert("sections", &sections_value);
         html_context.insert("css", &rendered_css);
         html_context.insert("js", &js_content);
-        if let Some(ref qr_data) = qr_code_data {
+        for (name, url) in &asset_urls {
+            html_context.insert(*name, url);
+        }
+        if let Some(qr_data) = qr_codes.first() {
             html_context.insert("qr_code_data", qr_data);
+            html_context.insert("qr_code_size", &self.config.qr.size);
+        }
+        if let Some(qr_data) = qr_codes.get(1) {
+            html_context.insert("qr_code_dark_data", qr_data);
+        }
+        if let Some(ref share_image) = share_image {
+            html_context.insert("share_image", share_image);
+        }
+        if let Some(ref app_icons) = app_icons {
+            html_context.insert("app_icons", app_icons);
+        }
+        if let Some(web_manifest) = web_manifest {
+            html_context.insert("web_manifest", web_manifest);
+        }
+        if pwa_settings.enabled {
+            html_context.insert("service_worker", pwa::SERVICE_WORKER);
         }
+        html_context.insert(
+            "json_ld",
+            &self.json_ld(
+                page_url,
+                share_image.as_ref().and_then(|i| i["url"].as_str()),
+                &social_indices,
+                &social_schedules,
+                now,
+            )?,
+        );
 
         // Render final HTML
         let rendered_html = html_tera
@@ -206,11 +692,6 @@ impl Generator {
             .context("Failed to render HTML template")?;
 
         // Minify HTML
-        let cfg = Cfg {
-            minify_css: true,
-            minify_js: true,
-            ..Cfg::default()
-        };
         let original_size = rendered_html.len();
         let minified_html = minify(rendered_html.as_bytes(), &cfg);
         let minified_size = minified_html.len();
@@ -221,192 +702,864 @@ impl Generator {
             "HTML minified: {} → {} bytes ({:.1}% reduction)",
             original_size, minified_size, reduction_percent
         );
+        let mut output_html =
+            String::from_utf8(minified_html).context("Minified HTML is not valid UTF-8")?;
+
+        // Hash the final inline CSS and JS, so this must run after minifying
+        let csp_settings = &self.config.csp;
+        let mut headers = None;
+        if csp_settings.enabled || csp_settings.headers_file {
+            let mut policy = ContentSecurityPolicy::for_page(&output_html);
+            if let Some(ref css) = external_css {
+                policy.allow_stylesheet(css);
+            }
+            policy.allow_all(&manifest.csp_sources(&theme_options));
+            policy.allow_all(&csp_settings.sources);
+            if pwa_settings.enabled {
+                // With `_headers` the worker gets this policy too, and it
+                // fetches the page's files
+                policy.allow("worker-src", "'self'");
+                policy.allow("connect-src", "'self'");
+            }
+            for blocked in &policy.blocked {
+                let warning = Diagnostic::warning(format!(
+                    "{} will be blocked by the Content-Security-Policy",
+                    blocked
+                ));
+                eprintln!("Warning: {}", warning);
+                report.warnings.push(warning);
+            }
+
+            if csp_settings.enabled {
+                output_html = csp::insert_meta(&output_html, &policy);
+            }
+            if csp_settings.headers_file {
+                let mut file = csp::headers_file(&policy);
+                if output_mode == OutputMode::Multi {
+                    // Asset names change with their contents, so they never go stale
+                    file.push_str(&format!(
+                        "/{}/*\n  Cache-Control: public, max-age=31536000, immutable\n",
+                        ASSETS_DIR
+                    ));
+                }
+                headers = Some(file);
+            }
+        }
+
+        // Nothing has been written yet, so a failed check leaves the output
+        // as it was
+        if let Some(ref check) = self.check {
+            check(&report)?;
+        }
 
         // Create output directory if it doesn't exist
         if let Some(parent) = self.output_path.parent() {
             fs::create_dir_all(parent).context("Failed to create output directory")?;
         }
+        self.write_pending()?;
+        if let Some(headers) = headers {
+            let headers_path = self.output_dir().join("_headers");
+            fs::write(&headers_path, headers)
+                .with_context(|| format!("Failed to write {}", headers_path.display()))?;
+            println!("Wrote CSP headers to: {}", headers_path.display());
+        }
+
+        let mut output_files = asset_files.clone();
+        if let Some(name) = web_manifest {
+            self.write_web_manifest(name, app_icons.is_some())?;
+            output_files.insert(name.to_string(), name.to_string());
+        }
+        if app_icons.is_some() {
+            output_files.extend(
+                icons::file_names()
+                    .into_iter()
+                    .map(|name| (name.clone(), name)),
+            );
+        }
+        self.finish_assets(&output_files)?;
 
         // Write output file
-        fs::write(&self.output_path, minified_html).context("Failed to write output file")?;
+        fs::write(&self.output_path, &output_html).context("Failed to write output file")?;
+
+        let mut precache: Vec<String> = web_manifest.iter().map(|m| m.to_string()).collect();
+        if app_icons.is_some() {
+            precache.extend(icons::file_names());
+        }
+        precache.extend(asset_files.into_values().collect::<BTreeSet<_>>());
+        self.write_service_worker(&output_html, precache)?;
 
         println!("Generated page at: {}", self.output_path.display());
-        Ok(())
+        Ok(report)
     }
 
-    fn load_theme_file(&self, filename: &str) -> Result<String> {
-        let file_path = self.theme_path.join(filename);
-        fs::read_to_string(&file_path)
-            .with_context(|| format!("Failed to read theme file: {}", file_path.display()))
+    /// Returns the files and directories that affect the generated output
+    ///
+    /// Used by `genkan serve` to decide when to rebuild. This includes the
+    /// theme directories (including parent themes), the project's
+    /// `templates/` overrides, and every avatar, background, icon or favicon
+    /// that refers to a local file. Files that don't exist yet are included,
+    /// so that adding them triggers a rebuild.
+    pub fn watched_paths(&self) -> Vec<PathBuf> {
+        let profile = &self.config.profile;
+        let mut sources: Vec<&str> = vec![&profile.light.avatar, &profile.dark.avatar];
+        sources.extend(profile.light.background_image.as_deref());
+        sources.extend(profile.dark.background_image.as_deref());
+        sources.extend(profile.social_links.iter().map(|s| s.icon.as_str()));
+        sources.extend(self.config.links.iter().filter_map(|l| l.icon.as_deref()));
+        sources.extend(self.config.meta.favicon.as_deref());
+        sources.extend(self.config.meta.og_image.as_deref());
+        sources.extend(self.config.icons.source.as_deref());
+
+        let mut paths = match self.resolve_theme() {
+            Ok(theme) => theme.paths(),
+            Err(_) => vec![self.theme_path.clone()],
+        };
+        let templates_dir = crate::themes::project_templates_dir(&self.project_root);
+        if templates_dir.is_dir() {
+            paths.push(templates_dir);
+        }
+        paths.extend(
+            sources
+                .into_iter()
+                .filter(|s| !s.is_empty() && !is_remote_or_inline(s))
+                .map(|s| self.resolve_path(s)),
+        );
+        paths
     }
 
-    fn generate_qr_code(&self, url: &str) -> Result<String> {
-        use image::{DynamicImage, ImageFormat};
-        use std::io::Cursor;
-
-        // Generate QR code
-        let code = QrCode::new(url.as_bytes()).context("Failed to create QR code")?;
+    /// Resolves the theme and every theme it extends
+    pub fn resolve_theme(&self) -> Result<ResolvedTheme> {
+        ResolvedTheme::resolve(
+            &self.project_root,
+            &self.config.theme.name,
+            &self.theme_path,
+        )
+    }
 
-        // Render QR code as image
-        let image = code
-            .render::<Luma<u8>>()
-            .min_dimensions(200, 200)
-            .max_dimensions(200, 200)
-            .build();
+    /// Returns the image to use for link previews
+    ///
+    /// This is `meta.og_image`, or else the light mode avatar if it is a
+    /// raster image file or URL.
+    fn share_image_source(&self) -> &str {
+        if let Some(ref og_image) = self.config.meta.og_image {
+            return og_image;
+        }
+        let avatar = &self.config.profile.light.avatar;
+        if self.is_raster_source(avatar) {
+            avatar
+        } else {
+            ""
+        }
+    }
 
-        // Convert to PNG and encode as base64
-        let mut png_data = Vec::new();
-        let dynamic_image = DynamicImage::ImageLuma8(image);
-        dynamic_image
-            .write_to(&mut Cursor::new(&mut png_data), ImageFormat::Png)
-            .context("Failed to encode QR code as PNG")?;
+    /// Returns the image to make the favicon and home screen icons from
+    ///
+    /// This is `icons.source`, or else `meta.favicon` or the light mode
+    /// avatar, whichever is first to be a raster image file or URL.
+    fn app_icon_source(&self) -> &str {
+        if let Some(ref source) = self.config.icons.source {
+            return source;
+        }
+        [
+            self.config.meta.favicon.as_deref().unwrap_or(""),
+            &self.config.profile.light.avatar,
+        ]
+        .into_iter()
+        .find(|source| self.is_raster_source(source))
+        .unwrap_or("")
+    }
 
-        // Create base64 data URL
-        let base64_data =
-            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &png_data);
-        Ok(format!("data:image/png;base64,{}", base64_data))
+    /// Returns true for remote URLs and local files that aren't SVG
+    ///
+    /// Emoji, inline SVG and data URLs are false.
+    fn is_raster_source(&self, source: &str) -> bool {
+        !source.is_empty()
+            && !source.ends_with(".svg")
+            && (is_remote(source)
+                || (!source.starts_with("data:") && self.resolve_path(source).is_file()))
     }
 
-    fn resize_image(&self, image_data: &[u8], target_size: u32) -> Result<Vec<u8>> {
-        use image::{ImageFormat, imageops::FilterType};
-        use std::io::Cursor;
+    /// Builds the web app manifest
+    ///
+    /// Without `[pwa]` it only lists the home screen icons; with it, the
+    /// manifest also sets how the installed page is shown.
+    ///
+    /// # Arguments
+    ///
+    /// * `with_icons` - The icon set was written next to the page
+    fn web_manifest(&self, with_icons: bool) -> Result<String> {
+        let icons: Vec<_> = icons::MANIFEST_ICON_SIZES
+            .iter()
+            .filter(|_| with_icons)
+            .map(|&size| {
+                serde_json::json!({
+                    "src": icons::png_file_name(size),
+                    "sizes": format!("{0}x{0}", size),
+                    "type": "image/png",
+                })
+            })
+            .collect();
+        let pwa_settings = &self.config.pwa;
+        let (short_name, display) = if pwa_settings.enabled {
+            (
+                pwa_settings
+                    .short_name
+                    .as_deref()
+                    .unwrap_or(&self.config.profile.name),
+                pwa_settings.display.as_str(),
+            )
+        } else {
+            (self.config.profile.name.as_str(), "browser")
+        };
+        let background = &self.config.theme.light.background_color;
+        let manifest = serde_json::json!({
+            "name": self.config.meta.title,
+            "short_name": short_name,
+            "description": self.config.meta.description,
+            "start_url": ".",
+            "scope": ".",
+            "display": display,
+            "theme_color": background,
+            "background_color": background,
+            "icons": icons,
+        });
+        serde_json::to_string_pretty(&manifest).context("Failed to serialize web manifest")
+    }
 
-        // Load the image
-        let img =
-            image::load_from_memory(image_data).context("Failed to load image for resizing")?;
+    /// Writes the web app manifest next to the page
+    ///
+    /// The one from the other mode is removed by [`Generator::finish_assets`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `site.webmanifest`, or `manifest.webmanifest` with `[pwa]`
+    /// * `with_icons` - The icon set was written next to the page
+    fn write_web_manifest(&self, name: &str, with_icons: bool) -> Result<()> {
+        let manifest = self.web_manifest(with_icons)? + "\n";
+        cache::write_atomic(&self.output_dir().join(name), manifest.as_bytes())
+    }
 
-        // Get current dimensions
-        let (width, height) = (img.width(), img.height());
+    /// Writes the service worker, or retires one from an earlier build
+    ///
+    /// A worker that browsers have installed keeps serving its cache until
+    /// `sw.js` changes, so without `[pwa]` a worker written by Genkan is
+    /// replaced by one that unregisters itself rather than deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `html` - The page as written
+    /// * `files` - Other files to cache, relative to the page
+    fn write_service_worker(&self, html: &str, files: Vec<String>) -> Result<()> {
+        let path = self.output_dir().join(pwa::SERVICE_WORKER);
+        if !self.config.pwa.enabled {
+            let generated =
+                fs::read_to_string(&path).is_ok_and(|source| pwa::is_generated(&source));
+            if generated {
+                cache::write_atomic(&path, pwa::retired_service_worker().as_bytes())?;
+                println!("Retired the service worker at: {}", path.display());
+            }
+            return Ok(());
+        }
 
-        // If image is already smaller than target size, return original
-        if width <= target_size && height <= target_size {
-            return Ok(image_data.to_vec());
+        // The version covers every cached file, so any change updates the worker
+        let mut hashes = cache::hash_hex(html.as_bytes());
+        for file in &files {
+            let file_path = self.output_dir().join(file);
+            let data = fs::read(&file_path)
+                .with_context(|| format!("Failed to read {}", file_path.display()))?;
+            hashes.push_str(&cache::hash_hex(&data));
         }
+        let version = &cache::hash_hex(hashes.as_bytes())[..16];
 
-        // Calculate new dimensions maintaining aspect ratio
-        let (new_width, new_height) = if width > height {
-            let ratio = target_size as f32 / width as f32;
-            (target_size, (height as f32 * ratio) as u32)
-        } else {
-            let ratio = target_size as f32 / height as f32;
-            ((width as f32 * ratio) as u32, target_size)
+        let page = match self.output_path.file_name().and_then(|name| name.to_str()) {
+            Some("index.html") | None => "./".to_string(),
+            Some(name) => name.to_string(),
         };
+        let precache: Vec<String> = std::iter::once(page).chain(files).collect();
+        cache::write_atomic(&path, pwa::service_worker(version, &precache).as_bytes())?;
+        println!("Wrote service worker to: {}", path.display());
+        Ok(())
+    }
 
-        // Resize the image using Lanczos3 filter for high quality
-        let resized = img.resize(new_width, new_height, FilterType::Lanczos3);
+    /// Returns the indices of the links written to the page at `now`
+    ///
+    /// A header's schedule applies to its whole section, so the links under
+    /// a header that is left out are left out too.
+    fn scheduled_links(
+        &self,
+        links: &[Link],
+        schedules: &[Schedule],
+        now: DateTime<Utc>,
+    ) -> Vec<usize> {
+        let mut section_shown = true;
+        links
+            .iter()
+            .zip(schedules)
+            .enumerate()
+            .filter_map(|(idx, (link, schedule))| {
+                let shown = self.is_shown(schedule, now);
+                if link.link_type.eq_ignore_ascii_case("header") {
+                    section_shown = shown;
+                }
+                (section_shown && shown).then_some(idx)
+            })
+            .collect()
+    }
 
-        // Encode to PNG format
-        let mut output = Vec::new();
-        resized
-            .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
-            .context("Failed to encode resized image")?;
+    /// Returns true if a link with this schedule is written to the page
+    fn is_shown(&self, schedule: &Schedule, now: DateTime<Utc>) -> bool {
+        match schedule.visibility(now) {
+            Visibility::Visible => true,
+            Visibility::Upcoming => {
+                self.at.is_none()
+                    && UpcomingLinks::parse(&self.config.build.upcoming_links)
+                        == Some(UpcomingLinks::Reveal)
+            }
+            Visibility::Expired => false,
+        }
+    }
 
-        Ok(output)
+    /// Returns the `schedule` template value of a link written to the page
+    ///
+    /// Pages built with [`Generator::with_at`] are a snapshot of that
+    /// moment, so their links never change in the browser.
+    fn schedule_value(&self, schedule: &Schedule, now: DateTime<Utc>) -> tera::Value {
+        if self.at.is_some() {
+            return tera::Value::Null;
+        }
+        schedule.value(now)
     }
 
-    fn process_svg_for_inline(&self, svg_data: &[u8]) -> Result<String> {
-        // Convert SVG data to string
-        let mut svg_content =
-            String::from_utf8(svg_data.to_vec()).context("Failed to parse SVG as UTF-8")?;
-
-        // Replace hardcoded colors with currentColor to inherit CSS color
-        use regex::Regex;
-
-        // Remove XML declaration if present
-        let xml_decl_regex =
-            Regex::new(r"<\?xml[^?]*\?>").context("Failed to compile XML declaration regex")?;
-        svg_content = xml_decl_regex.replace_all(&svg_content, "").to_string();
-
-        // Remove or clean up HTML comments
-        let comment_regex =
-            Regex::new(r"<!--[^>]*-->").context("Failed to compile comment regex")?;
-        svg_content = comment_regex.replace_all(&svg_content, "").to_string();
-
-        // Remove fixed width and height attributes from the SVG tag (let CSS control dimensions)
-        let width_regex =
-            Regex::new(r#"\s+width="[^"]*""#).context("Failed to compile width regex")?;
-        svg_content = width_regex.replace_all(&svg_content, "").to_string();
-
-        let height_regex =
-            Regex::new(r#"\s+height="[^"]*""#).context("Failed to compile height regex")?;
-        svg_content = height_regex.replace_all(&svg_content, "").to_string();
-
-        // Replace fill="<color>" with fill="currentColor" (but preserve fill="none")
-        let fill_regex = Regex::new(r#"fill="[^"]*""#).context("Failed to compile fill regex")?;
-        svg_content = fill_regex
-            .replace_all(&svg_content, |caps: &regex::Captures| {
-                // caps.get(0) is guaranteed to exist in replace_all callback
-                let matched = caps.get(0).map(|m| m.as_str()).unwrap_or("");
-                if matched == r#"fill="none""# {
-                    matched.to_string()
-                } else {
-                    r#"fill="currentColor""#.to_string()
-                }
-            })
-            .to_string();
-
-        // Replace stroke="<color>" with stroke="currentColor" (but preserve stroke="none")
-        let stroke_regex =
-            Regex::new(r#"stroke="[^"]*""#).context("Failed to compile stroke regex")?;
-        svg_content = stroke_regex
-            .replace_all(&svg_content, |caps: &regex::Captures| {
-                // caps.get(0) is guaranteed to exist in replace_all callback
-                let matched = caps.get(0).map(|m| m.as_str()).unwrap_or("");
-                if matched == r#"stroke="none""# {
-                    matched.to_string()
-                } else {
-                    r#"stroke="currentColor""#.to_string()
+    /// Builds the schema.org `ProfilePage` JSON-LD block for the page
+    ///
+    /// # Arguments
+    ///
+    /// * `page_url` - The `meta.page_url`, if set
+    /// * `image` - Absolute URL of the share image, if any
+    /// * `social_indices` - Config indices of the social links on the page
+    /// * `social_schedules` - Schedule of each social link, so links
+    ///   written hidden until they are revealed are left out
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` with JSON that is safe to place in a `<script>` element
+    /// * `Err(anyhow::Error)` if the JSON couldn't be serialized
+    fn json_ld(
+        &self,
+        page_url: Option<&str>,
+        image: Option<&str>,
+        social_indices: &[usize],
+        social_schedules: &[Schedule],
+        now: DateTime<Utc>,
+    ) -> Result<String> {
+        let profile = &self.config.profile;
+        let mut person = serde_json::json!({
+            "@type": "Person",
+            "name": profile.name,
+        });
+        if !profile.bio.is_empty() {
+            person["description"] = profile.bio.clone().into();
+        }
+        if let Some(image) = image {
+            person["image"] = image.into();
+        }
+        let same_as: Vec<&str> = social_indices
+            .iter()
+            .filter(|&&idx| social_schedules[idx].visibility(now) == Visibility::Visible)
+            .map(|&idx| profile.social_links[idx].url.as_str())
+            .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+            .collect();
+        if !same_as.is_empty() {
+            person["sameAs"] = same_as.into();
+        }
+
+        let mut page = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "ProfilePage",
+            "name": self.config.meta.title,
+            "description": self.config.meta.description,
+            "mainEntity": person,
+        });
+        if let Some(page_url) = page_url {
+            page["url"] = page_url.into();
+        }
+        let json = serde_json::to_string(&page).context("Failed to serialize JSON-LD")?;
+        // A `</script>` in a bio would otherwise end the element
+        Ok(json.replace("</", "<\\/"))
+    }
+
+    /// Renders the QR code for a URL as a standalone image
+    ///
+    /// Used by `genkan qr`. With `qr.logo`, the logo is made from the light
+    /// mode avatar, which is downloaded if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - Text to encode, usually `meta.page_url`
+    /// * `format` - SVG or PNG
+    /// * `size` - Width and height in pixels
+    /// * `dark` - Use the `[qr.dark]` colours
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` with the encoded image
+    /// * `Err(anyhow::Error)` if the avatar couldn't be loaded or the code
+    ///   couldn't be rendered
+    pub fn render_qr_code(
+        &self,
+        url: &str,
+        format: QrFormat,
+        size: u32,
+        dark: bool,
+    ) -> Result<Vec<u8>> {
+        let logo = if self.config.qr.logo {
+            let avatar = &self.config.profile.light.avatar;
+            let data = if is_remote(avatar) {
+                let mut log = JobLog::default();
+                let data = self.cache().fetch(avatar, &mut log);
+                log.print();
+                data?
+            } else {
+                self.image_data(avatar)
+                    .map_or_else(|| fs::read(self.resolve_path(avatar)), Ok)
+                    .with_context(|| format!("Failed to read avatar: {}", avatar))?
+            };
+            if images::mime_type(&data).is_none() {
+                anyhow::bail!("The QR code logo needs a raster avatar");
+            }
+            Some(data)
+        } else {
+            None
+        };
+        qr::render(
+            url,
+            &self.config.qr.style(dark),
+            format,
+            size,
+            logo.as_deref(),
+        )
+    }
+
+    /// Returns the bytes of an image from its data URL or asset file
+    ///
+    /// # Returns
+    ///
+    /// The image, or `None` for anything else, such as inline SVG, emoji or
+    /// remote URLs
+    fn image_data(&self, url: &str) -> Option<Vec<u8>> {
+        let data = if let Some(rest) = url.strip_prefix("data:") {
+            let (_, encoded) = rest.split_once(";base64,")?;
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded).ok()?
+        } else if is_asset_file(url) {
+            fs::read(self.output_dir().join(url)).ok()?
+        } else {
+            return None;
+        };
+        images::mime_type(&data).map(|_| data)
+    }
+
+    /// Downloads and processes every image in the graph in parallel
+    ///
+    /// Each remote URL is downloaded once, then every image is resized and
+    /// embedded. Progress and warnings are printed in the order the images
+    /// were added, regardless of which job finished first.
+    ///
+    /// Warnings and images left as remote URLs are added to `report`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ProcessedAssets)` with a result for every slot
+    /// * `Err(anyhow::Error)` if a background image or favicon couldn't be read
+    fn process_assets(
+        &self,
+        graph: &AssetGraph,
+        report: &mut BuildReport,
+    ) -> Result<ProcessedAssets> {
+        let workers = self.workers();
+        let cache = self.cache();
+
+        let urls = graph.remote_urls();
+        let fetched = jobs::run_parallel(&urls, workers, |url| {
+            let mut log = JobLog::default();
+            let data = cache.fetch(url, &mut log).map_err(|e| e.to_string());
+            (data, log)
+        });
+        let mut download_logs = Vec::with_capacity(urls.len());
+        let mut downloads = Downloads::new();
+        for (url, (data, log)) in urls.iter().zip(fetched) {
+            downloads.insert(url.clone(), data);
+            download_logs.push(Some(log));
+        }
+
+        let processed = jobs::run_parallel(&graph.jobs, workers, |job| {
+            let mut log = JobLog::default();
+            let result = self.process_job(job, &downloads, &mut log);
+            (result, log)
+        });
+        let (mut results, mut job_logs): (Vec<_>, Vec<_>) = processed
+            .into_iter()
+            .map(|(result, log)| (Some(result), Some(log)))
+            .unzip();
+
+        // Print logs as if the images had been processed one by one
+        let mut resolved: Vec<Option<std::result::Result<Option<ProcessedImage>, String>>> =
+            vec![None; graph.jobs.len()];
+        let mut values = Vec::with_capacity(graph.slots.len());
+        for slot in &graph.slots {
+            let job_idx = slot.job;
+            let job = &graph.jobs[job_idx];
+            let url_idx = urls.iter().position(|url| *url == job.source);
+            let logs = [
+                url_idx.and_then(|idx| download_logs[idx].take()),
+                job_logs[job_idx].take(),
+            ];
+            for log in logs.iter().flatten() {
+                log.print();
+                for warning in log.warnings() {
+                    report
+                        .warnings
+                        .push(Diagnostic::warning(warning).at(slot.path.clone()));
                 }
-            })
-            .to_string();
-
-        // Also handle style attributes with fill/stroke
-        let style_fill_regex =
-            Regex::new(r"fill:\s*([^;]+)").context("Failed to compile style fill regex")?;
-        svg_content = style_fill_regex
-            .replace_all(&svg_content, |caps: &regex::Captures| {
-                // caps.get(1) should exist if the regex matched
-                let color = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-                if color.trim() == "none" {
-                    format!("fill:{}", color)
-                } else {
-                    "fill:currentColor".to_string()
+            }
+
+            if let Some(result) = results[job_idx].take() {
+                resolved[job_idx] = Some(match result {
+                    Ok(value) => Ok(value),
+                    Err(e) if job.kind == AssetKind::Icon => Err(e.to_string()),
+                    Err(e) => return Err(e),
+                });
+            }
+            let value = match &resolved[job_idx] {
+                Some(Ok(value)) => value.clone(),
+                Some(Err(e)) => {
+                    let warning =
+                        Diagnostic::warning(format!("Failed to process {}: {}", slot.label, e))
+                            .at(slot.path.clone());
+                    eprintln!("Warning: {}", warning.message);
+                    report.warnings.push(warning);
+                    None
                 }
-            })
-            .to_string();
-
-        let style_stroke_regex =
-            Regex::new(r"stroke:\s*([^;]+)").context("Failed to compile style stroke regex")?;
-        svg_content = style_stroke_regex
-            .replace_all(&svg_content, |caps: &regex::Captures| {
-                // caps.get(1) should exist if the regex matched
-                let color = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-                if color.trim() == "none" {
-                    format!("stroke:{}", color)
-                } else {
-                    "stroke:currentColor".to_string()
+                None => None,
+            };
+
+            // Without a value the original is kept, except for the share image,
+            // which is left out
+            let embedded = match value {
+                Some(ref value) => value.src.as_str(),
+                None if matches!(job.kind, AssetKind::ShareImage | AssetKind::AppIcons) => "",
+                None => job.source.as_str(),
+            };
+            if is_remote(embedded) {
+                report.hotlinked.push(Hotlink {
+                    path: slot.path.clone(),
+                    url: embedded.to_string(),
+                });
+            }
+            values.push(value);
+        }
+
+        // Name image files after the config key they came from
+        let mut files = BTreeMap::new();
+        for (slot, value) in graph.slots.iter().zip(&values) {
+            for (density, url) in value.iter().flat_map(ProcessedImage::urls) {
+                if !is_asset_file(url) {
+                    continue;
                 }
-            })
-            .to_string();
+                let name = match density {
+                    1 => slot.path.clone(),
+                    _ => format!("{}@{}x", slot.path, density),
+                };
+                files.insert(name, url.to_string());
+            }
+        }
+
+        Ok(ProcessedAssets { values, files })
+    }
+
+    /// Writes the asset manifest and removes files from previous builds
+    ///
+    /// The manifest lists every file the build wrote besides the page and
+    /// the service worker. Asset file names are content hashes, so every
+    /// change to an asset leaves the old file behind, and icons or a web
+    /// manifest stay behind once they are turned off. Only files listed in
+    /// the previous manifest are removed, so the user's own files are safe
+    /// when the output directory is the project itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - Logical names (e.g. `style.css`, `links[0].icon`,
+    ///   `favicon.ico`) mapped to the files written by this build, relative
+    ///   to the page
+    fn finish_assets(&self, files: &BTreeMap<String, String>) -> Result<()> {
+        let dir = self.output_dir();
+        let manifest_path = dir.join(ASSET_MANIFEST);
+        let previous: BTreeMap<String, String> = fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|manifest| serde_json::from_str(&manifest).ok())
+            .unwrap_or_default();
+        if files.is_empty() {
+            if manifest_path.exists() {
+                fs::remove_file(&manifest_path)
+                    .with_context(|| format!("Failed to remove {}", manifest_path.display()))?;
+            }
+        } else {
+            let manifest = serde_json::to_string_pretty(files)
+                .context("Failed to serialize asset manifest")?;
+            fs::write(&manifest_path, manifest + "\n")
+                .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+        }
+
+        let keep: HashSet<&str> = files.values().map(String::as_str).collect();
+        // Never follow an edited manifest out of the output directory
+        let stale = previous
+            .values()
+            .filter(|url| is_output_file(url) && !keep.contains(url.as_str()));
+        for url in stale {
+            let path = dir.join(url);
+            if path.is_file() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+
+        let assets_dir = self.assets_dir();
+        let written = keep.iter().filter(|url| is_asset_file(url)).count();
+        if written > 0 {
+            println!(
+                "Wrote {} asset file(s) to {}",
+                written,
+                assets_dir.display()
+            );
+        } else if previous.values().any(|url| is_asset_file(url)) {
+            // Only a directory an earlier build wrote assets to; `remove_dir`
+            // leaves it alone if anything else is in it
+            let _ = fs::remove_dir(&assets_dir);
+        }
+        Ok(())
+    }
+
+    /// Returns the directory the page is written to
+    fn output_dir(&self) -> &Path {
+        self.output_path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Returns the directory asset files are written to
+    fn assets_dir(&self) -> PathBuf {
+        self.output_dir().join(ASSETS_DIR)
+    }
+
+    /// Adds an asset file named after a hash of its contents
+    ///
+    /// The file is written by [`Generator::write_pending`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` with the URL of the file relative to the page
+    fn write_asset(&self, extension: &str, data: &[u8]) -> Result<String> {
+        let name = format!("{}.{}", &cache::hash_hex(data)[..16], extension);
+        let path = self.assets_dir().join(&name);
+        if !path.exists() {
+            self.stage(path, data.to_vec());
+        }
+        Ok(format!("{}/{}", ASSETS_DIR, name))
+    }
+
+    /// Holds back a file until the build has passed its check
+    fn stage(&self, path: PathBuf, data: Vec<u8>) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path, data);
+    }
+
+    /// Writes the asset and icon files made during the build
+    fn write_pending(&self) -> Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        for (path, data) in pending {
+            cache::write_atomic(&path, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the URL the page uses for an image
+    ///
+    /// Images are embedded as data URLs, or written to the assets directory
+    /// in multi-file mode or with `image.embed = false`.
+    fn image_url(&self, mime_type: &str, data: &[u8]) -> Result<String> {
+        if self.config.image.embed && self.output_mode() == OutputMode::Single {
+            return Ok(data_url(mime_type, data));
+        }
+        self.write_asset(images::extension(mime_type), data)
+    }
+
+    /// Produces an image for each configured pixel density
+    ///
+    /// Densities beyond the resolution of the source are skipped, since
+    /// they would only repeat the previous image.
+    ///
+    /// # Arguments
+    ///
+    /// * `image_data` - The original image bytes
+    /// * `target_size` - Size of the 1x image
+    /// * `densities` - Pixel densities to produce, e.g. `[1, 2, 3]`
+    /// * `what` - Description used in the build log
+    /// * `log` - Receives progress messages
+    fn responsive_image(
+        &self,
+        image_data: &[u8],
+        target_size: Option<u32>,
+        densities: &[u32],
+        what: &str,
+        log: &mut JobLog,
+    ) -> Result<ProcessedImage> {
+        let mut densities = densities.to_vec();
+        densities.sort_unstable();
+        densities.dedup();
+        let source_size = images::dimensions(image_data).map(|(w, h)| w.max(h));
+
+        let mut variants: Vec<ImageVariant> = Vec::new();
+        for density in densities {
+            let size = target_size.map(|size| size * density);
+            if let Some(previous) = variants.last()
+                && let (Some(source), Some(previous_size)) = (source_size, previous.target_size)
+                && source <= previous_size
+            {
+                break;
+            }
+            let (data, mime_type) = self.compress_image(image_data, size, self.format(), log)?;
+            log.info(compression_message(
+                what,
+                image_data.len(),
+                &data,
+                mime_type,
+                size,
+            ));
+            let (width, height) = images::dimensions(&data).unwrap_or((0, 0));
+            variants.push(ImageVariant {
+                url: self.image_url(mime_type, &data)?,
+                density,
+                width,
+                height,
+                target_size: size,
+            });
+        }
+
+        let src = variants
+            .first()
+            .map(|v| v.url.clone())
+            .context("No image densities configured")?;
+        let srcset = (variants.len() > 1).then(|| {
+            variants
+                .iter()
+                .map(|v| format!("{} {}x", v.url, v.density))
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+        Ok(ProcessedImage {
+            src: src.clone(),
+            image: Some(ResponsiveImage {
+                src,
+                srcset,
+                variants,
+            }),
+            placeholder: None,
+        })
+    }
+
+    /// Returns the number of worker threads for asset processing
+    fn workers(&self) -> usize {
+        self.jobs
+            .or(self.config.build.jobs)
+            .unwrap_or_else(jobs::default_workers)
+    }
+
+    fn process_job(
+        &self,
+        job: &AssetJob,
+        downloads: &Downloads,
+        log: &mut JobLog,
+    ) -> Result<Option<ProcessedImage>> {
+        match job.kind {
+            AssetKind::Icon => self
+                .process_icon(&job.source, job.target_size, downloads, log)
+                .map(Some),
+            AssetKind::Background => self.process_background(&job.source, downloads, log),
+            AssetKind::Favicon => Ok(self
+                .process_favicon(&job.source, job.target_size, downloads, log)?
+                .map(ProcessedImage::from)),
+            AssetKind::ShareImage => {
+                self.process_share_image(&job.source, job.target_size, downloads, log)
+            }
+            AssetKind::AppIcons => self.process_app_icons(&job.source, downloads, log),
+        }
+    }
+
+    /// Resizes and re-encodes an image with the `[image]` quality setting
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((Vec<u8>, &str))` with the image bytes and their MIME type
+    /// * `Err(anyhow::Error)` if the image couldn't be decoded or encoded
+    fn compress_image(
+        &self,
+        image_data: &[u8],
+        target_size: Option<u32>,
+        format: OutputFormat,
+        log: &mut JobLog,
+    ) -> Result<(Vec<u8>, &'static str)> {
+        let settings = &self.config.image;
+        let variant = format!(
+            "{}-{}-{}",
+            target_size.map_or("full".to_string(), |size| size.to_string()),
+            format.name(),
+            settings.quality
+        );
+        let data = self.cache().resized(
+            image_data,
+            &variant,
+            || images::optimize(image_data, target_size, format, settings.quality),
+            log,
+        )?;
+        let mime_type = images::mime_type(&data).context("Unknown image format")?;
+        Ok((data, mime_type))
+    }
+
+    /// Returns the output format for avatars, icons and the favicon
+    fn format(&self) -> OutputFormat {
+        OutputFormat::parse(&self.config.image.format).unwrap_or(OutputFormat::Auto)
+    }
+
+    /// Returns the output format for background images
+    fn background_format(&self) -> OutputFormat {
+        self.config
+            .image
+            .background_format
+            .as_deref()
+            .and_then(OutputFormat::parse)
+            .unwrap_or_else(|| self.format())
+    }
+
+    fn process_svg_for_inline(&self, svg_data: &[u8]) -> Result<String> {
+        // Strip active content and recolor to currentColor to inherit CSS color
+        let svg_content = crate::svg::sanitize_svg(svg_data)?;
 
         // Return with special marker to indicate this is inline SVG
         Ok(format!("__INLINE_SVG__{}", svg_content))
     }
 
-    fn download_and_embed_image(&self, url: &str, target_size: Option<u32>) -> Result<String> {
-        // Download the image
-        let response = ureq::get(url)
-            .set("User-Agent", "Mozilla/5.0 (compatible; Genkan/1.0)")
-            .timeout(std::time::Duration::from_secs(10))
-            .call()
-            .with_context(|| format!("Failed to download image from: {}", url))?;
-
-        // Read response body
-        let mut image_data = Vec::new();
-        response
-            .into_reader()
-            .read_to_end(&mut image_data)
-            .with_context(|| format!("Failed to read image data from: {}", url))?;
+    fn embed_downloaded_image(
+        &self,
+        url: &str,
+        target_size: Option<u32>,
+        densities: &[u32],
+        downloads: &Downloads,
+        log: &mut JobLog,
+    ) -> Result<ProcessedImage> {
+        let image_data = match downloads.get(url) {
+            Some(Ok(data)) => data.as_slice(),
+            Some(Err(e)) => anyhow::bail!("{}", e),
+            None => anyhow::bail!("{} was not downloaded", url),
+        };
 
         // Check if it's an SVG (don't resize SVGs)
         let is_svg = url.ends_with(".svg")
@@ -416,34 +1569,19 @@ impl Generator {
 
         // If it's an SVG, process it for inline rendering
         if is_svg {
-            return self.process_svg_for_inline(&image_data);
-        }
-
-        // Resize if target_size is specified
-        let final_data = if let Some(size) = target_size {
-            match self.resize_image(&image_data, size) {
-                Ok(resized) => {
-                    println!(
-                        "Compressed image from {} to {} bytes (target size: {}px)",
-                        image_data.len(),
-                        resized.len(),
-                        size
-                    );
-                    resized
-                }
-                Err(e) => {
-                    eprintln!("Warning: Failed to resize image: {}. Using original.", e);
-                    image_data
-                }
-            }
-        } else {
-            image_data
-        };
+            return self
+                .process_svg_for_inline(image_data)
+                .map(ProcessedImage::from);
+        }
 
-        // Determine MIME type - use PNG for resized images
-        let mime_type = if target_size.is_some() {
-            "image/png"
-        } else if url.ends_with(".jpg")
+        // Resize and pick the smallest encoding
+        match self.responsive_image(image_data, target_size, densities, "image", log) {
+            Ok(image) => return Ok(image),
+            Err(e) => log.warn(format!("Failed to resize image: {}. Using original.", e)),
+        }
+
+        // Determine MIME type from the URL
+        let mime_type = if url.ends_with(".jpg")
             || url.ends_with(".jpeg")
             || url.contains(".jpg?")
             || url.contains(".jpeg?")
@@ -460,37 +1598,42 @@ impl Generator {
             "image/png"
         };
 
-        // Encode as base64
-        let base64_data =
-            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &final_data);
-        Ok(format!("data:{};base64,{}", mime_type, base64_data))
+        Ok(self.image_url(mime_type, image_data)?.into())
     }
 
-    fn process_icon(&self, icon: &str, target_size: Option<u32>) -> Result<String> {
+    fn process_icon(
+        &self,
+        icon: &str,
+        target_size: Option<u32>,
+        downloads: &Downloads,
+        log: &mut JobLog,
+    ) -> Result<ProcessedImage> {
         // If it's already a data URL, return as-is
         if icon.starts_with("data:") {
-            return Ok(icon.to_string());
+            return Ok(icon.to_string().into());
         }
 
-        // If it's an external URL, download and embed it
-        if icon.starts_with("http://") || icon.starts_with("https://") || icon.starts_with("//") {
-            match self.download_and_embed_image(icon, target_size) {
+        let densities = &self.config.image.densities;
+
+        // If it's an external URL, embed the downloaded image
+        if is_remote(icon) {
+            match self.embed_downloaded_image(icon, target_size, densities, downloads, log) {
                 Ok(embedded) => {
-                    println!("Embedded external icon: {}", icon);
+                    log.info(format!("Embedded external icon: {}", icon));
                     return Ok(embedded);
                 }
                 Err(e) => {
-                    eprintln!(
-                        "Warning: Failed to download icon '{}': {}. Using original URL.",
+                    log.warn(format!(
+                        "Failed to download icon '{}': {}. Using original URL.",
                         icon, e
-                    );
-                    return Ok(icon.to_string());
+                    ));
+                    return Ok(icon.to_string().into());
                 }
             }
         }
 
         // If it's a local file path, read and convert to data URL
-        let file_path = PathBuf::from(icon);
+        let file_path = self.resolve_path(icon);
         if file_path.exists() {
             let file_data = fs::read(&file_path)
                 .with_context(|| format!("Failed to read icon file: {}", file_path.display()))?;
@@ -499,89 +1642,365 @@ impl Generator {
 
             // If it's an SVG, process it for inline rendering
             if is_svg {
-                return self.process_svg_for_inline(&file_data);
-            }
-
-            // Resize local files too if target_size is specified
-            let final_data = if let Some(size) = target_size {
-                match self.resize_image(&file_data, size) {
-                    Ok(resized) => {
-                        println!(
-                            "Compressed local icon from {} to {} bytes (target size: {}px)",
-                            file_data.len(),
-                            resized.len(),
-                            size
-                        );
-                        resized
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Failed to resize icon: {}. Using original.", e);
-                        file_data
-                    }
-                }
-            } else {
-                file_data
+                return self
+                    .process_svg_for_inline(&file_data)
+                    .map(ProcessedImage::from);
+            }
+
+            // Resize local files too and pick the smallest encoding
+            match self.responsive_image(&file_data, target_size, densities, "local icon", log) {
+                Ok(image) => return Ok(image),
+                Err(e) => log.warn(format!("Failed to resize icon: {}. Using original.", e)),
+            }
+
+            let mime_type = match file_path.extension().and_then(|e| e.to_str()) {
+                Some("png") => "image/png",
+                Some("jpg") | Some("jpeg") => "image/jpeg",
+                Some("gif") => "image/gif",
+                Some("webp") => "image/webp",
+                Some("ico") => "image/x-icon",
+                _ => "image/png",
             };
+            return Ok(self.image_url(mime_type, &file_data)?.into());
+        }
 
-            let mime_type = if target_size.is_some() {
-                "image/png"
-            } else {
-                match file_path.extension().and_then(|e| e.to_str()) {
-                    Some("png") => "image/png",
-                    Some("jpg") | Some("jpeg") => "image/jpeg",
-                    Some("gif") => "image/gif",
-                    Some("webp") => "image/webp",
-                    Some("ico") => "image/x-icon",
-                    _ => "image/png",
+        // Not a URL or file path - probably an emoji or text, return as-is
+        Ok(icon.to_string().into())
+    }
+
+    /// Downloads or reads a background image, scales it down and embeds it
+    ///
+    /// Images wider than `image.background_max_width` are scaled down and
+    /// re-encoded in `image.background_format`. SVGs and (possibly animated)
+    /// GIFs are embedded as-is.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(ProcessedImage))` with the image URL and, if enabled, a placeholder
+    /// * `Ok(None)` to keep the original value (missing file or failed download)
+    /// * `Err(anyhow::Error)` if a local file couldn't be read
+    fn process_background(
+        &self,
+        background_image: &str,
+        downloads: &Downloads,
+        log: &mut JobLog,
+    ) -> Result<Option<ProcessedImage>> {
+        if background_image.is_empty() || background_image.starts_with("data:") {
+            return Ok(None);
+        }
+
+        let data = if is_remote(background_image) {
+            match downloads.get(background_image) {
+                Some(Ok(data)) => data.clone(),
+                Some(Err(e)) => {
+                    log.warn(format!(
+                        "Failed to download background image '{}': {}. Using original URL.",
+                        background_image, e
+                    ));
+                    return Ok(None);
                 }
-            };
+                None => return Ok(None),
+            }
+        } else {
+            let file_path = self.resolve_path(background_image);
+            if !file_path.exists() {
+                log.warn(format!(
+                    "Background image not found: {}",
+                    file_path.display()
+                ));
+                return Ok(None);
+            }
+            fs::read(&file_path).with_context(|| {
+                format!("Failed to read background image: {}", file_path.display())
+            })?
+        };
 
-            let base64_data =
-                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &final_data);
-            return Ok(format!("data:{};base64,{}", mime_type, base64_data));
+        let mime_type = images::mime_type(&data);
+        let is_svg = mime_type.is_none()
+            && (background_image.ends_with(".svg") || background_image.contains(".svg?"));
+        if is_svg || mime_type == Some("image/gif") {
+            let mime_type = mime_type.unwrap_or("image/svg+xml");
+            return Ok(Some(self.image_url(mime_type, &data)?.into()));
         }
 
-        // Not a URL or file path - probably an emoji or text, return as-is
-        Ok(icon.to_string())
+        let target_size = self.background_target_size(&data);
+        let src = match self.compress_image(&data, target_size, self.background_format(), log) {
+            Ok((compressed, mime_type)) => {
+                log.info(compression_message(
+                    "background image",
+                    data.len(),
+                    &compressed,
+                    mime_type,
+                    target_size,
+                ));
+                self.image_url(mime_type, &compressed)?
+            }
+            Err(e) => {
+                log.warn(format!(
+                    "Failed to compress background image: {}. Using original.",
+                    e
+                ));
+                self.image_url(mime_type.unwrap_or("image/png"), &data)?
+            }
+        };
+
+        let mut processed = ProcessedImage::from(src);
+        if self.config.image.background_placeholder {
+            match self
+                .cache()
+                .resized(&data, "placeholder", || images::placeholder(&data), log)
+            {
+                Ok(placeholder) => {
+                    let mime_type = images::mime_type(&placeholder).unwrap_or("image/jpeg");
+                    log.info(format!(
+                        "Created background placeholder ({} bytes)",
+                        placeholder.len()
+                    ));
+                    processed.placeholder = Some(data_url(mime_type, &placeholder));
+                }
+                Err(e) => log.warn(format!("Failed to create background placeholder: {}", e)),
+            }
+        }
+        Ok(Some(processed))
     }
 
-    fn process_favicon(&self, target_size: Option<u32>) -> Result<Option<String>> {
-        let favicon = match &self.config.meta.favicon {
-            Some(f) if !f.is_empty() => f,
-            _ => return Ok(None),
+    /// Downloads or reads the share image, scales it down and writes it to
+    /// the assets directory
+    ///
+    /// Link previews are fetched by other servers, so the image is always a
+    /// file, even when everything else is embedded.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(ProcessedImage))` with the image file, or the original URL
+    ///   if a remote image couldn't be downloaded
+    /// * `Ok(None)` if there is no usable image
+    /// * `Err(anyhow::Error)` if a local file couldn't be read or the image
+    ///   couldn't be written
+    fn process_share_image(
+        &self,
+        source: &str,
+        target_size: Option<u32>,
+        downloads: &Downloads,
+        log: &mut JobLog,
+    ) -> Result<Option<ProcessedImage>> {
+        let data = if is_remote(source) {
+            match downloads.get(source) {
+                Some(Ok(data)) => data.clone(),
+                Some(Err(e)) => {
+                    log.warn(format!(
+                        "Failed to download share image '{}': {}. Using original URL.",
+                        source, e
+                    ));
+                    return Ok(Some(source.to_string().into()));
+                }
+                None => return Ok(None),
+            }
+        } else {
+            let file_path = self.resolve_path(source);
+            if !file_path.is_file() {
+                log.warn(format!("Share image not found: {}", file_path.display()));
+                return Ok(None);
+            }
+            fs::read(&file_path)
+                .with_context(|| format!("Failed to read share image: {}", file_path.display()))?
         };
 
+        let size = target_size.unwrap_or(SHARE_IMAGE_SIZE);
+        let quality = self.config.image.quality;
+        let compressed = match self.cache().resized(
+            &data,
+            &format!("share-{}-{}", size, quality),
+            || images::share_image(&data, size, quality),
+            log,
+        ) {
+            Ok(compressed) => compressed,
+            Err(e) => {
+                log.warn(format!(
+                    "Failed to process share image '{}': {}. Link previews need a PNG, JPEG, WebP or GIF image.",
+                    source, e
+                ));
+                return Ok(None);
+            }
+        };
+        let mime_type = images::mime_type(&compressed).unwrap_or("image/jpeg");
+        log.info(compression_message(
+            "share image",
+            data.len(),
+            &compressed,
+            mime_type,
+            Some(size),
+        ));
+
+        let (width, height) = images::dimensions(&compressed).unwrap_or((0, 0));
+        let url = self.write_asset(images::extension(mime_type), &compressed)?;
+        Ok(Some(ProcessedImage {
+            src: url.clone(),
+            image: Some(ResponsiveImage {
+                src: url.clone(),
+                srcset: None,
+                variants: vec![ImageVariant {
+                    url,
+                    density: 1,
+                    width,
+                    height,
+                    target_size: Some(size),
+                }],
+            }),
+            placeholder: None,
+        }))
+    }
+
+    /// Writes `favicon.ico` and the PNG icons next to the page
+    ///
+    /// Browsers and phones request these by their well-known names, so they
+    /// are never embedded or hashed.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Local path or URL of a raster image
+    /// * `downloads` - Downloaded images by URL
+    /// * `log` - Receives progress messages and warnings
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(ProcessedImage))` with the URL of `favicon.ico`
+    /// * `Ok(None)` if the source couldn't be read or isn't a raster image
+    /// * `Err(anyhow::Error)` if a local file couldn't be read
+    fn process_app_icons(
+        &self,
+        source: &str,
+        downloads: &Downloads,
+        log: &mut JobLog,
+    ) -> Result<Option<ProcessedImage>> {
+        let data = if is_remote(source) {
+            match downloads.get(source) {
+                Some(Ok(data)) => data.clone(),
+                Some(Err(e)) => {
+                    log.warn(format!(
+                        "Failed to download icon source '{}': {}",
+                        source, e
+                    ));
+                    return Ok(None);
+                }
+                None => return Ok(None),
+            }
+        } else {
+            let file_path = self.resolve_path(source);
+            if !file_path.is_file() {
+                log.warn(format!("Icon source not found: {}", file_path.display()));
+                return Ok(None);
+            }
+            fs::read(&file_path)
+                .with_context(|| format!("Failed to read icon source: {}", file_path.display()))?
+        };
+
+        // iOS shows transparent areas of home screen icons as black
+        let background =
+            qr::parse_color(&self.config.theme.light.background_color).unwrap_or([255, 255, 255]);
+        let mut files = Vec::new();
+        let sizes = icons::FAVICON_SIZES
+            .iter()
+            .chain([&icons::APPLE_TOUCH_ICON_SIZE])
+            .chain(icons::MANIFEST_ICON_SIZES);
+        for &size in sizes {
+            let (variant, background) = if size == icons::APPLE_TOUCH_ICON_SIZE {
+                let [r, g, b] = background;
+                (
+                    format!("icon-{}-{:02x}{:02x}{:02x}", size, r, g, b),
+                    Some(background),
+                )
+            } else {
+                (format!("icon-{}", size), None)
+            };
+            let icon = self.cache().resized(
+                &data,
+                &variant,
+                || icons::png_icon(&data, size, background),
+                log,
+            );
+            files.push((icons::png_file_name(size), icon));
+        }
+        let ico = self
+            .cache()
+            .resized(&data, "favicon-ico", || icons::favicon_ico(&data), log);
+        files.push((icons::FAVICON_ICO.to_string(), ico));
+
+        let mut made = Vec::with_capacity(files.len());
+        for (name, icon) in files {
+            match icon {
+                Ok(icon) => made.push((name, icon)),
+                Err(e) => {
+                    log.warn(format!(
+                        "Failed to make icons from '{}': {}. Icons need a PNG, JPEG, WebP or GIF image.",
+                        source, e
+                    ));
+                    return Ok(None);
+                }
+            }
+        }
+        log.info(format!("Made {} icons from {}", made.len(), source));
+        let dir = self.output_dir();
+        for (name, icon) in made {
+            self.stage(dir.join(name), icon);
+        }
+        Ok(Some(icons::FAVICON_ICO.to_string().into()))
+    }
+
+    /// Returns the size that limits a background image to
+    /// `image.background_max_width`, or `None` if it is narrow enough
+    ///
+    /// Resizing fits images into a square, so portrait images need a
+    /// target taller than the maximum width.
+    fn background_target_size(&self, data: &[u8]) -> Option<u32> {
+        let max_width = self.config.image.background_max_width;
+        let (width, height) = images::dimensions(data)?;
+        if width <= max_width {
+            return None;
+        }
+        let scaled_height = u64::from(height) * u64::from(max_width) / u64::from(width);
+        Some(max_width.max(scaled_height as u32))
+    }
+
+    fn process_favicon(
+        &self,
+        favicon: &str,
+        target_size: Option<u32>,
+        downloads: &Downloads,
+        log: &mut JobLog,
+    ) -> Result<Option<String>> {
+        if favicon.is_empty() {
+            return Ok(None);
+        }
+
         // If it's already a data URL, return as-is
         if favicon.starts_with("data:") {
-            return Ok(Some(favicon.clone()));
+            return Ok(Some(favicon.to_string()));
         }
 
-        // If it's an external URL, download and embed it
-        if favicon.starts_with("http://")
-            || favicon.starts_with("https://")
-            || favicon.starts_with("//")
-        {
-            match self.download_and_embed_image(favicon, target_size) {
+        // If it's an external URL, embed the downloaded image
+        if is_remote(favicon) {
+            match self.embed_downloaded_image(favicon, target_size, &[1], downloads, log) {
                 Ok(embedded) => {
-                    println!("Embedded favicon: {}", favicon);
-                    return Ok(Some(embedded));
+                    log.info(format!("Embedded favicon: {}", favicon));
+                    return Ok(Some(embedded.src));
                 }
                 Err(e) => {
-                    eprintln!(
-                        "Warning: Failed to download favicon '{}': {}. Using original URL.",
+                    log.warn(format!(
+                        "Failed to download favicon '{}': {}. Using original URL.",
                         favicon, e
-                    );
-                    return Ok(Some(favicon.clone()));
+                    ));
+                    return Ok(Some(favicon.to_string()));
                 }
             }
         }
 
         // It's a local file path - read and convert to data URL
-        let file_path = PathBuf::from(favicon);
+        let file_path = self.resolve_path(favicon);
 
         // Check if file exists
         if !file_path.exists() {
-            eprintln!("Warning: Favicon file not found: {}", favicon);
+            log.warn(format!("Favicon file not found: {}", favicon));
             return Ok(None);
         }
 
@@ -592,161 +2011,406 @@ impl Generator {
         let is_svg = matches!(file_path.extension().and_then(|e| e.to_str()), Some("svg"));
         let is_ico = matches!(file_path.extension().and_then(|e| e.to_str()), Some("ico"));
 
-        // Resize if target_size is specified and it's not SVG or ICO
-        let final_data = if let Some(size) = target_size {
-            if is_svg || is_ico {
-                file_data
-            } else {
-                match self.resize_image(&file_data, size) {
-                    Ok(resized) => {
-                        println!(
-                            "Compressed favicon from {} to {} bytes (target size: {}px)",
-                            file_data.len(),
-                            resized.len(),
-                            size
-                        );
-                        resized
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Failed to resize favicon: {}. Using original.", e);
-                        file_data
-                    }
+        // Resize and pick the smallest encoding, unless it's SVG or ICO
+        if !is_svg && !is_ico {
+            match self.compress_image(&file_data, target_size, self.format(), log) {
+                Ok((compressed, mime_type)) => {
+                    log.info(compression_message(
+                        "favicon",
+                        file_data.len(),
+                        &compressed,
+                        mime_type,
+                        target_size,
+                    ));
+                    return Ok(Some(self.image_url(mime_type, &compressed)?));
                 }
+                Err(e) => log.warn(format!("Failed to resize favicon: {}. Using original.", e)),
             }
-        } else {
-            file_data
-        };
+        }
 
         // Determine MIME type from extension
-        let mime_type = if target_size.is_some() && !is_svg && !is_ico {
-            "image/png"
-        } else {
-            match file_path.extension().and_then(|e| e.to_str()) {
-                Some("ico") => "image/x-icon",
-                Some("png") => "image/png",
-                Some("jpg") | Some("jpeg") => "image/jpeg",
-                Some("gif") => "image/gif",
-                Some("svg") => "image/svg+xml",
-                Some("webp") => "image/webp",
-                _ => {
-                    eprintln!("Warning: Unknown favicon file type, defaulting to image/x-icon");
-                    "image/x-icon"
-                }
+        let mime_type = match file_path.extension().and_then(|e| e.to_str()) {
+            Some("ico") => "image/x-icon",
+            Some("png") => "image/png",
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("svg") => "image/svg+xml",
+            Some("webp") => "image/webp",
+            _ => {
+                log.warn("Unknown favicon file type, defaulting to image/x-icon");
+                "image/x-icon"
             }
         };
 
-        // Encode as base64
-        let base64_data =
-            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &final_data);
-        Ok(Some(format!("data:{};base64,{}", mime_type, base64_data)))
+        Ok(Some(self.image_url(mime_type, &file_data)?))
     }
 }
 
-/// Downloads a theme from the default repository
-///
-/// Clones the Genkan repository and extracts the specified theme to the local themes directory.
-///
-/// # Arguments
-///
-/// * `theme_name` - Name of the theme to download
-///
-/// # Returns
+/// Problems found while generating the site
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// Warnings printed during the build, pointing at the config where possible
+    pub warnings: Diagnostics,
+    /// Images that are still loaded from a remote URL instead of embedded
+    pub hotlinked: Vec<Hotlink>,
+}
+
+/// An image the page loads from its original URL
+#[derive(Debug, Clone)]
+pub struct Hotlink {
+    /// Config key path of the image, e.g. `links[3].icon`
+    pub path: String,
+    pub url: String,
+}
+
+/// Downloaded image data by URL, or the download error
+type Downloads = HashMap<String, std::result::Result<Vec<u8>, String>>;
+
+/// What an image is used for, which decides how it's processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AssetKind {
+    /// Avatars and icons: downloaded, resized and embedded; failures are warnings
+    Icon,
+    /// Background images: downloaded, limited in width and embedded with an
+    /// optional placeholder; failures are warnings
+    Background,
+    /// The favicon: like an icon, but ICO and SVG files aren't resized
+    Favicon,
+    /// The image for link previews: always written to a file, since other
+    /// servers fetch it; failures are warnings
+    ShareImage,
+    /// The source of the favicon and home screen icons, which are written
+    /// next to the page under fixed names; failures are warnings
+    AppIcons,
+}
+
+/// A unique image to process
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AssetJob {
+    kind: AssetKind,
+    source: String,
+    target_size: Option<u32>,
+}
+
+/// Images referenced from the config
 ///
-/// * `Ok(PathBuf)` with the path to the downloaded theme directory
-/// * `Err(anyhow::Error)` if the download failed
-fn download_theme(theme_name: &str) -> Result<PathBuf> {
-    println!(
-        "Theme '{}' not found locally. Downloading from repository...",
-        theme_name
-    );
-
-    // Create themes directory if it doesn't exist
-    let themes_dir = PathBuf::from("themes");
-    fs::create_dir_all(&themes_dir).context("Failed to create themes directory")?;
-
-    // Create a temporary directory for cloning
-    let temp_dir = std::env::temp_dir().join(format!("genkan-theme-{}", theme_name));
-
-    // Remove temp dir if it exists from a previous run
-    let _ = fs::remove_dir_all(&temp_dir);
-
-    // Clone the repository
-    println!("Cloning repository...");
-    let _repo = git2::Repository::clone(DEFAULT_THEME_REPO, &temp_dir)
-        .context("Failed to clone theme repository")?;
-
-    // Check if the theme exists in the cloned repo
-    let theme_source_path = temp_dir.join("themes").join(theme_name);
-    if !theme_source_path.exists() || !theme_source_path.is_dir() {
-        let _ = fs::remove_dir_all(&temp_dir);
-        anyhow::bail!(
-            "Theme '{}' not found in the default repository. Available themes should be in the themes/ directory.",
-            theme_name
-        );
+/// Each place an image is used gets a slot. Slots using the same source at
+/// the same size share one job, and jobs using the same URL share one
+/// download.
+#[derive(Debug, Default)]
+struct AssetGraph {
+    jobs: Vec<AssetJob>,
+    /// Places images are used, in config order
+    slots: Vec<AssetSlot>,
+}
+
+/// A place in the config where an image is used
+#[derive(Debug)]
+struct AssetSlot {
+    /// Index of the job producing the image
+    job: usize,
+    /// Config key path, e.g. `links[3].icon`
+    path: String,
+    /// Description used in warnings, e.g. `link icon`
+    label: &'static str,
+}
+
+impl AssetGraph {
+    /// Adds a slot for an image, or returns `None` if `source` is empty
+    fn add(
+        &mut self,
+        kind: AssetKind,
+        source: &str,
+        target_size: Option<u32>,
+        path: String,
+        label: &'static str,
+    ) -> Option<usize> {
+        if source.is_empty() {
+            return None;
+        }
+        let job = AssetJob {
+            kind,
+            source: source.to_string(),
+            target_size,
+        };
+        let job_idx = match self.jobs.iter().position(|j| *j == job) {
+            Some(idx) => idx,
+            None => {
+                self.jobs.push(job);
+                self.jobs.len() - 1
+            }
+        };
+        self.slots.push(AssetSlot {
+            job: job_idx,
+            path,
+            label,
+        });
+        Some(self.slots.len() - 1)
     }
 
-    // Copy the theme to local themes directory
-    let theme_dest_path = themes_dir.join(theme_name);
-    copy_dir_recursive(&theme_source_path, &theme_dest_path)
-        .context("Failed to copy theme files")?;
+    /// Returns the unique remote URLs to download, in slot order
+    fn remote_urls(&self) -> Vec<String> {
+        let mut urls: Vec<String> = Vec::new();
+        for slot in &self.slots {
+            let job = &self.jobs[slot.job];
+            if is_remote(&job.source) && !urls.contains(&job.source) {
+                urls.push(job.source.clone());
+            }
+        }
+        urls
+    }
+}
 
-    // Clean up temporary directory
-    let _ = fs::remove_dir_all(&temp_dir);
+/// Results of [`Generator::process_assets`], by slot
+struct ProcessedAssets {
+    values: Vec<Option<ProcessedImage>>,
+    /// Image files written, by logical name
+    files: BTreeMap<String, String>,
+}
 
-    println!("Theme '{}' downloaded successfully!", theme_name);
-    Ok(theme_dest_path)
+impl ProcessedAssets {
+    /// Returns the processed value for a slot, or `None` to keep the original
+    fn take(&mut self, slot: Option<usize>) -> Option<ProcessedImage> {
+        slot.and_then(|idx| self.values[idx].take())
+    }
 }
 
-/// Recursively copies a directory
-fn copy_dir_recursive(src: &PathBuf, dst: &PathBuf) -> Result<()> {
-    fs::create_dir_all(dst)?;
+/// A processed image and the URL the page refers to it by
+#[derive(Debug, Clone)]
+struct ProcessedImage {
+    /// Value used in place of the original, e.g. a data URL or inline SVG
+    src: String,
+    /// Set for raster images, which can have several densities
+    image: Option<ResponsiveImage>,
+    /// Data URL of a blurred copy to show while a background image loads
+    placeholder: Option<String>,
+}
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let path = entry.path();
-        let dest_path = dst.join(entry.file_name());
+impl ProcessedImage {
+    /// Returns the URL of each density of the image
+    fn urls(&self) -> Vec<(u32, &str)> {
+        match self.image {
+            Some(ref image) => image
+                .variants
+                .iter()
+                .map(|variant| (variant.density, variant.url.as_str()))
+                .collect(),
+            None => vec![(1, self.src.as_str())],
+        }
+    }
+}
 
-        if path.is_dir() {
-            copy_dir_recursive(&path, &dest_path)?;
-        } else {
-            fs::copy(&path, &dest_path)?;
+impl From<String> for ProcessedImage {
+    fn from(src: String) -> Self {
+        Self {
+            src,
+            image: None,
+            placeholder: None,
         }
     }
+}
 
-    Ok(())
+/// A raster image as exposed to templates, e.g. `link.icon_image`
+#[derive(Debug, Clone, Serialize)]
+struct ResponsiveImage {
+    /// URL of the 1x image
+    src: String,
+    /// Value for the `srcset` attribute, if there is more than one density
+    srcset: Option<String>,
+    variants: Vec<ImageVariant>,
 }
 
-/// Finds the path to a theme directory
-///
-/// Searches for the theme in multiple locations:
-/// 1. `themes/{theme_name}`
-/// 2. `./themes/{theme_name}`
-/// 3. `../themes/{theme_name}`
-///
-/// If the theme is not found locally, attempts to download it from the default repository.
-///
-/// # Arguments
-///
-/// * `theme_name` - Name of the theme to find
+/// One pixel density of a responsive image
+#[derive(Debug, Clone, Serialize)]
+struct ImageVariant {
+    url: String,
+    density: u32,
+    width: u32,
+    height: u32,
+    #[serde(skip)]
+    target_size: Option<u32>,
+}
+
+/// Describes the result of compressing an image for the build log
+fn compression_message(
+    what: &str,
+    original_len: usize,
+    compressed: &[u8],
+    mime_type: &str,
+    target_size: Option<u32>,
+) -> String {
+    let format = mime_type.trim_start_matches("image/");
+    match target_size {
+        Some(size) => format!(
+            "Compressed {} from {} to {} bytes as {} (target size: {}px)",
+            what,
+            original_len,
+            compressed.len(),
+            format,
+            size
+        ),
+        None => format!(
+            "Compressed {} from {} to {} bytes as {}",
+            what,
+            original_len,
+            compressed.len(),
+            format
+        ),
+    }
+}
+
+/// Groups links into the `sections` template variable
 ///
-/// # Returns
+/// Each `header` link starts a section holding the links after it. Links
+/// before the first header form a section without a header. Every section
+/// gets an `id` made from its title, which themes use to remember whether
+/// a visitor collapsed it.
+fn link_sections(links: &tera::Value) -> tera::Value {
+    let mut sections: Vec<serde_json::Value> = Vec::new();
+    let mut ids = HashSet::new();
+    for link in links.as_array().into_iter().flatten() {
+        if is_header(link) {
+            let title = link["title"].as_str().unwrap_or_default();
+            let slug = title
+                .to_lowercase()
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join("-");
+            let base = if slug.is_empty() {
+                format!("section-{}", sections.len() + 1)
+            } else {
+                slug
+            };
+            let mut id = base.clone();
+            let mut suffix = 2;
+            while !ids.insert(id.clone()) {
+                id = format!("{}-{}", base, suffix);
+                suffix += 1;
+            }
+            let collapsed = link["collapsed"].as_bool().unwrap_or(false);
+            sections.push(serde_json::json!({
+                "id": id,
+                "header": link,
+                "collapsible": collapsed || link["collapsible"].as_bool().unwrap_or(false),
+                "collapsed": collapsed,
+                "links": [],
+            }));
+            continue;
+        }
+        if sections.is_empty() {
+            sections.push(serde_json::json!({ "header": null, "links": [] }));
+        }
+        if let Some(section_links) = sections
+            .last_mut()
+            .and_then(|section| section["links"].as_array_mut())
+        {
+            section_links.push(link.clone());
+        }
+    }
+    sections.into()
+}
+
+/// Returns true for links with `link_type = "header"`
+fn is_header(link: &tera::Value) -> bool {
+    link["link_type"]
+        .as_str()
+        .is_some_and(|link_type| link_type.eq_ignore_ascii_case("header"))
+}
+
+/// Returns the `app_icons` template variable for the icons written by
+/// [`Generator::process_app_icons`]
+fn app_icons_value() -> serde_json::Value {
+    let size = |size: u32| format!("{0}x{0}", size);
+    let favicons: Vec<_> = icons::FAVICON_SIZES
+        .iter()
+        .map(|&s| serde_json::json!({ "url": icons::png_file_name(s), "sizes": size(s) }))
+        .collect();
+    serde_json::json!({
+        "favicon": {
+            "url": icons::FAVICON_ICO,
+            "sizes": icons::ICO_SIZES.iter().map(|&s| size(s)).collect::<Vec<_>>().join(" "),
+        },
+        "icons": favicons,
+        "apple_touch_icon": icons::png_file_name(icons::APPLE_TOUCH_ICON_SIZE),
+    })
+}
+
+/// Encodes image bytes as a base64 data URL
+fn data_url(mime_type: &str, data: &[u8]) -> String {
+    let base64_data = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data);
+    format!("data:{};base64,{}", mime_type, base64_data)
+}
+
+/// Minifies a standalone stylesheet or script
 ///
-/// * `Ok(PathBuf)` with the path to the theme directory
-/// * `Err(anyhow::Error)` if the theme was not found
-pub fn find_theme_path(theme_name: &str) -> Result<PathBuf> {
-    // Try multiple locations for theme directory
-    let possible_paths = vec![
-        PathBuf::from(format!("themes/{}", theme_name)),
-        PathBuf::from(format!("./themes/{}", theme_name)),
-        PathBuf::from(format!("../themes/{}", theme_name)),
-    ];
+/// minify-html only works on documents, so the content is minified as the
+/// only element of one and unwrapped again.
+fn minify_asset(tag: &str, content: &str, cfg: &Cfg) -> String {
+    let document = format!("<{tag}>{content}</{tag}>");
+    let minified = String::from_utf8_lossy(&minify(document.as_bytes(), cfg)).into_owned();
+    let start = minified.find('>').map_or(0, |idx| idx + 1);
+    let end = minified.rfind("</").unwrap_or(minified.len()).max(start);
+    minified[start..end].to_string()
+}
 
-    for path in possible_paths {
-        if path.exists() && path.is_dir() {
-            return Ok(path);
+/// Returns the profile for rendering a linked stylesheet
+///
+/// URLs in a stylesheet are relative to the stylesheet, which lives in the
+/// assets directory, so asset URLs lose their `assets/` prefix.
+fn stylesheet_profile(profile: &tera::Value) -> tera::Value {
+    let mut profile = profile.clone();
+    for scheme in ["light", "dark"] {
+        for key in ["avatar", "background_image"] {
+            if let Some(url) = profile[scheme][key].as_str()
+                && is_asset_file(url)
+            {
+                profile[scheme][key] = url[ASSETS_DIR.len() + 1..].into();
+            }
         }
     }
+    profile
+}
+
+/// Resolves a URL relative to the page against `meta.page_url`
+///
+/// A page URL ending in a file name (`https://example.com/index.html`) is
+/// resolved against its directory; any other is treated as a directory.
+fn absolute_url(page_url: &str, url: &str) -> String {
+    let page_url = page_url.split(['?', '#']).next().unwrap_or_default();
+    let authority = page_url.find("//").map_or(0, |idx| idx + 2);
+    let path_start = page_url[authority..]
+        .find('/')
+        .map_or(page_url.len(), |idx| authority + idx);
+    let dir = match page_url.rsplit_once('/') {
+        Some((dir, file)) if dir.len() >= path_start && file.contains('.') => dir,
+        _ => page_url.trim_end_matches('/'),
+    };
+    format!("{}/{}", dir, url)
+}
+
+/// Returns true for URLs of files in the assets directory
+fn is_asset_file(url: &str) -> bool {
+    url.strip_prefix(ASSETS_DIR)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Returns true for files the build writes: a file next to the page or in
+/// the assets directory
+fn is_output_file(url: &str) -> bool {
+    let name = url
+        .strip_prefix(ASSETS_DIR)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .unwrap_or(url);
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.starts_with('.')
+}
+
+/// Returns true for remote URLs, including protocol-relative ones
+fn is_remote(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://") || source.starts_with("//")
+}
 
-    // Theme not found locally, attempt to download it
-    download_theme(theme_name)
+/// Returns true for sources that are not local files (URLs and data URLs)
+fn is_remote_or_inline(source: &str) -> bool {
+    source.starts_with("data:") || is_remote(source)
 }
@@ -0,0 +1,127 @@
+//! Favicons and home screen icons
+//!
+//! Browsers and phones each look for their own icon: desktop browsers use
+//! `favicon.ico` or a small PNG, iOS uses `apple-touch-icon.png` for home
+//! screen bookmarks, and Android reads the icons listed in the web app
+//! manifest. Without them, a bookmarked page shows a screenshot or a letter.
+//!
+//! Every icon is made from one source image. Images that aren't square are
+//! centred on a transparent square first, so nothing is cropped.
+
+use anyhow::{Context, Result};
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::imageops::FilterType;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, Rgba, RgbaImage};
+
+/// File name of the multi-resolution favicon
+pub const FAVICON_ICO: &str = "favicon.ico";
+
+/// File name of the web app manifest that lists the icons
+pub const WEB_MANIFEST: &str = "site.webmanifest";
+
+/// Sizes stored in `favicon.ico`
+pub const ICO_SIZES: &[u32] = &[16, 32, 48];
+
+/// Sizes of the PNG favicons for browser tabs
+pub const FAVICON_SIZES: &[u32] = &[16, 32];
+
+/// Size of the icon iOS uses for home screen bookmarks
+pub const APPLE_TOUCH_ICON_SIZE: u32 = 180;
+
+/// Sizes of the icons listed in the web app manifest
+pub const MANIFEST_ICON_SIZES: &[u32] = &[192, 512];
+
+/// Returns the file name of a PNG icon
+pub fn png_file_name(size: u32) -> String {
+    if size == APPLE_TOUCH_ICON_SIZE {
+        "apple-touch-icon.png".to_string()
+    } else if FAVICON_SIZES.contains(&size) {
+        format!("favicon-{0}x{0}.png", size)
+    } else {
+        format!("icon-{}.png", size)
+    }
+}
+
+/// Returns the file names of every icon, without the manifest
+pub fn file_names() -> Vec<String> {
+    let mut names = vec![FAVICON_ICO.to_string()];
+    names.extend(
+        FAVICON_SIZES
+            .iter()
+            .chain([&APPLE_TOUCH_ICON_SIZE])
+            .chain(MANIFEST_ICON_SIZES)
+            .map(|&size| png_file_name(size)),
+    );
+    names
+}
+
+/// Scales an image to a square PNG icon
+///
+/// # Arguments
+///
+/// * `image_data` - The source image bytes
+/// * `size` - Width and height in pixels
+/// * `background` - Colour to fill transparent areas with, for platforms
+///   that show them black (such as iOS)
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` with the PNG
+/// * `Err(anyhow::Error)` if the image couldn't be decoded or encoded
+pub fn png_icon(image_data: &[u8], size: u32, background: Option<[u8; 3]>) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(image_data).context("Failed to load icon source")?;
+    let icon = square(&img, size, background).to_rgba8();
+    // Opaque icons are stored without an alpha channel
+    let (pixels, color) = if icon.pixels().all(|p| p[3] == 255) {
+        (
+            DynamicImage::ImageRgba8(icon).to_rgb8().into_raw(),
+            ExtendedColorType::Rgb8,
+        )
+    } else {
+        (icon.into_raw(), ExtendedColorType::Rgba8)
+    };
+    let mut png = Vec::new();
+    PngEncoder::new_with_quality(&mut png, CompressionType::Best, PngFilter::Adaptive)
+        .write_image(&pixels, size, size, color)
+        .context("Failed to encode icon as PNG")?;
+    Ok(png)
+}
+
+/// Builds a `favicon.ico` with a PNG image for each of [`ICO_SIZES`]
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` with the ICO file
+/// * `Err(anyhow::Error)` if the image couldn't be decoded or encoded
+pub fn favicon_ico(image_data: &[u8]) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(image_data).context("Failed to load icon source")?;
+    let frames = ICO_SIZES
+        .iter()
+        .map(|&size| {
+            let icon = square(&img, size, None).to_rgba8();
+            IcoFrame::as_png(icon.as_raw(), size, size, ExtendedColorType::Rgba8)
+                .context("Failed to encode favicon image")
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut ico = Vec::new();
+    IcoEncoder::new(&mut ico)
+        .encode_images(&frames)
+        .context("Failed to encode favicon.ico")?;
+    Ok(ico)
+}
+
+/// Scales an image to fit a square and centres it there
+fn square(img: &DynamicImage, size: u32, background: Option<[u8; 3]>) -> DynamicImage {
+    let scaled = img.resize(size, size, FilterType::Lanczos3).to_rgba8();
+    let fill = match background {
+        Some([r, g, b]) => Rgba([r, g, b, 255]),
+        None => Rgba([0, 0, 0, 0]),
+    };
+    let mut canvas = RgbaImage::from_pixel(size, size, fill);
+    let x = (size - scaled.width()) / 2;
+    let y = (size - scaled.height()) / 2;
+    image::imageops::overlay(&mut canvas, &scaled, i64::from(x), i64::from(y));
+    DynamicImage::ImageRgba8(canvas)
+}
@@ -0,0 +1,264 @@
+//! Resizing and re-encoding of embedded images
+//!
+//! Every raster image is embedded as a data URL, so its encoded size goes
+//! straight into the page. Images are scaled down to their target size and
+//! then encoded in one or more formats, keeping whichever result is smallest:
+//!
+//! - `auto` tries JPEG (at the configured quality), lossless WebP and PNG for
+//!   opaque images, and lossless WebP and PNG for images with transparency.
+//!   Photos usually end up as JPEG and flat graphics as WebP or PNG.
+//! - `webp`, `jpeg` and `png` force a single format. JPEG can't store
+//!   transparency, so transparent images are encoded as WebP or PNG instead.
+//!
+//! If none of the encodings is smaller than the original file, the original
+//! bytes are kept.
+
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, ImageFormat};
+use std::io::Cursor;
+
+/// Maximum width and height of placeholders in pixels
+const PLACEHOLDER_SIZE: u32 = 32;
+
+/// Gaussian blur applied to placeholders, in placeholder pixels
+const PLACEHOLDER_BLUR: f32 = 1.5;
+
+/// JPEG quality of placeholders; they are blurred anyway
+const PLACEHOLDER_QUALITY: u8 = 60;
+
+/// Values accepted for `image.format`
+pub const FORMATS: &[&str] = &["auto", "webp", "jpeg", "png"];
+
+/// Format to encode embedded images in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Auto,
+    WebP,
+    Jpeg,
+    Png,
+}
+
+impl OutputFormat {
+    /// Parses an `image.format` value (case-insensitive, `jpg` is accepted)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "webp" => Some(Self::WebP),
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+
+    /// Returns the config name of the format
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::WebP => "webp",
+            Self::Jpeg => "jpeg",
+            Self::Png => "png",
+        }
+    }
+}
+
+/// Returns the MIME type of an encoded image, detected from its contents
+pub fn mime_type(data: &[u8]) -> Option<&'static str> {
+    match image::guess_format(data).ok()? {
+        ImageFormat::Png => Some("image/png"),
+        ImageFormat::Jpeg => Some("image/jpeg"),
+        ImageFormat::WebP => Some("image/webp"),
+        ImageFormat::Gif => Some("image/gif"),
+        ImageFormat::Ico => Some("image/x-icon"),
+        ImageFormat::Avif => Some("image/avif"),
+        ImageFormat::Bmp => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+/// Returns the file extension for an image MIME type
+pub fn extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        "image/x-icon" => "ico",
+        "image/avif" => "avif",
+        "image/bmp" => "bmp",
+        "image/svg+xml" => "svg",
+        _ => "png",
+    }
+}
+
+/// Returns the width and height of an encoded image without decoding it
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Resizes an image to fit within `target_size` and re-encodes it
+///
+/// # Arguments
+///
+/// * `image_data` - The original image bytes
+/// * `target_size` - Maximum width and height in pixels, or `None` to keep the dimensions
+/// * `format` - Format to encode in
+/// * `quality` - JPEG quality from 1 to 100
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` with the smallest encoding, or the original bytes if that
+///   is smaller and can be shown by browsers
+/// * `Err(anyhow::Error)` if the image couldn't be decoded or encoded
+pub fn optimize(
+    image_data: &[u8],
+    target_size: Option<u32>,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(image_data).context("Failed to load image for resizing")?;
+    let resized = match target_size {
+        Some(size) => resize(&img, size),
+        None => img,
+    };
+
+    let transparent = has_transparency(&resized);
+    let candidates: &[OutputFormat] = match format {
+        OutputFormat::Auto => &[OutputFormat::Jpeg, OutputFormat::WebP, OutputFormat::Png],
+        OutputFormat::Jpeg if transparent => &[OutputFormat::WebP, OutputFormat::Png],
+        _ => std::slice::from_ref(&format),
+    };
+
+    let mut best: Option<Vec<u8>> = None;
+    for &candidate in candidates {
+        if candidate == OutputFormat::Jpeg && transparent {
+            continue;
+        }
+        let encoded = encode(&resized, candidate, transparent, quality)?;
+        if best.as_ref().is_none_or(|b| encoded.len() < b.len()) {
+            best = Some(encoded);
+        }
+    }
+    let best = best.context("No output format can store this image")?;
+
+    // Keep the original if re-encoding doesn't help, unless browsers can't
+    // show it (e.g. TIFF or BMP)
+    let original_usable = matches!(
+        mime_type(image_data),
+        Some("image/png" | "image/jpeg" | "image/webp" | "image/gif")
+    );
+    if original_usable && image_data.len() <= best.len() {
+        return Ok(image_data.to_vec());
+    }
+    Ok(best)
+}
+
+/// Resizes an image for link previews
+///
+/// Not every site that unfurls links reads WebP, so the image is always
+/// encoded as JPEG, or as PNG if it has transparency.
+///
+/// # Arguments
+///
+/// * `image_data` - The original image bytes
+/// * `target_size` - Maximum width and height in pixels
+/// * `quality` - JPEG quality from 1 to 100
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` with the encoded image
+/// * `Err(anyhow::Error)` if the image couldn't be decoded or encoded
+pub fn share_image(image_data: &[u8], target_size: u32, quality: u8) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(image_data).context("Failed to load image")?;
+    let resized = resize(&img, target_size);
+    let transparent = has_transparency(&resized);
+    let format = if transparent {
+        OutputFormat::Png
+    } else {
+        OutputFormat::Jpeg
+    };
+    encode(&resized, format, transparent, quality)
+}
+
+/// Creates a tiny blurred copy of an image to show while it loads
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` with a JPEG (or lossless WebP for transparent images) of
+///   at most 32px, which is a few hundred bytes
+/// * `Err(anyhow::Error)` if the image couldn't be decoded or encoded
+pub fn placeholder(image_data: &[u8]) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(image_data).context("Failed to load image")?;
+    let small = img
+        .thumbnail(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE)
+        .blur(PLACEHOLDER_BLUR);
+    let transparent = has_transparency(&small);
+    let format = if transparent {
+        OutputFormat::WebP
+    } else {
+        OutputFormat::Jpeg
+    };
+    encode(&small, format, transparent, PLACEHOLDER_QUALITY)
+}
+
+/// Scales an image down to fit within `target_size`, keeping the aspect ratio
+///
+/// Images already smaller than the target are returned unchanged.
+fn resize(img: &DynamicImage, target_size: u32) -> DynamicImage {
+    let (width, height) = (img.width(), img.height());
+    if width <= target_size && height <= target_size {
+        return img.clone();
+    }
+
+    let (new_width, new_height) = if width > height {
+        let ratio = target_size as f32 / width as f32;
+        (target_size, ((height as f32 * ratio) as u32).max(1))
+    } else {
+        let ratio = target_size as f32 / height as f32;
+        (((width as f32 * ratio) as u32).max(1), target_size)
+    };
+
+    // Lanczos3 gives the best quality for downscaling
+    img.resize(new_width, new_height, FilterType::Lanczos3)
+}
+
+fn has_transparency(img: &DynamicImage) -> bool {
+    img.color().has_alpha() && img.to_rgba8().pixels().any(|p| p[3] < 255)
+}
+
+fn encode(
+    img: &DynamicImage,
+    format: OutputFormat,
+    transparent: bool,
+    quality: u8,
+) -> Result<Vec<u8>> {
+    // Opaque images are stored without an alpha channel
+    let (pixels, color) = if transparent {
+        (img.to_rgba8().into_raw(), ExtendedColorType::Rgba8)
+    } else {
+        (img.to_rgb8().into_raw(), ExtendedColorType::Rgb8)
+    };
+    let (width, height) = (img.width(), img.height());
+
+    let mut output = Vec::new();
+    match format {
+        OutputFormat::Jpeg => JpegEncoder::new_with_quality(&mut output, quality.clamp(1, 100))
+            .write_image(&pixels, width, height, color)
+            .context("Failed to encode image as JPEG")?,
+        OutputFormat::WebP => WebPEncoder::new_lossless(&mut output)
+            .write_image(&pixels, width, height, color)
+            .context("Failed to encode image as WebP")?,
+        OutputFormat::Png | OutputFormat::Auto => {
+            PngEncoder::new_with_quality(&mut output, CompressionType::Best, PngFilter::Adaptive)
+                .write_image(&pixels, width, height, color)
+                .context("Failed to encode image as PNG")?
+        }
+    }
+    Ok(output)
+}
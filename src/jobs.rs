@@ -0,0 +1,109 @@
+//! Parallel job execution with ordered output
+//!
+//! Asset processing is mostly waiting on the network, so the generator runs
+//! it on a pool of worker threads. Jobs write their progress messages and
+//! warnings to a [`JobLog`] instead of printing them, and the caller prints
+//! the logs in job order afterwards. The output is therefore the same no
+//! matter how many workers ran or which job finished first.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Messages produced by a single job
+#[derive(Debug, Default)]
+pub struct JobLog {
+    messages: Vec<(bool, String)>,
+}
+
+impl JobLog {
+    /// Records a progress message
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.messages.push((false, message.into()));
+    }
+
+    /// Records a warning
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.messages.push((true, message.into()));
+    }
+
+    /// Returns the recorded warnings
+    pub fn warnings(&self) -> impl Iterator<Item = &str> {
+        self.messages
+            .iter()
+            .filter(|(warning, _)| *warning)
+            .map(|(_, message)| message.as_str())
+    }
+
+    /// Prints the messages in the order they were recorded
+    ///
+    /// Progress goes to stdout and warnings to stderr, like the rest of the
+    /// build output.
+    pub fn print(&self) {
+        for (warning, message) in &self.messages {
+            if *warning {
+                eprintln!("Warning: {}", message);
+            } else {
+                println!("{}", message);
+            }
+        }
+    }
+}
+
+/// Returns the default number of workers: one per available CPU
+pub fn default_workers() -> usize {
+    thread::available_parallelism().map_or(4, |n| n.get())
+}
+
+/// Runs `job` for every item on up to `workers` threads
+///
+/// # Arguments
+///
+/// * `items` - Inputs for the jobs
+/// * `workers` - Maximum number of threads; `1` runs the jobs on the current thread
+/// * `job` - Work to do for each item
+///
+/// # Returns
+///
+/// The results in the same order as `items`
+pub fn run_parallel<T, R, F>(items: &[T], workers: usize, job: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = workers.clamp(1, items.len().max(1));
+    if workers == 1 {
+        return items.iter().map(job).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let (job, next) = (&job, &next);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(idx) else {
+                            break;
+                        };
+                        done.push((idx, job(item)));
+                    }
+                    done
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
@@ -3,5 +3,20 @@
 //! This library provides the core functionality for Genkan, a static site generator
 //! for creating Linktree-like pages.
 
+pub mod cache;
 pub mod config;
+pub mod csp;
+pub mod diagnostics;
 pub mod generator;
+pub mod icons;
+pub mod images;
+pub mod jobs;
+pub mod manifest;
+pub mod migrate;
+pub mod pwa;
+pub mod qr;
+pub mod schedule;
+pub mod security;
+pub mod server;
+pub mod svg;
+pub mod themes;
@@ -20,9 +20,14 @@
 //! ```
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use genkan::{config, generator};
-use std::path::PathBuf;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{Args, Parser, Subcommand};
+use genkan::cache::AssetCache;
+use genkan::diagnostics::{Diagnostics, SourceFile};
+use genkan::manifest::ThemeManifest;
+use genkan::qr::QrFormat;
+use genkan::{config, generator, migrate, schedule, server, themes};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "genkan")]
@@ -44,6 +49,30 @@ enum Commands {
         /// Output directory
         #[arg(short, long, default_value = "output")]
         output: PathBuf,
+
+        #[command(flatten)]
+        options: BuildOptions,
+    },
+    /// Serve the site locally, rebuilding and reloading on changes
+    Serve {
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+
+        /// Output directory
+        #[arg(short, long, default_value = "output")]
+        output: PathBuf,
+
+        /// Address to bind the server to
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+
+        #[command(flatten)]
+        options: BuildOptions,
     },
     /// Initialize a new Genkan project
     Init {
@@ -57,14 +86,193 @@ enum Commands {
         #[arg(short, long, default_value = "config.toml")]
         config: PathBuf,
     },
+    /// Rewrite legacy config keys into the current layout
+    Migrate {
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+
+        /// Print the migrated config instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Manage themes
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommands,
+    },
+    /// Write the page's QR code to a file, e.g. for business cards
+    Qr {
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+
+        /// File to write; the format follows the extension (.svg or .png)
+        #[arg(short, long, default_value = "qr.png")]
+        output: PathBuf,
+
+        /// Image format, if it can't be told from the file name
+        #[arg(long, value_parser = ["svg", "png"])]
+        format: Option<String>,
+
+        /// Width and height in pixels
+        #[arg(
+            long,
+            default_value_t = 1024,
+            value_parser = clap::value_parser!(u32).range(1..=i64::from(genkan::qr::MAX_SIZE))
+        )]
+        size: u32,
+
+        /// Use the [qr.dark] colours
+        #[arg(long)]
+        dark: bool,
+
+        /// URL to encode instead of meta.page_url
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Only use a cached avatar for the logo and never access the network
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Manage the cache of downloaded and resized images
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+}
+
+/// Options shared by `build` and `serve`
+#[derive(Args, Clone, Default)]
+struct BuildOptions {
+    /// Only use cached downloads and never access the network
+    #[arg(long)]
+    offline: bool,
+
+    /// Number of images to download and resize in parallel
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+    jobs: Option<u64>,
+
+    /// Fail the build if there are any warnings
+    #[arg(long)]
+    strict: bool,
+
+    /// Fail the build if any image would be loaded from its remote URL
+    #[arg(long)]
+    deny_hotlinks: bool,
+
+    /// Write a single self-contained page, or the page plus hashed asset
+    /// files (overrides output.mode)
+    #[arg(long, value_parser = ["single", "multi"])]
+    output_mode: Option<String>,
+
+    /// Build the page as it looks at a date and time, e.g.
+    /// 2025-12-24T18:00:00+01:00, to preview scheduled links
+    #[arg(long, value_parser = schedule::parse_datetime)]
+    at: Option<DateTime<Utc>>,
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// List cached downloads
+    Ls {
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// Delete every cached download and resized image
+    Clean {
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ThemeCommands {
+    /// List project, user and built-in themes
+    List {
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// Install a theme from a git repository and pin it in themes.lock
+    Install {
+        /// Git URL, local repository path, or theme name in the default repository
+        source: String,
+
+        /// Branch, tag or commit to install (defaults to the remote HEAD)
+        #[arg(long)]
+        rev: Option<String>,
+
+        /// Name to install the theme as (defaults to the repository name)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Theme directory inside the repository
+        #[arg(long)]
+        path: Option<String>,
+
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// Update installed themes to the latest commit of their revision
+    Update {
+        /// Theme to update (defaults to every theme in themes.lock)
+        name: Option<String>,
+
+        /// Switch to this branch, tag or commit
+        #[arg(long, requires = "name")]
+        rev: Option<String>,
+
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// Remove an installed theme from the project
+    Remove {
+        /// Theme to remove
+        name: String,
+
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// Copy a built-in theme into the project's themes directory
+    Eject {
+        /// Name of the built-in theme
+        name: String,
+
+        /// Path to config file
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+
+        /// Overwrite the theme if it already exists in the project
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Commands::Build { config, output }) => {
-            build_site(config, output)?;
+        Some(Commands::Build {
+            config,
+            output,
+            options,
+        }) => {
+            build_site(config, output, &options)?;
+        }
+        Some(Commands::Serve {
+            config,
+            output,
+            host,
+            port,
+            options,
+        }) => {
+            serve_site(config, output, host, port, options)?;
         }
         Some(Commands::Init { path }) => {
             init_project(path)?;
@@ -72,53 +280,267 @@ fn main() -> Result<()> {
         Some(Commands::Validate { config }) => {
             validate_config(config)?;
         }
+        Some(Commands::Migrate { config, dry_run }) => {
+            migrate_config(config, dry_run)?;
+        }
+        Some(Commands::Theme { command }) => match command {
+            ThemeCommands::List { config } => {
+                list_themes(config)?;
+            }
+            ThemeCommands::Install {
+                source,
+                rev,
+                name,
+                path,
+                config,
+            } => {
+                install_theme(config, &source, rev, name, path)?;
+            }
+            ThemeCommands::Update { name, rev, config } => {
+                update_themes(config, name, rev)?;
+            }
+            ThemeCommands::Remove { name, config } => {
+                remove_theme(config, &name)?;
+            }
+            ThemeCommands::Eject {
+                name,
+                config,
+                force,
+            } => {
+                eject_theme(config, &name, force)?;
+            }
+        },
+        Some(Commands::Qr {
+            config,
+            output,
+            format,
+            size,
+            dark,
+            url,
+            offline,
+        }) => {
+            write_qr_code(config, output, format, size, dark, url, offline)?;
+        }
+        Some(Commands::Cache { command }) => match command {
+            CacheCommands::Ls { config } => {
+                list_cache(config)?;
+            }
+            CacheCommands::Clean { config } => {
+                clean_cache(config)?;
+            }
+        },
         None => {
             // Default behavior: build with default settings
-            build_site(PathBuf::from("config.toml"), PathBuf::from("output"))?;
+            build_site(
+                PathBuf::from("config.toml"),
+                PathBuf::from("output"),
+                &BuildOptions::default(),
+            )?;
         }
     }
 
     Ok(())
 }
 
+/// Loads a configuration file and reports every problem found in it
+///
+/// All errors and warnings are printed as source snippets pointing into
+/// the TOML file, so they can be fixed in a single pass.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+///
+/// # Returns
+///
+/// * `Ok((Config, Diagnostics))` with the warnings that were printed, if the
+///   file parsed and has no errors
+/// * `Err(anyhow::Error)` if the file couldn't be read, parsed, or has errors
+fn load_config(config_path: &Path) -> Result<(config::Config, Diagnostics)> {
+    let source = SourceFile::load(config_path).context("Failed to load configuration")?;
+
+    let (config, mut diagnostics) = match config::Config::from_source(&source) {
+        Ok(parsed) => parsed,
+        Err(diagnostic) => {
+            eprintln!("{}", source.render(&diagnostic));
+            anyhow::bail!("Failed to parse TOML config");
+        }
+    };
+
+    diagnostics.extend(config.diagnose());
+    report_diagnostics(&source, &diagnostics)?;
+
+    Ok((config, diagnostics))
+}
+
+/// Prints diagnostics as source snippets and fails if any of them are errors
+///
+/// # Arguments
+///
+/// * `source` - The config file the diagnostics refer to
+/// * `diagnostics` - Problems found in the config
+///
+/// # Returns
+///
+/// * `Ok(())` if there are no errors (warnings are allowed)
+/// * `Err(anyhow::Error)` with a summary otherwise
+fn report_diagnostics(source: &SourceFile, diagnostics: &Diagnostics) -> Result<()> {
+    if !diagnostics.is_empty() {
+        eprint!("{}", diagnostics.render(source));
+    }
+    if diagnostics.has_errors() {
+        anyhow::bail!("Configuration validation failed: {}", diagnostics.summary());
+    }
+    Ok(())
+}
+
+/// Checks a config against the theme's `theme.toml` and those of its parents
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+/// * `config` - The loaded configuration
+/// * `theme_path` - Path to the theme directory
+///
+/// # Returns
+///
+/// * `Ok((ThemeManifest, Diagnostics))` with the warnings that were printed,
+///   if the config only uses options the theme declares
+/// * `Err(anyhow::Error)` if the manifest is invalid or the config doesn't match it
+fn check_theme(
+    config_path: &Path,
+    config: &config::Config,
+    theme_path: &Path,
+) -> Result<(ThemeManifest, Diagnostics)> {
+    let project_root = config::project_root(config_path);
+    let theme = themes::ResolvedTheme::resolve(&project_root, &config.theme.name, theme_path)?;
+    let source = SourceFile::load(config_path).context("Failed to load configuration")?;
+    let diagnostics = theme.manifest.diagnose(config);
+    report_diagnostics(&source, &diagnostics)?;
+    Ok((theme.manifest, diagnostics))
+}
+
+/// Fails a strict build if there were any warnings or denied hot-links
+///
+/// Runs before the generator writes anything, so a failed build leaves the
+/// output directory as it was.
+///
+/// # Arguments
+///
+/// * `warnings` - Config, theme and build warnings, in the order they were printed
+/// * `hotlinked` - Images the page still loads from a remote URL
+/// * `strict` - Whether warnings fail the build
+/// * `deny_hotlinks` - Whether hot-linked images fail the build
+///
+/// # Returns
+///
+/// * `Ok(())` if nothing fails the build
+/// * `Err(anyhow::Error)` listing every problem otherwise
+fn check_strict(
+    warnings: &Diagnostics,
+    hotlinked: &[generator::Hotlink],
+    strict: bool,
+    deny_hotlinks: bool,
+) -> Result<()> {
+    let mut problems: Vec<String> = Vec::new();
+    if strict {
+        problems.extend(warnings.warnings().map(ToString::to_string));
+    }
+    if deny_hotlinks {
+        problems.extend(hotlinked.iter().map(|hotlink| {
+            format!(
+                "{} is hot-linked instead of embedded (at {})",
+                hotlink.url, hotlink.path
+            )
+        }));
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = format!("Build failed with {} problem(s):", problems.len());
+    for problem in &problems {
+        message.push_str("\n  - ");
+        message.push_str(problem);
+    }
+    anyhow::bail!(message)
+}
+
 /// Builds a static site from the configuration file
 ///
 /// # Arguments
 ///
 /// * `config_path` - Path to the TOML configuration file
 /// * `output_dir` - Directory where the generated HTML will be saved
+/// * `options` - Build flags such as `--offline` and `--jobs`
 ///
 /// # Returns
 ///
-/// * `Ok(())` if the site was generated successfully
+/// * `Ok(Generator)` with the generator used, if the site was generated successfully
 /// * `Err(anyhow::Error)` if configuration loading, validation, or generation failed
-fn build_site(config_path: PathBuf, output_dir: PathBuf) -> Result<()> {
+fn build_site(
+    config_path: PathBuf,
+    output_dir: PathBuf,
+    options: &BuildOptions,
+) -> Result<generator::Generator> {
     println!("Building site...\n");
 
-    // Load configuration
+    // Load and validate configuration
     println!("Loading config from: {}", config_path.display());
-    let config = config::Config::from_file(&config_path).context("Failed to load configuration")?;
-
-    // Validate configuration
-    config
-        .validate()
-        .context("Configuration validation failed")?;
+    let (config, mut warnings) = load_config(&config_path)?;
     println!("Config validated\n");
+    let strict = options.strict || config.build.strict;
+    let deny_hotlinks = options.deny_hotlinks || !config.build.allow_hotlinks;
+
+    // Local paths in the config are relative to the config file
+    let project_root = config::project_root(&config_path);
 
     // Find theme path
-    let theme_path =
-        generator::find_theme_path(&config.theme.name).context("Failed to find theme")?;
-    println!(
-        "Using theme: {} ({})",
-        config.theme.name,
-        theme_path.display()
-    );
+    let theme_path = generator::find_theme_path(&project_root, &config.theme.name)
+        .context("Failed to find theme")?;
+    let (manifest, theme_warnings) = check_theme(&config_path, &config, &theme_path)?;
+    warnings.extend(theme_warnings);
+    match manifest.version {
+        Some(ref version) => println!(
+            "Using theme: {} {} ({})",
+            config.theme.name,
+            version,
+            theme_path.display()
+        ),
+        None => println!(
+            "Using theme: {} ({})",
+            config.theme.name,
+            theme_path.display()
+        ),
+    }
+
+    if let Some(at) = options.at {
+        println!(
+            "Showing links as of {}",
+            at.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+    }
 
     // Create output path
     let output_path = output_dir.join("index.html");
 
     // Generate site
-    let generator = generator::Generator::new(config, theme_path, output_path.clone());
+    let generator = generator::Generator::new(config, theme_path, output_path.clone())
+        .with_project_root(project_root)
+        .with_offline(options.offline)
+        .with_jobs(options.jobs.map(|jobs| jobs as usize))
+        .with_output_mode(
+            options
+                .output_mode
+                .as_deref()
+                .and_then(generator::OutputMode::parse),
+        )
+        .with_at(options.at)
+        .with_check(Box::new(move |report| {
+            let mut warnings = warnings.clone();
+            warnings.extend(report.warnings.clone());
+            check_strict(&warnings, &report.hotlinked, strict, deny_hotlinks)
+        }));
     generator.generate().context("Failed to generate site")?;
 
     println!(
@@ -127,7 +549,43 @@ fn build_site(config_path: PathBuf, output_dir: PathBuf) -> Result<()> {
     );
     println!("\nTip: Open the file in your browser to see your page!");
 
-    Ok(())
+    Ok(generator)
+}
+
+/// Serves the site locally and rebuilds it whenever its inputs change
+///
+/// Watches the config file, the active theme directory and any local
+/// icon files. After each rebuild, open browser tabs are reloaded.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+/// * `output_dir` - Directory where the generated HTML will be saved and served from
+/// * `host` - Address to bind the server to
+/// * `port` - Port to listen on
+/// * `options` - Build flags used for every rebuild
+///
+/// # Returns
+///
+/// * `Err(anyhow::Error)` if the server could not be started
+fn serve_site(
+    config_path: PathBuf,
+    output_dir: PathBuf,
+    host: String,
+    port: u16,
+    build_options: BuildOptions,
+) -> Result<()> {
+    let options = server::ServeOptions {
+        host,
+        port,
+        output_dir: output_dir.clone(),
+        always_watch: vec![config_path.clone()],
+    };
+
+    server::serve(options, || {
+        let generator = build_site(config_path.clone(), output_dir.clone(), &build_options)?;
+        Ok(generator.watched_paths())
+    })
 }
 
 /// Initializes a new Genkan project with default configuration
@@ -166,32 +624,45 @@ fn init_project(path: PathBuf) -> Result<()> {
         "[profile]\n",
         "name = \"Your Name\"\n",
         "bio = \"Welcome to my link page!\"\n",
+        "\n",
+        "[profile.light]\n",
         "# Avatar can be a URL or local path (relative to config.toml)\n",
         "avatar = \"https://via.placeholder.com/150\"\n",
-        "# Optional: background image or gradient\n",
+        "# Optional: background color, gradient or image\n",
         "# background = \"linear-gradient(135deg, #667eea 0%, #764ba2 100%)\"\n",
+        "# background_image = \"./background.jpg\"\n",
+        "\n",
+        "# Optional: separate avatar and background for dark mode\n",
+        "# [profile.dark]\n",
+        "# avatar = \"./avatar-dark.png\"\n",
         "\n",
         "[theme]\n",
-        "# Theme name (currently supports: simple)\n",
+        "# Theme name (built-in: simple, doodle)\n",
         "name = \"simple\"\n",
-        "# Primary color for buttons and accents\n",
-        "primary_color = \"#000000\"\n",
-        "# Secondary color for accents\n",
-        "secondary_color = \"#000000\"\n",
-        "# Background color (can be overridden by profile.background)\n",
-        "background_color = \"#ffffff\"\n",
         "# Button style: rounded, pill, square\n",
         "button_style = \"rounded\"\n",
         "# Font family\n",
         "font_family = \"system-ui, -apple-system, sans-serif\"\n",
         "# Spacing between link buttons\n",
         "link_spacing = \"24px\"\n",
+        "\n",
+        "[theme.light]\n",
+        "# Primary color for buttons and accents\n",
+        "primary_color = \"#000000\"\n",
+        "# Secondary color for accents\n",
+        "secondary_color = \"#000000\"\n",
+        "# Background color (can be overridden by profile.light.background)\n",
+        "background_color = \"#ffffff\"\n",
         "# Color domains - granular control over text colors\n",
         "header_color = \"#000000\"\n",
         "bio_color = \"rgba(0, 0, 0, 0.7)\"\n",
         "link_title_color = \"#000000\"\n",
         "link_description_color = \"rgba(0, 0, 0, 0.6)\"\n",
         "\n",
+        "[dark_mode]\n",
+        "# auto, light, dark, or disable\n",
+        "mode = \"disable\"\n",
+        "\n",
         "[meta]\n",
         "# Page metadata\n",
         "title = \"My Links\"\n",
@@ -205,8 +676,9 @@ fn init_project(path: PathBuf) -> Result<()> {
         "\n",
         "# Define your links here\n",
         "# Each link can have: title, url (optional), icon (optional), description (optional)\n",
-        "# link_type: \"block\" (default) or \"space\" (for spacing)\n",
+        "# link_type: \"block\" (default), \"space\" (for spacing) or \"header\" (starts a section)\n",
         "# Omit url for non-clickable text blocks, omit icon for text-only\n",
+        "# visible_from / visible_until (optional) show a link only between two dates\n",
         "[[links]]\n",
         "title = \"My Website\"\n",
         "url = \"https://example.com\"\n",
@@ -261,6 +733,7 @@ fn init_project(path: PathBuf) -> Result<()> {
 /// - The configuration file can be parsed
 /// - All required fields are present and valid
 /// - The specified theme exists
+/// - Theme options are declared by the theme and it supports this Genkan version
 ///
 /// # Arguments
 ///
@@ -273,17 +746,14 @@ fn init_project(path: PathBuf) -> Result<()> {
 fn validate_config(config_path: PathBuf) -> Result<()> {
     println!("Validating config...\n");
 
-    // Load configuration
-    let config = config::Config::from_file(&config_path).context("Failed to load configuration")?;
-
-    // Validate configuration
-    config
-        .validate()
-        .context("Configuration validation failed")?;
+    // Load and validate configuration
+    let (config, _) = load_config(&config_path)?;
 
     // Check theme exists
-    let theme_path =
-        generator::find_theme_path(&config.theme.name).context("Failed to find theme")?;
+    let project_root = config::project_root(&config_path);
+    let theme_path = generator::find_theme_path(&project_root, &config.theme.name)
+        .context("Failed to find theme")?;
+    check_theme(&config_path, &config, &theme_path)?;
 
     println!("Configuration is valid");
     println!(
@@ -295,3 +765,373 @@ fn validate_config(config_path: PathBuf) -> Result<()> {
 
     Ok(())
 }
+
+/// Migrates a configuration file from a legacy layout
+///
+/// Moves keys such as `profile.avatar` and `theme.primary_color` into
+/// `profile.light` and `theme.light`, keeping comments and formatting.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file to migrate
+/// * `dry_run` - Print the migrated config to stdout instead of writing it
+///
+/// # Returns
+///
+/// * `Ok(())` if the config was migrated or already up to date
+/// * `Err(anyhow::Error)` if the file couldn't be read, parsed, or written
+fn migrate_config(config_path: PathBuf, dry_run: bool) -> Result<()> {
+    let content = std::fs::read_to_string(&config_path).context("Failed to read config file")?;
+    let migration = migrate::migrate_config(&content)?;
+    for skipped in &migration.skipped {
+        eprintln!("Warning: {}", skipped);
+    }
+
+    if migration.is_noop() {
+        println!("{} is already up to date", config_path.display());
+        return Ok(());
+    }
+
+    if dry_run {
+        print!("{}", migration.content);
+        return Ok(());
+    }
+
+    std::fs::write(&config_path, &migration.content).context("Failed to write config file")?;
+
+    println!("Migrated {}:", config_path.display());
+    for change in &migration.changes {
+        println!("  - {}", change);
+    }
+
+    Ok(())
+}
+
+/// Copies a built-in theme into the project for customisation
+///
+/// The ejected copy lives in `themes/<name>` next to the config file and
+/// takes precedence over the built-in version on the next build.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+/// * `name` - Name of the built-in theme
+/// * `force` - Overwrite an existing copy in the project
+///
+/// # Returns
+///
+/// * `Ok(())` if the theme was copied
+/// * `Err(anyhow::Error)` if the theme isn't built in or already exists
+fn eject_theme(config_path: PathBuf, name: &str, force: bool) -> Result<()> {
+    let project_root = config::project_root(&config_path);
+    let theme_path = themes::eject_theme(&project_root, name, force)?;
+
+    println!("Ejected theme '{}' to {}", name, theme_path.display());
+    println!(
+        "Edit the files there to customise it; they now take precedence over the built-in theme."
+    );
+
+    Ok(())
+}
+
+/// Lists the themes available to the project
+///
+/// Themes are shown in lookup order. The theme selected in the config, if
+/// the config can be read, is marked with `*`.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+///
+/// # Returns
+///
+/// * `Ok(())` if the themes were listed
+/// * `Err(anyhow::Error)` if `themes.lock` couldn't be read
+fn list_themes(config_path: PathBuf) -> Result<()> {
+    let project_root = config::project_root(&config_path);
+    let active = config::Config::from_file(&config_path)
+        .ok()
+        .map(|c| c.theme.name);
+
+    let mut seen = std::collections::HashSet::new();
+    for theme in themes::list_themes(&project_root)? {
+        let marker =
+            if active.as_deref() == Some(theme.name.as_str()) && !seen.contains(&theme.name) {
+                "*"
+            } else {
+                " "
+            };
+        let location = match theme.location {
+            themes::ThemeLocation::Project => "project",
+            themes::ThemeLocation::User => "user",
+            themes::ThemeLocation::Builtin => "built-in",
+        };
+        let shadowed = if seen.contains(&theme.name) {
+            " (shadowed)"
+        } else {
+            ""
+        };
+        match theme.locked {
+            Some(ref locked) => println!(
+                "{} {:<16} {:<9} {}@{}{}",
+                marker,
+                theme.name,
+                location,
+                locked.source,
+                locked.short_commit(),
+                shadowed
+            ),
+            None => println!("{} {:<16} {}{}", marker, theme.name, location, shadowed),
+        }
+        seen.insert(theme.name);
+    }
+
+    Ok(())
+}
+
+/// Installs a theme from a git repository into the project
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+/// * `source` - Git URL, local repository path, or theme name in the default repository
+/// * `rev` - Branch, tag or commit to install
+/// * `name` - Name to install the theme as
+/// * `path` - Theme directory inside the repository
+///
+/// # Returns
+///
+/// * `Ok(())` if the theme was installed and recorded in themes.lock
+/// * `Err(anyhow::Error)` if cloning or copying failed
+fn install_theme(
+    config_path: PathBuf,
+    source: &str,
+    rev: Option<String>,
+    name: Option<String>,
+    path: Option<String>,
+) -> Result<()> {
+    let project_root = config::project_root(&config_path);
+    let locked = themes::install_theme(
+        &project_root,
+        source,
+        rev.as_deref(),
+        name.as_deref(),
+        path.as_deref(),
+    )?;
+
+    println!(
+        "Installed theme '{}' at {} ({})",
+        locked.name,
+        locked.short_commit(),
+        themes::project_themes_dir(&project_root)
+            .join(&locked.name)
+            .display()
+    );
+    println!(
+        "Set `name = \"{}\"` under [theme] in config.toml to use it.",
+        locked.name
+    );
+
+    Ok(())
+}
+
+/// Updates one or all locked themes
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+/// * `name` - Theme to update, or `None` for every theme in themes.lock
+/// * `rev` - Switch the theme to this branch, tag or commit
+///
+/// # Returns
+///
+/// * `Ok(())` if every theme was updated
+/// * `Err(anyhow::Error)` if a theme isn't locked or fetching failed
+fn update_themes(config_path: PathBuf, name: Option<String>, rev: Option<String>) -> Result<()> {
+    let project_root = config::project_root(&config_path);
+    let names = match name {
+        Some(name) => vec![name],
+        None => themes::ThemeLock::load(&project_root)?
+            .themes
+            .into_iter()
+            .map(|t| t.name)
+            .collect(),
+    };
+
+    if names.is_empty() {
+        println!("No themes in {} to update", themes::LOCK_FILE);
+        return Ok(());
+    }
+
+    for name in names {
+        let (previous, updated) = themes::update_theme(&project_root, &name, rev.as_deref())?;
+        if previous.commit == updated.commit {
+            println!(
+                "Theme '{}' is up to date ({})",
+                name,
+                updated.short_commit()
+            );
+        } else {
+            println!(
+                "Updated theme '{}': {} -> {}",
+                name,
+                previous.short_commit(),
+                updated.short_commit()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes a theme from the project and from themes.lock
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+/// * `name` - Theme to remove
+///
+/// # Returns
+///
+/// * `Ok(())` if the theme was removed
+/// * `Err(anyhow::Error)` if the theme isn't installed in the project
+fn remove_theme(config_path: PathBuf, name: &str) -> Result<()> {
+    let project_root = config::project_root(&config_path);
+    themes::remove_theme(&project_root, name)?;
+    println!("Removed theme '{}'", name);
+
+    Ok(())
+}
+
+/// Lists the downloads in the project's asset cache
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+///
+/// # Returns
+///
+/// * `Ok(())` once the cache has been listed
+fn list_cache(config_path: PathBuf) -> Result<()> {
+    let cache = AssetCache::for_project(&config::project_root(&config_path));
+    println!("Cache: {}\n", cache.dir().display());
+
+    let downloads = cache.downloads();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    for download in &downloads {
+        println!(
+            "{:>10}  {:>9}  {}",
+            format_size(download.size),
+            format_age(now.saturating_sub(download.fetched_at)),
+            download.url
+        );
+    }
+
+    let (resized, resized_size) = cache.resized_stats();
+    let total: u64 = downloads.iter().map(|d| d.size).sum();
+    println!(
+        "\n{} download(s) ({}), {} resized image(s) ({})",
+        downloads.len(),
+        format_size(total),
+        resized,
+        format_size(resized_size)
+    );
+
+    Ok(())
+}
+
+/// Writes the page's QR code to a standalone image file
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+/// * `output` - File to write
+/// * `format` - `svg` or `png`, or `None` to use the file extension
+/// * `size` - Width and height in pixels
+/// * `dark` - Use the `[qr.dark]` colours
+/// * `url` - URL to encode instead of `meta.page_url`
+/// * `offline` - Only use the cache when downloading the avatar for the logo
+///
+/// # Returns
+///
+/// * `Ok(())` if the file was written
+/// * `Err(anyhow::Error)` if there is no URL to encode, the format is
+///   unknown, or the code couldn't be rendered or written
+fn write_qr_code(
+    config_path: PathBuf,
+    output: PathBuf,
+    format: Option<String>,
+    size: u32,
+    dark: bool,
+    url: Option<String>,
+    offline: bool,
+) -> Result<()> {
+    let (config, _) = load_config(&config_path)?;
+    let url = url
+        .or_else(|| config.meta.page_url.clone().filter(|url| !url.is_empty()))
+        .context("Set meta.page_url or pass --url to choose what the QR code links to")?;
+    let format = match format {
+        Some(ref format) => QrFormat::parse(format),
+        None => QrFormat::from_path(&output),
+    }
+    .with_context(|| {
+        format!(
+            "Can't tell the QR code format from {}; use a .svg or .png file or --format",
+            output.display()
+        )
+    })?;
+
+    // The QR code doesn't depend on the theme or the page output
+    let generator = generator::Generator::new(config, PathBuf::new(), PathBuf::new())
+        .with_project_root(config::project_root(&config_path))
+        .with_offline(offline);
+    let data = generator.render_qr_code(&url, format, size, dark)?;
+    std::fs::write(&output, data)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    println!("Wrote QR code for {} to {}", url, output.display());
+
+    Ok(())
+}
+
+/// Deletes the project's asset cache
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the TOML configuration file
+///
+/// # Returns
+///
+/// * `Ok(())` if the cache was deleted or didn't exist
+/// * `Err(anyhow::Error)` if the cache directory couldn't be removed
+fn clean_cache(config_path: PathBuf) -> Result<()> {
+    let cache = AssetCache::for_project(&config::project_root(&config_path));
+    let removed = cache.clean()?;
+    println!(
+        "Removed {} from {}",
+        format_size(removed),
+        cache.dir().display()
+    );
+
+    Ok(())
+}
+
+/// Formats a byte count for display, e.g. `12.3 KB`
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Formats an age in seconds for display, e.g. `3h ago`
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
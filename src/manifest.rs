@@ -0,0 +1,400 @@
+//! Theme manifests (`theme.toml`)
+//!
+//! A theme directory may contain a `theme.toml` describing the theme, the
+//! Genkan version it needs, the features it supports, and any extra options
+//! it accepts under `[theme.options]` in the site config:
+//!
+//! ```toml
+//! name = "simple"
+//! version = "1.0.0"
+//! author = "Genkan"
+//! min_genkan_version = "0.2.0"
+//!
+//! [features]
+//! qr_share = true
+//! dark_mode = true
+//! social_links = true
+//! link_sections = true
+//!
+//! [options.avatar_radius]
+//! default = "50%"
+//! description = "Corner radius of the avatar"
+//! ```
+//!
+//! Sources the theme loads from elsewhere go in a `[csp]` table, e.g.
+//! `font-src = ["https://fonts.gstatic.com"]`, so the generated
+//! Content-Security-Policy allows them. Sources only needed while an option
+//! is on go in that option's `csp` table instead, e.g.
+//! `[options.web_fonts.csp]`, and are left out when it is `false`.
+//!
+//! A theme can also build on another one with `extends = "simple"`. It then
+//! inherits the parent's templates, options and features, and only needs to
+//! provide the blocks, CSS and JS it changes.
+//!
+//! Themes without a manifest are assumed to support every feature and to
+//! take no options.
+
+use crate::config::Config;
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// File name of the theme manifest inside a theme directory
+pub const MANIFEST_FILE: &str = "theme.toml";
+
+/// Metadata and options declared by a theme
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeManifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Oldest Genkan release able to render the theme
+    #[serde(default)]
+    pub min_genkan_version: Option<String>,
+    /// Name of the parent theme whose templates, CSS and JS are reused
+    #[serde(default)]
+    pub extends: Option<String>,
+    /// Include the parent's `style.css` before this theme's
+    #[serde(default = "default_true")]
+    pub inherit_css: bool,
+    /// Include the parent's `script.js` before this theme's
+    #[serde(default = "default_true")]
+    pub inherit_js: bool,
+    /// Supported features; inherited from the parent, or all if not declared
+    #[serde(default)]
+    pub features: Option<ThemeFeatures>,
+    /// Extra options accepted under `[theme.options]`
+    #[serde(default)]
+    pub options: BTreeMap<String, ThemeOption>,
+    /// Content-Security-Policy sources the theme needs, such as web font hosts
+    #[serde(default)]
+    pub csp: BTreeMap<String, Vec<String>>,
+}
+
+/// Optional page features a theme knows how to render
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeFeatures {
+    /// Share button with a QR code for `meta.page_url`
+    #[serde(default)]
+    pub qr_share: bool,
+    /// Dark color scheme and toggle
+    #[serde(default)]
+    pub dark_mode: bool,
+    /// Icon row for `profile.social_links`
+    #[serde(default)]
+    pub social_links: bool,
+    /// Links grouped under `link_type = "header"` links
+    #[serde(default)]
+    pub link_sections: bool,
+}
+
+/// A theme-specific option and its default value
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeOption {
+    /// Default value; user values must have the same type
+    pub default: toml::Value,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Content-Security-Policy sources needed unless the option is `false`
+    #[serde(default)]
+    pub csp: BTreeMap<String, Vec<String>>,
+}
+
+impl ThemeManifest {
+    /// Loads the manifest from a theme directory
+    ///
+    /// # Arguments
+    ///
+    /// * `theme_path` - Path to the theme directory
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ThemeManifest)` with the parsed manifest, or a permissive default
+    ///   if the theme has no `theme.toml`
+    /// * `Err(anyhow::Error)` if the manifest exists but is invalid
+    pub fn load(theme_path: &Path) -> Result<Self> {
+        let manifest_path = theme_path.join(MANIFEST_FILE);
+        if !manifest_path.exists() {
+            let name = theme_path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            return Ok(Self::unversioned(name));
+        }
+
+        let content = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse theme manifest {}", manifest_path.display()))
+    }
+
+    /// Manifest assumed for themes that don't ship a `theme.toml`
+    pub fn unversioned(name: String) -> Self {
+        Self {
+            name,
+            version: None,
+            author: None,
+            description: None,
+            min_genkan_version: None,
+            extends: None,
+            inherit_css: true,
+            inherit_js: true,
+            features: None,
+            options: BTreeMap::new(),
+            csp: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the features this theme supports
+    ///
+    /// Themes that don't declare `[features]` are assumed to support all of them.
+    pub fn features(&self) -> ThemeFeatures {
+        self.features.clone().unwrap_or(ThemeFeatures {
+            qr_share: true,
+            dark_mode: true,
+            social_links: true,
+            link_sections: true,
+        })
+    }
+
+    /// Fills in everything this theme inherits from its parent
+    ///
+    /// Options declared by the parent are accepted unless the child redeclares
+    /// them, features are inherited when the child doesn't declare any, CSP
+    /// sources are combined, and the stricter of the two minimum Genkan
+    /// versions applies.
+    pub fn inherit(&mut self, parent: &ThemeManifest) {
+        for (key, option) in &parent.options {
+            self.options
+                .entry(key.clone())
+                .or_insert_with(|| option.clone());
+        }
+        if self.features.is_none() {
+            self.features = parent.features.clone();
+        }
+        for (directive, sources) in &parent.csp {
+            let own = self.csp.entry(directive.clone()).or_default();
+            for source in sources {
+                if !own.contains(source) {
+                    own.push(source.clone());
+                }
+            }
+        }
+        self.min_genkan_version = match (self.min_genkan_version.take(), &parent.min_genkan_version)
+        {
+            (Some(own), Some(inherited)) => {
+                let newer = semver::Version::parse(inherited.trim())
+                    .ok()
+                    .zip(semver::Version::parse(own.trim()).ok())
+                    .is_some_and(|(inherited, own)| inherited > own);
+                Some(if newer { inherited.clone() } else { own })
+            }
+            (own, inherited) => own.or_else(|| inherited.clone()),
+        };
+    }
+
+    /// Returns the theme options with defaults filled in
+    ///
+    /// Values set under `[theme.options]` override the manifest defaults.
+    /// The result is exposed to templates as `theme_options`.
+    pub fn resolve_options(&self, config: &Config) -> toml::Table {
+        let mut options: toml::Table = self
+            .options
+            .iter()
+            .map(|(key, option)| (key.clone(), option.default.clone()))
+            .collect();
+        for (key, value) in &config.theme.options {
+            options.insert(key.clone(), value.clone());
+        }
+        options
+    }
+
+    /// Returns the Content-Security-Policy sources the theme needs
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Option values from [`ThemeManifest::resolve_options`]
+    ///
+    /// # Returns
+    ///
+    /// The `[csp]` sources, plus those of every option that isn't `false`
+    pub fn csp_sources(&self, options: &toml::Table) -> BTreeMap<String, Vec<String>> {
+        let mut sources = self.csp.clone();
+        for (key, option) in &self.options {
+            if options.get(key) == Some(&toml::Value::Boolean(false)) {
+                continue;
+            }
+            for (directive, option_sources) in &option.csp {
+                let own = sources.entry(directive.clone()).or_default();
+                for source in option_sources {
+                    if !own.contains(source) {
+                        own.push(source.clone());
+                    }
+                }
+            }
+        }
+        sources
+    }
+
+    /// Checks a config against this theme
+    ///
+    /// Reports an error for every option the theme doesn't declare or whose
+    /// type doesn't match the default, and for a Genkan version older than
+    /// the theme requires. Using a feature the theme doesn't support is a
+    /// warning, since the page still renders without it.
+    pub fn diagnose(&self, config: &Config) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+
+        if let Some(ref required) = self.min_genkan_version {
+            match check_genkan_version(required) {
+                Ok(true) => {}
+                Ok(false) => diagnostics.push(
+                    Diagnostic::error(format!(
+                        "Theme '{}' requires genkan {} or newer (this is {})",
+                        self.name,
+                        required,
+                        env!("CARGO_PKG_VERSION")
+                    ))
+                    .at("theme.name")
+                    .with_help("upgrade genkan or choose another theme"),
+                ),
+                Err(e) => diagnostics.push(
+                    Diagnostic::error(format!(
+                        "Theme '{}' has an invalid min_genkan_version '{}': {}",
+                        self.name, required, e
+                    ))
+                    .at("theme.name"),
+                ),
+            }
+        }
+
+        for (key, value) in &config.theme.options {
+            let path = format!("theme.options.{}", key);
+            match self.options.get(key) {
+                None => {
+                    let help = if self.options.is_empty() {
+                        format!("theme '{}' doesn't take any options", self.name)
+                    } else {
+                        let declared: Vec<&str> = self.options.keys().map(String::as_str).collect();
+                        format!("theme '{}' declares: {}", self.name, declared.join(", "))
+                    };
+                    diagnostics.push(
+                        Diagnostic::error(format!("Unknown theme option '{}'", key))
+                            .at(path)
+                            .with_help(help),
+                    );
+                }
+                Some(option) if !same_type(&option.default, value) => {
+                    diagnostics.push(
+                        Diagnostic::error(format!(
+                            "Theme option '{}' must be a {}, found a {}",
+                            key,
+                            option.default.type_str(),
+                            value.type_str()
+                        ))
+                        .at(path)
+                        .with_help(format!("the default is {}", option.default)),
+                    );
+                }
+                Some(_) => {
+                    // Options are written into style.css, which isn't escaped
+                    if let toml::Value::String(value) = value
+                        && let Err(reason) = crate::security::check_css_value(value)
+                    {
+                        diagnostics.push(
+                            Diagnostic::error(format!(
+                                "Invalid value for theme option '{}': {}",
+                                key, reason
+                            ))
+                            .at(path),
+                        );
+                    }
+                }
+            }
+        }
+
+        let features = self.features();
+        let unsupported = |feature: &str, path: &str| {
+            Diagnostic::warning(format!(
+                "Theme '{}' doesn't support {}; it will be left out",
+                self.name, feature
+            ))
+            .at(path)
+        };
+        if !features.dark_mode && !config.dark_mode.mode.eq_ignore_ascii_case("disable") {
+            diagnostics.push(unsupported("dark mode", "dark_mode.mode"));
+        }
+        if !features.qr_share
+            && config
+                .meta
+                .page_url
+                .as_deref()
+                .is_some_and(|u| !u.is_empty())
+        {
+            diagnostics.push(unsupported("the QR share button", "meta.page_url"));
+        }
+        if !features.social_links && !config.profile.social_links.is_empty() {
+            diagnostics.push(unsupported("social links", "profile.social_links"));
+        }
+        if !features.link_sections
+            && let Some(idx) = config
+                .links
+                .iter()
+                .position(|link| link.link_type.eq_ignore_ascii_case("header"))
+        {
+            diagnostics.push(unsupported("link sections", &format!("links[{}]", idx)));
+        }
+
+        diagnostics
+    }
+
+    /// Validates a config against this theme
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if [`diagnose`](Self::diagnose) found no errors
+    /// * `Err(anyhow::Error)` listing every error otherwise
+    pub fn validate(&self, config: &Config) -> Result<()> {
+        let errors: Vec<String> = self
+            .diagnose(config)
+            .errors()
+            .map(ToString::to_string)
+            .collect();
+        match errors.len() {
+            0 => Ok(()),
+            1 => anyhow::bail!("{}", errors[0]),
+            n => anyhow::bail!("{} theme errors:\n  - {}", n, errors.join("\n  - ")),
+        }
+    }
+}
+
+/// Returns true if the running Genkan version is at least `required`
+fn check_genkan_version(required: &str) -> std::result::Result<bool, semver::Error> {
+    let required = semver::Version::parse(required.trim())?;
+    let current = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
+    Ok(current >= required)
+}
+
+/// Returns true if both values have the same TOML type
+///
+/// Integers are accepted where the default is a float.
+fn same_type(default: &toml::Value, value: &toml::Value) -> bool {
+    matches!(
+        (default, value),
+        (toml::Value::Float(_), toml::Value::Integer(_))
+    ) || std::mem::discriminant(default) == std::mem::discriminant(value)
+}
+
+fn default_true() -> bool {
+    true
+}
@@ -0,0 +1,209 @@
+//! Config migration from legacy layouts
+//!
+//! Older configs put a single avatar/background on `[profile]` and a single
+//! color scheme on `[theme]`. The current schema expects these under
+//! `[profile.light]` and `[theme.light]`. This module moves the legacy keys
+//! to their new location while keeping comments and formatting intact.
+//!
+//! Link URLs used to accept any scheme. Schemes such as `mailto:` that a
+//! config already uses are added to `urls.allow_schemes`, so its links keep
+//! passing validation.
+
+use crate::config::LEGACY_KEYS;
+use crate::security;
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+
+/// Result of migrating a config file
+pub struct Migration {
+    /// The rewritten config
+    pub content: String,
+    /// Human-readable description of every change made
+    pub changes: Vec<String>,
+    /// Legacy keys that couldn't be migrated, and why; these don't change
+    /// the config
+    pub skipped: Vec<String>,
+}
+
+impl Migration {
+    /// Returns true if the config was already up to date
+    pub fn is_noop(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Rewrites legacy keys into the current config layout
+///
+/// Each legacy key is moved together with the comments above it. If the
+/// new key already exists, its value wins and the legacy key is dropped.
+/// Schemes used by links but not yet allowed are added to
+/// `urls.allow_schemes`.
+///
+/// # Arguments
+///
+/// * `content` - Contents of the TOML configuration file
+///
+/// # Returns
+///
+/// * `Ok(Migration)` with the rewritten config and a list of changes
+/// * `Err(anyhow::Error)` if the config is not valid TOML
+pub fn migrate_config(content: &str) -> Result<Migration> {
+    let mut document: DocumentMut = content.parse().context("Failed to parse TOML config")?;
+    let mut changes = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (legacy, replacement) in LEGACY_KEYS {
+        let Some((section, key)) = legacy.split_once('.') else {
+            continue;
+        };
+        let Some(target_table) = replacement
+            .strip_prefix(section)
+            .and_then(|rest| rest.trim_start_matches('.').split_once('.'))
+            .map(|(table, _)| table)
+        else {
+            continue;
+        };
+
+        let Some(section_table) = document.get_mut(section).and_then(Item::as_table_like_mut)
+        else {
+            continue;
+        };
+        if !section_table.contains_key(key) {
+            continue;
+        }
+        if section_table
+            .get(target_table)
+            .is_some_and(|t| t.as_table_like().is_none())
+        {
+            skipped.push(format!(
+                "skipped `{}`: `{}.{}` is not a table",
+                legacy, section, target_table
+            ));
+            continue;
+        }
+
+        let Some(mut moved_key) = section_table.key(key).cloned() else {
+            continue;
+        };
+        let Some(item) = section_table.remove(key) else {
+            continue;
+        };
+
+        let target = section_table
+            .entry(target_table)
+            .or_insert_with(|| Item::Table(Table::new()));
+        let is_standard_table = target.is_table();
+        let Some(target) = target.as_table_like_mut() else {
+            continue;
+        };
+
+        if target.contains_key(key) {
+            changes.push(format!(
+                "removed `{}`: `{}` is already set",
+                legacy, replacement
+            ));
+            continue;
+        }
+
+        // Inline tables can't carry comment lines above their keys
+        if !is_standard_table {
+            moved_key.leaf_decor_mut().clear();
+        }
+
+        target.entry_format(&moved_key).or_insert(item);
+        changes.push(format!("moved `{}` to `{}`", legacy, replacement));
+    }
+
+    allow_used_schemes(&mut document, &mut changes, &mut skipped);
+
+    Ok(Migration {
+        content: document.to_string(),
+        changes,
+        skipped,
+    })
+}
+
+/// Adds the schemes of link and social link URLs to `urls.allow_schemes`
+///
+/// `http` and `https` are always allowed, and schemes that can run code in
+/// the page can't be, so neither is added.
+fn allow_used_schemes(
+    document: &mut DocumentMut,
+    changes: &mut Vec<String>,
+    skipped: &mut Vec<String>,
+) {
+    let mut urls = link_urls(document.get("links"));
+    urls.extend(link_urls(
+        document
+            .get("profile")
+            .and_then(|profile| profile.get("social_links")),
+    ));
+
+    let allowed: Vec<String> = document
+        .get("urls")
+        .and_then(|urls| urls.get("allow_schemes"))
+        .and_then(Item::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|scheme| scheme.as_str().map(str::to_ascii_lowercase))
+        .collect();
+    let schemes: BTreeSet<String> = urls
+        .iter()
+        .filter_map(|url| security::url_scheme(url))
+        .filter(|scheme| {
+            !scheme.is_empty()
+                && !security::DEFAULT_SCHEMES.contains(&scheme.as_str())
+                && !security::FORBIDDEN_SCHEMES.contains(&scheme.as_str())
+                && !allowed.contains(scheme)
+        })
+        .collect();
+    if schemes.is_empty() {
+        return;
+    }
+
+    let Some(urls_table) = document
+        .entry("urls")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_like_mut()
+    else {
+        skipped.push("skipped `urls.allow_schemes`: `urls` is not a table".to_string());
+        return;
+    };
+    let Some(allow_schemes) = urls_table
+        .entry("allow_schemes")
+        .or_insert(Item::Value(Value::Array(Array::new())))
+        .as_array_mut()
+    else {
+        skipped.push("skipped `urls.allow_schemes`: it is not an array".to_string());
+        return;
+    };
+    for scheme in schemes {
+        allow_schemes.push(scheme.as_str());
+        changes.push(format!(
+            "added \"{}\" to `urls.allow_schemes`, which links with `{}:` URLs now need",
+            scheme, scheme
+        ));
+    }
+}
+
+/// Returns the `url` of every table in a `[[links]]`-style array
+fn link_urls(item: Option<&Item>) -> Vec<String> {
+    let Some(item) = item else {
+        return Vec::new();
+    };
+    if let Some(tables) = item.as_array_of_tables() {
+        return tables
+            .iter()
+            .filter_map(|table| table.get("url").and_then(Item::as_str))
+            .map(str::to_string)
+            .collect();
+    }
+    item.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_inline_table)
+        .filter_map(|table| table.get("url").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,116 @@
+//! Service worker for installing the page as a Progressive Web App
+//!
+//! The worker precaches the page and the files it links to, so the page
+//! opens without a connection once it has been visited:
+//!
+//! - Navigations go to the network first, so visitors see changes as soon
+//!   as they are online, and fall back to the cached page.
+//! - Other requests to the page's own origin are served from the cache
+//!   first. Requests to other origins are left to the browser.
+//!
+//! The cache is named after a hash of everything it holds. Any change to
+//! the page changes `sw.js`, which makes browsers install the new worker
+//! and drop the old cache.
+
+/// File name of the web app manifest for installable pages
+pub const MANIFEST: &str = "manifest.webmanifest";
+
+/// File name of the service worker
+pub const SERVICE_WORKER: &str = "sw.js";
+
+/// Values accepted for `pwa.display`
+pub const DISPLAY_MODES: &[&str] = &["fullscreen", "standalone", "minimal-ui", "browser"];
+
+/// First line of every worker Genkan writes, so it never replaces a
+/// hand-written `sw.js`
+const HEADER: &str = "// Generated by Genkan";
+
+/// Prefix of the names of caches created by the worker
+const CACHE_PREFIX: &str = "genkan-";
+
+/// Returns the source of the service worker
+///
+/// # Arguments
+///
+/// * `version` - Changes whenever a precached file changes
+/// * `precache` - URLs relative to the worker; the first is the page itself
+pub fn service_worker(version: &str, precache: &[String]) -> String {
+    let precache = serde_json::to_string(precache).unwrap_or_else(|_| "[]".to_string());
+    format!(
+        r#"{HEADER}
+const CACHE = "{CACHE_PREFIX}{version}";
+const PRECACHE = {precache};
+
+self.addEventListener("install", (event) => {{
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      .then((cache) => cache.addAll(PRECACHE.map((url) => new Request(url, {{ cache: "reload" }}))))
+      .then(() => self.skipWaiting())
+  );
+}});
+
+self.addEventListener("activate", (event) => {{
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys
+            .filter((key) => key.startsWith("{CACHE_PREFIX}") && key !== CACHE)
+            .map((key) => caches.delete(key))
+        )
+      )
+      .then(() => self.clients.claim())
+  );
+}});
+
+self.addEventListener("fetch", (event) => {{
+  const request = event.request;
+  if (request.method !== "GET" || new URL(request.url).origin !== self.location.origin) {{
+    return;
+  }}
+  if (request.mode === "navigate") {{
+    event.respondWith(
+      fetch(request).catch(() =>
+        caches.match(PRECACHE[0]).then((cached) => cached || Response.error())
+      )
+    );
+    return;
+  }}
+  event.respondWith(caches.match(request).then((cached) => cached || fetch(request)));
+}});
+"#
+    )
+}
+
+/// Returns a worker that deletes its caches and unregisters itself
+///
+/// Browsers keep using an installed worker until it is replaced, so when
+/// the PWA is turned off, `sw.js` is replaced by this one instead of being
+/// deleted.
+pub fn retired_service_worker() -> String {
+    format!(
+        r#"{HEADER}: the page no longer uses a service worker
+self.addEventListener("install", () => self.skipWaiting());
+
+self.addEventListener("activate", (event) => {{
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys.filter((key) => key.startsWith("{CACHE_PREFIX}")).map((key) => caches.delete(key))
+        )
+      )
+      .then(() => self.registration.unregister())
+  );
+}});
+"#
+    )
+}
+
+/// Returns true if a `sw.js` was written by Genkan
+pub fn is_generated(source: &str) -> bool {
+    source.starts_with(HEADER)
+}
@@ -0,0 +1,337 @@
+//! QR code rendering for the share button and `genkan qr`
+//!
+//! Codes are drawn module by module instead of with the `qrcode` crate's
+//! renderers, so both formats support custom colours and a centre logo:
+//!
+//! - SVG draws each run of dark modules as one path segment, so the code
+//!   stays sharp at any size and zoom level.
+//! - PNG draws every module as a square of whole pixels, so the image is at
+//!   least the requested size and never blurred by scaling.
+//!
+//! A logo hides the modules behind it, which the scanner has to recover
+//! through error correction, so codes with a logo always use the `high`
+//! level.
+
+use crate::images::{self, OutputFormat};
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use qrcode::{Color, QrCode};
+use std::fmt::Write;
+use std::io::Cursor;
+use std::path::Path;
+
+pub use qrcode::EcLevel;
+
+/// Values accepted for `qr.format`
+pub const FORMATS: &[&str] = &["svg", "png"];
+
+/// Values accepted for `qr.error_correction`
+pub const ERROR_CORRECTION_LEVELS: &[&str] = &["low", "medium", "quartile", "high"];
+
+/// Largest `qr.size` and `genkan qr --size`, in pixels
+pub const MAX_SIZE: u32 = 2048;
+
+/// Largest `qr.quiet_zone`, in modules
+///
+/// Scanners need 4; much more only shrinks the code.
+pub const MAX_QUIET_ZONE: u32 = 16;
+
+/// Width of a centre logo as a share of the code's width
+///
+/// A fifth of the width hides 4% of the modules, well within the 30% the
+/// `high` level can recover.
+const LOGO_RATIO: f32 = 0.2;
+
+/// JPEG quality of logos embedded in SVG codes
+const LOGO_QUALITY: u8 = 85;
+
+/// Image format of a rendered QR code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrFormat {
+    Svg,
+    Png,
+}
+
+impl QrFormat {
+    /// Parses a `qr.format` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "svg" => Some(Self::Svg),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+
+    /// Returns the format matching the extension of a file name
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str().and_then(Self::parse)
+    }
+
+    /// Returns the MIME type of the format
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Svg => "image/svg+xml",
+            Self::Png => "image/png",
+        }
+    }
+}
+
+/// Colours, quiet zone and error correction of a QR code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QrStyle {
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+    /// Blank border around the code, in modules
+    pub quiet_zone: u32,
+    pub error_correction: EcLevel,
+}
+
+impl Default for QrStyle {
+    fn default() -> Self {
+        Self {
+            foreground: [0, 0, 0],
+            background: [255, 255, 255],
+            quiet_zone: 4,
+            error_correction: EcLevel::M,
+        }
+    }
+}
+
+/// Parses a `qr.error_correction` value
+pub fn parse_error_correction(value: &str) -> Option<EcLevel> {
+    match value.to_ascii_lowercase().as_str() {
+        "low" => Some(EcLevel::L),
+        "medium" => Some(EcLevel::M),
+        "quartile" => Some(EcLevel::Q),
+        "high" => Some(EcLevel::H),
+        _ => None,
+    }
+}
+
+/// Parses a `#rgb` or `#rrggbb` colour
+pub fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+        3 => Some([
+            channel(&hex[0..1])? * 17,
+            channel(&hex[1..2])? * 17,
+            channel(&hex[2..3])? * 17,
+        ]),
+        6 => Some([
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        ]),
+        _ => None,
+    }
+}
+
+/// Returns the WCAG contrast ratio of two colours, from 1 (none) to 21
+pub fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f32 {
+    let luminance = |color: [u8; 3]| {
+        let linear = color.map(|channel| {
+            let c = f32::from(channel) / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        0.2126 * linear[0] + 0.7152 * linear[1] + 0.0722 * linear[2]
+    };
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Renders a QR code
+///
+/// # Arguments
+///
+/// * `data` - Text to encode, usually the page URL
+/// * `style` - Colours, quiet zone and error correction
+/// * `format` - SVG or PNG
+/// * `size` - Width and height in pixels; PNGs are rounded up to a whole
+///   number of pixels per module
+/// * `logo` - Image to show in the centre, in any format the `image` crate reads
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` with the encoded image
+/// * `Err(anyhow::Error)` if the data doesn't fit in a QR code, the image
+///   would be too large or the logo couldn't be decoded
+pub fn render(
+    data: &str,
+    style: &QrStyle,
+    format: QrFormat,
+    size: u32,
+    logo: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    let level = match logo {
+        Some(_) => EcLevel::H,
+        None => style.error_correction,
+    };
+    let code = QrCode::with_error_correction_level(data.as_bytes(), level)
+        .context("Failed to create QR code")?;
+    let modules = Modules::new(&code, style.quiet_zone, logo.is_some())?;
+    match format {
+        QrFormat::Svg => render_svg(&modules, style, size.max(1), logo).map(String::into_bytes),
+        QrFormat::Png => render_png(&modules, style, size.max(1), logo),
+    }
+}
+
+/// The dark modules of a code including its quiet zone
+struct Modules {
+    dark: Vec<bool>,
+    /// Width in modules, including the quiet zone
+    width: u32,
+    /// First module and width in modules of the square left for the logo
+    logo: Option<(u32, u32)>,
+}
+
+impl Modules {
+    fn new(code: &QrCode, quiet_zone: u32, with_logo: bool) -> Result<Self> {
+        let code_width = code.width() as u32;
+        let width = quiet_zone
+            .checked_mul(2)
+            .and_then(|border| border.checked_add(code_width))
+            .context("QR code quiet zone is too large")?;
+        let area = width
+            .checked_mul(width)
+            .context("QR code quiet zone is too large")?;
+        let colors = code.to_colors();
+
+        let mut dark = vec![false; area as usize];
+        for y in 0..code_width {
+            for x in 0..code_width {
+                if colors[(y * code_width + x) as usize] == Color::Dark {
+                    dark[((y + quiet_zone) * width + x + quiet_zone) as usize] = true;
+                }
+            }
+        }
+
+        let logo = with_logo.then(|| {
+            let mut logo_width = ((code_width as f32 * LOGO_RATIO).round() as u32).max(1);
+            // Keep the logo centred on whole modules
+            if !(code_width - logo_width).is_multiple_of(2) {
+                logo_width += 1;
+            }
+            (quiet_zone + (code_width - logo_width) / 2, logo_width)
+        });
+        // Modules behind the logo are left out so its edges stay clean
+        if let Some((start, logo_width)) = logo {
+            for y in start..start + logo_width {
+                for x in start..start + logo_width {
+                    dark[(y * width + x) as usize] = false;
+                }
+            }
+        }
+
+        Ok(Self { dark, width, logo })
+    }
+
+    fn is_dark(&self, x: u32, y: u32) -> bool {
+        self.dark[(y * self.width + x) as usize]
+    }
+}
+
+fn render_svg(
+    modules: &Modules,
+    style: &QrStyle,
+    size: u32,
+    logo: Option<&[u8]>,
+) -> Result<String> {
+    let width = modules.width;
+    let mut path = String::new();
+    for y in 0..width {
+        let mut x = 0;
+        while x < width {
+            if !modules.is_dark(x, y) {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < width && modules.is_dark(x, y) {
+                x += 1;
+            }
+            let _ = write!(path, "M{} {}h{}v1h-{}z", start, y, x - start, x - start);
+        }
+    }
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {width}" width="{size}" height="{size}" shape-rendering="crispEdges"><rect width="{width}" height="{width}" fill="{}"/><path fill="{}" d="{path}"/>"#,
+        hex(style.background),
+        hex(style.foreground),
+    );
+    if let (Some(logo), Some((start, logo_width))) = (logo, modules.logo) {
+        // Sharp on 3x screens, without embedding the full-size avatar
+        let pixels = (size as f32 * logo_width as f32 / width as f32 * 3.0).ceil() as u32;
+        let data = images::optimize(logo, Some(pixels.max(1)), OutputFormat::Auto, LOGO_QUALITY)
+            .context("Failed to resize QR code logo")?;
+        let mime_type = images::mime_type(&data).context("Unknown QR code logo format")?;
+        let base64_data = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data);
+        // Inset by half a module so the logo doesn't touch the code
+        let _ = write!(
+            svg,
+            r#"<image href="data:{};base64,{}" x="{}.5" y="{}.5" width="{}" height="{}"/>"#,
+            mime_type,
+            base64_data,
+            start,
+            start,
+            logo_width - 1,
+            logo_width - 1
+        );
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+fn render_png(
+    modules: &Modules,
+    style: &QrStyle,
+    size: u32,
+    logo: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    let scale = size.div_ceil(modules.width);
+    let pixels = modules
+        .width
+        .checked_mul(scale)
+        .context("QR code is too large")?;
+    let [fr, fg, fb] = style.foreground;
+    let [br, bg, bb] = style.background;
+    let mut img = RgbaImage::from_fn(pixels, pixels, |x, y| {
+        if modules.is_dark(x / scale, y / scale) {
+            Rgba([fr, fg, fb, 255])
+        } else {
+            Rgba([br, bg, bb, 255])
+        }
+    });
+
+    if let (Some(logo), Some((start, logo_width))) = (logo, modules.logo) {
+        // Inset by half a module so the logo doesn't touch the code
+        let inset = scale / 2;
+        let box_size = (logo_width * scale).saturating_sub(2 * inset).max(1);
+        let logo = image::load_from_memory(logo)
+            .context("Failed to load QR code logo")?
+            .resize(box_size, box_size, FilterType::Lanczos3)
+            .to_rgba8();
+        let x = start * scale + inset + (box_size - logo.width()) / 2;
+        let y = start * scale + inset + (box_size - logo.height()) / 2;
+        image::imageops::overlay(&mut img, &logo, i64::from(x), i64::from(y));
+    }
+
+    // Both colours are opaque, so the alpha channel can go
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(img).to_rgb8())
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .context("Failed to encode QR code as PNG")?;
+    Ok(png)
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
@@ -0,0 +1,138 @@
+//! Links that are only shown between two moments
+//!
+//! Links and social links can set `visible_from` and `visible_until`. Each
+//! build compares them with the current time (or `genkan build --at`):
+//!
+//! - Links whose `visible_until` has passed are left out.
+//! - Links whose `visible_from` is still ahead are left out, or with
+//!   `build.upcoming_links = "reveal"` written hidden, for the theme's
+//!   script to show once the time comes.
+//! - Links that are shown carry their `visible_until`, so the script hides
+//!   them when it passes even if the page isn't rebuilt.
+//!
+//! Times without a UTC offset are in the build machine's time zone, and a
+//! date on its own means midnight at the start of that day.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc};
+
+/// Values accepted for `build.upcoming_links`
+pub const UPCOMING_LINK_MODES: &[&str] = &["drop", "reveal"];
+
+/// What happens to links whose `visible_from` is still ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpcomingLinks {
+    /// Left out of the page
+    Drop,
+    /// Written hidden, for the theme's script to show
+    Reveal,
+}
+
+impl UpcomingLinks {
+    /// Parses a `build.upcoming_links` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "drop" => Some(Self::Drop),
+            "reveal" => Some(Self::Reveal),
+            _ => None,
+        }
+    }
+}
+
+/// Formats accepted for times without a UTC offset
+const LOCAL_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parses a `visible_from`, `visible_until` or `--at` value
+///
+/// Accepts RFC 3339 (`2025-12-24T18:00:00+01:00`), a date and time without
+/// an offset (`2025-12-24T18:00` or `2025-12-24 18:00:00`), or a date
+/// (`2025-12-24`).
+///
+/// # Returns
+///
+/// * `Ok(DateTime<Utc>)` with the moment
+/// * `Err(String)` describing why the value isn't a date and time
+pub fn parse_datetime(value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+    let naive = LOCAL_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .ok_or_else(|| format!("'{}' is not a date or date and time", value))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok_or_else(|| format!("'{}' doesn't exist in the local time zone", value))
+}
+
+/// Whether a scheduled link is shown at a given moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// `visible_from` is still ahead
+    Upcoming,
+    Visible,
+    /// `visible_until` has passed
+    Expired,
+}
+
+/// The moments a link is shown between
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Schedule {
+    pub from: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Schedule {
+    /// Reads a link's `visible_from` and `visible_until`
+    ///
+    /// Values that can't be parsed are ignored; `Config::diagnose` reports them.
+    pub fn new(from: Option<&str>, until: Option<&str>) -> Self {
+        let parse = |value: Option<&str>| value.and_then(|value| parse_datetime(value).ok());
+        Self {
+            from: parse(from),
+            until: parse(until),
+        }
+    }
+
+    /// Returns whether the link is shown at `now`
+    pub fn visibility(&self, now: DateTime<Utc>) -> Visibility {
+        if self.until.is_some_and(|until| until <= now) {
+            Visibility::Expired
+        } else if self.from.is_some_and(|from| from > now) {
+            Visibility::Upcoming
+        } else {
+            Visibility::Visible
+        }
+    }
+
+    /// Returns the `schedule` template value of a link that is written to
+    /// the page, or null if nothing changes after `now`
+    ///
+    /// `from` and `until` are the moments the theme's script has to show
+    /// or hide the link at, and `upcoming` marks links written hidden.
+    pub fn value(&self, now: DateTime<Utc>) -> serde_json::Value {
+        let format = |datetime: DateTime<Utc>| datetime.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let from = self.from.filter(|&from| from > now);
+        let until = self.until.filter(|&until| until > now);
+        if from.is_none() && until.is_none() {
+            return serde_json::Value::Null;
+        }
+        serde_json::json!({
+            "from": from.map(format),
+            "until": until.map(format),
+            "upcoming": from.is_some(),
+        })
+    }
+}
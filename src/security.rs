@@ -0,0 +1,170 @@
+//! Checks for config values that end up in URLs and stylesheets
+//!
+//! Link URLs are written into `href` attributes, and colors, lengths and
+//! fonts are written into `style.css` or inline `style` attributes, none of
+//! which HTML escaping protects. These checks keep a config from running
+//! script through a `javascript:` link or breaking out of a CSS declaration.
+
+/// Schemes that are always allowed in links
+pub const DEFAULT_SCHEMES: &[&str] = &["http", "https"];
+
+/// Schemes that can never be allowed because they can run code in the page
+pub const FORBIDDEN_SCHEMES: &[&str] = &["javascript", "vbscript", "data", "file", "blob"];
+
+/// Units accepted in CSS lengths such as `height` and `link_spacing`
+const CSS_LENGTH_UNITS: &[&str] = &[
+    "px", "rem", "em", "%", "vh", "vw", "vmin", "vmax", "ch", "ex", "pt", "pc", "cm", "mm", "in",
+];
+
+/// CSS color functions accepted in color fields
+const CSS_COLOR_FUNCTIONS: &[&str] = &[
+    "rgb", "rgba", "hsl", "hsla", "hwb", "lab", "lch", "oklab", "oklch", "color",
+];
+
+/// Returns the lowercased scheme of a URL, or `None` for relative URLs
+///
+/// Browsers ignore tabs and newlines anywhere in a URL and leading control
+/// characters, so `java\tscript:` is treated as `javascript:` here too.
+pub fn url_scheme(url: &str) -> Option<String> {
+    let normalized: String = url
+        .trim_start_matches(|c: char| c.is_ascii_control() || c == ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+
+    let end = normalized.find([':', '/', '?', '#'])?;
+    if !normalized[end..].starts_with(':') {
+        return None;
+    }
+    let scheme = &normalized[..end];
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    // Anything else before the colon (e.g. `a b:`) is still not a relative path
+    Some(if valid {
+        scheme.to_ascii_lowercase()
+    } else {
+        String::new()
+    })
+}
+
+/// Checks a link URL against the allowed schemes
+///
+/// # Arguments
+///
+/// * `url` - The URL from the config
+/// * `extra_schemes` - Schemes allowed in addition to http and https
+/// * `allow_relative` - Whether relative URLs such as `/about` are allowed
+///
+/// # Returns
+///
+/// * `Ok(())` if the URL may be used
+/// * `Err(String)` describing why it was rejected
+pub fn check_url(url: &str, extra_schemes: &[String], allow_relative: bool) -> Result<(), String> {
+    match url_scheme(url) {
+        Some(scheme) if scheme.is_empty() => Err("URL has an invalid scheme".to_string()),
+        Some(scheme) if FORBIDDEN_SCHEMES.contains(&scheme.as_str()) => Err(format!(
+            "`{}:` URLs can run code in the page and are not allowed",
+            scheme
+        )),
+        Some(scheme)
+            if DEFAULT_SCHEMES.contains(&scheme.as_str())
+                || extra_schemes
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(&scheme)) =>
+        {
+            Ok(())
+        }
+        Some(scheme) => Err(format!("`{}:` URLs are not allowed", scheme)),
+        // Protocol-relative URLs use the page's scheme (http or https)
+        None if url.trim_start().starts_with("//") => Ok(()),
+        None if allow_relative => Ok(()),
+        None => Err("relative URLs are not allowed".to_string()),
+    }
+}
+
+/// Returns true if `value` is a plain CSS length such as `24px`, `1.5rem` or `0`
+pub fn is_css_length(value: &str) -> bool {
+    let value = value.trim();
+    if value == "0" {
+        return true;
+    }
+    let Some(unit) = CSS_LENGTH_UNITS
+        .iter()
+        .filter(|unit| value.to_ascii_lowercase().ends_with(*unit))
+        .max_by_key(|unit| unit.len())
+    else {
+        return false;
+    };
+    let number = &value[..value.len() - unit.len()];
+    !number.is_empty()
+        && number
+            .parse::<f64>()
+            .is_ok_and(|n| n.is_finite() && n >= 0.0)
+        && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Returns true if `value` is a CSS color
+///
+/// Accepts hex colors, named colors (including `transparent` and
+/// `currentColor`), and color functions such as `rgba(0, 0, 0, 0.5)`.
+pub fn is_css_color(value: &str) -> bool {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        return matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return true;
+    }
+    let Some((function, rest)) = value.split_once('(') else {
+        return false;
+    };
+    let Some(arguments) = rest.strip_suffix(')') else {
+        return false;
+    };
+    CSS_COLOR_FUNCTIONS.contains(&function.trim().to_ascii_lowercase().as_str())
+        && arguments.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, ' ' | ',' | '.' | '%' | '/' | '-' | '+')
+        })
+}
+
+/// Checks that a free-form CSS value can't escape its declaration
+///
+/// Used for values such as fonts and backgrounds that are too varied to
+/// validate precisely. Rejects anything that could end the declaration,
+/// rule or `<style>` element, or open a comment or escape sequence.
+///
+/// # Returns
+///
+/// * `Ok(())` if the value is safe to write into a stylesheet
+/// * `Err(String)` naming the offending character
+pub fn check_css_value(value: &str) -> Result<(), String> {
+    if let Some(c) = value
+        .chars()
+        .find(|c| matches!(c, ';' | '{' | '}' | '<' | '>' | '\\' | '@') || c.is_control())
+    {
+        return Err(format!(
+            "`{}` is not allowed in CSS values",
+            c.escape_default()
+        ));
+    }
+    if value.contains("/*") {
+        return Err("CSS comments are not allowed in CSS values".to_string());
+    }
+    for quote in ['"', '\''] {
+        if value.matches(quote).count() % 2 != 0 {
+            return Err(format!("unbalanced `{}` in CSS value", quote));
+        }
+    }
+    let depth = value.chars().try_fold(0i32, |depth, c| match c {
+        '(' => Some(depth + 1),
+        ')' if depth == 0 => None,
+        ')' => Some(depth - 1),
+        _ => Some(depth),
+    });
+    if depth != Some(0) {
+        return Err("unbalanced parentheses in CSS value".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+//! Development server with file watching and live reload
+//!
+//! This module powers `genkan serve`. It:
+//! - Serves the generated output directory over HTTP
+//! - Polls the config, theme and local asset files for changes
+//! - Rebuilds the site when any watched file changes
+//! - Pushes a reload to open browser tabs via a long-polling endpoint
+
+use crate::csp;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tiny_http::{Header, Request, Response, Server};
+
+/// Endpoint polled by the live reload script
+const RELOAD_ENDPOINT: &str = "/__genkan/reload";
+
+/// How long a reload request is held open before the browser re-polls
+const RELOAD_POLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// How often watched files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Script injected into served HTML pages to reload them after a rebuild
+const RELOAD_SCRIPT: &str = r#"
+(function() {
+    var version = null;
+    function poll() {
+        fetch('/__genkan/reload' + (version === null ? '' : '?v=' + version), { cache: 'no-store' })
+            .then(function(response) { return response.text(); })
+            .then(function(latest) {
+                if (version !== null && latest !== version) {
+                    window.location.reload();
+                    return;
+                }
+                version = latest;
+                poll();
+            })
+            .catch(function() { setTimeout(poll, 1000); });
+    }
+    poll();
+})();
+"#;
+
+/// Options for the development server
+pub struct ServeOptions {
+    /// Address to bind, e.g. `127.0.0.1`
+    pub host: String,
+    /// Port to listen on
+    pub port: u16,
+    /// Directory containing the generated site
+    pub output_dir: PathBuf,
+    /// Files that are always watched, even when a build fails (usually the config file)
+    pub always_watch: Vec<PathBuf>,
+}
+
+/// Build counter shared between the watcher and HTTP handlers
+///
+/// Every successful rebuild bumps the version and wakes up all pending
+/// reload requests.
+#[derive(Default)]
+struct ReloadState {
+    version: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl ReloadState {
+    fn bump(&self) {
+        let mut version = self.version.lock().unwrap_or_else(|e| e.into_inner());
+        *version += 1;
+        self.changed.notify_all();
+    }
+
+    /// Waits until the version differs from `seen` or the timeout expires
+    fn wait_for_change(&self, seen: Option<u64>, timeout: Duration) -> u64 {
+        let version = self.version.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(seen) = seen else {
+            return *version;
+        };
+        let (version, _) = self
+            .changed
+            .wait_timeout_while(version, timeout, |v| *v == seen)
+            .unwrap_or_else(|e| e.into_inner());
+        *version
+    }
+}
+
+/// Snapshot of modification times for a set of watched paths
+///
+/// Directories are walked recursively. Missing files are recorded too,
+/// so that creating a previously missing file counts as a change.
+#[derive(Debug, PartialEq, Eq)]
+pub struct WatchSnapshot {
+    entries: BTreeMap<PathBuf, Option<SystemTime>>,
+}
+
+impl WatchSnapshot {
+    /// Captures the current state of the given paths
+    pub fn capture(paths: &[PathBuf]) -> Self {
+        let mut entries = BTreeMap::new();
+        for path in paths {
+            collect_mtimes(path, &mut entries);
+        }
+        Self { entries }
+    }
+}
+
+fn collect_mtimes(path: &Path, entries: &mut BTreeMap<PathBuf, Option<SystemTime>>) {
+    if path.is_dir() {
+        if let Ok(read_dir) = fs::read_dir(path) {
+            for entry in read_dir.flatten() {
+                collect_mtimes(&entry.path(), entries);
+            }
+        }
+        return;
+    }
+
+    let mtime = fs::metadata(path).and_then(|m| m.modified()).ok();
+    entries.insert(path.to_path_buf(), mtime);
+}
+
+/// Injects the live reload script into an HTML document
+///
+/// The script is placed before `</body>` when present. Minified output
+/// usually omits the closing tag, in which case it is appended. The page's
+/// Content-Security-Policy is extended to allow the script and its polling.
+pub fn inject_reload_script(html: &str) -> String {
+    let hash = csp::hash_source(RELOAD_SCRIPT);
+    let html = csp::extend_page_policy(html, &[("script-src", &hash), ("connect-src", "'self'")]);
+    let script = format!("<script>{}</script>", RELOAD_SCRIPT);
+    match html.rfind("</body>") {
+        Some(idx) => format!("{}{}{}", &html[..idx], script, &html[idx..]),
+        None => format!("{}{}", html, script),
+    }
+}
+
+/// Runs the development server until the process is interrupted
+///
+/// # Arguments
+///
+/// * `options` - Server address, output directory and always-watched files
+/// * `build` - Rebuilds the site and returns the paths to watch for the next rebuild
+///
+/// # Returns
+///
+/// * `Err(anyhow::Error)` if the server could not be started
+pub fn serve<F>(options: ServeOptions, mut build: F) -> Result<()>
+where
+    F: FnMut() -> Result<Vec<PathBuf>>,
+{
+    let mut watched = options.always_watch.clone();
+    match build() {
+        Ok(paths) => watched.extend(paths),
+        Err(e) => eprintln!("Build failed: {:#}\n", e),
+    }
+
+    let address = format!("{}:{}", options.host, options.port);
+    let server = Server::http(&address)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .with_context(|| format!("Failed to start server on {}", address))?;
+
+    let state = Arc::new(ReloadState::default());
+    let output_dir = options.output_dir.clone();
+    let server_state = Arc::clone(&state);
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let output_dir = output_dir.clone();
+            let state = Arc::clone(&server_state);
+            thread::spawn(move || handle_request(request, &output_dir, &state));
+        }
+    });
+
+    println!(
+        "\nServing {} at http://{}",
+        options.output_dir.display(),
+        address
+    );
+    println!("Watching for changes. Press Ctrl+C to stop.\n");
+
+    let mut snapshot = WatchSnapshot::capture(&watched);
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+
+        let current = WatchSnapshot::capture(&watched);
+        if current == snapshot {
+            continue;
+        }
+
+        println!("Change detected, rebuilding...\n");
+        match build() {
+            Ok(paths) => {
+                watched = options.always_watch.clone();
+                watched.extend(paths);
+                state.bump();
+                println!("Reloaded open pages\n");
+            }
+            Err(e) => eprintln!("Build failed: {:#}\n", e),
+        }
+        snapshot = WatchSnapshot::capture(&watched);
+    }
+}
+
+fn handle_request(request: Request, output_dir: &Path, state: &ReloadState) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    let result = if path == RELOAD_ENDPOINT {
+        let seen = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("v="))
+            .and_then(|v| v.parse().ok());
+        let version = state.wait_for_change(seen, RELOAD_POLL_TIMEOUT);
+        respond(request, 200, "text/plain", version.to_string().into_bytes())
+    } else {
+        match resolve_request_path(output_dir, path).and_then(|f| fs::read(&f).ok().map(|b| (f, b)))
+        {
+            Some((file, body)) => {
+                let content_type = content_type_for(&file);
+                let body = if content_type.starts_with("text/html") {
+                    inject_reload_script(&String::from_utf8_lossy(&body)).into_bytes()
+                } else {
+                    body
+                };
+                respond(request, 200, content_type, body)
+            }
+            None => respond(request, 404, "text/plain", b"Not Found".to_vec()),
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("Warning: Failed to send response for {}: {}", url, e);
+    }
+}
+
+/// Sends a response with caching disabled, so edits always show up
+fn respond(
+    request: Request,
+    status: u16,
+    content_type: &str,
+    body: Vec<u8>,
+) -> std::io::Result<()> {
+    let mut response = Response::from_data(body).with_status_code(status);
+    for (field, value) in [
+        ("Content-Type", content_type),
+        ("Cache-Control", "no-store"),
+    ] {
+        if let Ok(header) = Header::from_bytes(field, value) {
+            response.add_header(header);
+        }
+    }
+    request.respond(response)
+}
+
+/// Maps a request path onto a file inside the output directory
+///
+/// Rejects paths that would escape the output directory and serves
+/// `index.html` for directory requests.
+fn resolve_request_path(output_dir: &Path, url_path: &str) -> Option<PathBuf> {
+    let relative = Path::new(url_path.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let mut file = output_dir.join(relative);
+    if file.is_dir() {
+        file = file.join("index.html");
+    }
+    file.is_file().then_some(file)
+}
+
+fn content_type_for(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") | Some("webmanifest") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,349 @@
+//! SVG sanitising for inline icons
+//!
+//! Icons are inlined into the page so they can inherit the text color, which
+//! means anything active inside a downloaded SVG would run in our page. This
+//! module parses the SVG and writes it back out with only known-safe content:
+//!
+//! - Elements outside a fixed allowlist (`<script>`, `<foreignObject>`,
+//!   `<style>`, `<image>`, animations, ...) are removed with their children.
+//! - Event handler attributes (`on*`) and `javascript:` values are dropped.
+//! - `href`s and `url(...)` references are kept only when they point at an
+//!   element inside the same SVG. Values using any other CSS function than
+//!   colors, transforms and filters are dropped.
+//! - IDs are prefixed with a hash of the source, so two inlined icons using
+//!   the same `id` don't collide.
+//! - `fill` and `stroke` colors are rewritten to `currentColor`, and the root
+//!   `width`/`height` are removed so CSS controls the size.
+
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node, NodeType, ParsingOptions};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+const SVG_NS: &str = "http://www.w3.org/2000/svg";
+const XLINK_NS: &str = "http://www.w3.org/1999/xlink";
+
+/// Elements that are kept; everything else is removed along with its children
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "svg",
+    "g",
+    "defs",
+    "symbol",
+    "use",
+    "title",
+    "desc",
+    "path",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "rect",
+    "text",
+    "tspan",
+    "textPath",
+    "linearGradient",
+    "radialGradient",
+    "stop",
+    "clipPath",
+    "mask",
+    "pattern",
+    "marker",
+    "filter",
+    "feBlend",
+    "feColorMatrix",
+    "feComponentTransfer",
+    "feComposite",
+    "feDropShadow",
+    "feFlood",
+    "feFuncA",
+    "feFuncB",
+    "feFuncG",
+    "feFuncR",
+    "feGaussianBlur",
+    "feMerge",
+    "feMergeNode",
+    "feMorphology",
+    "feOffset",
+];
+
+/// Elements replaced by their children (links could carry `javascript:` URLs)
+const UNWRAPPED_ELEMENTS: &[&str] = &["a"];
+
+/// Attributes whose value may contain `url(#id)` references
+const PAINT_ATTRIBUTES: &[&str] = &["fill", "stroke"];
+
+/// Functions kept in attribute and style values besides `url()`, in
+/// lowercase; values with any other function are dropped, since some (such
+/// as `image-set()`) load external files
+const ALLOWED_FUNCTIONS: &[&str] = &[
+    "rgb",
+    "rgba",
+    "hsl",
+    "hsla",
+    "calc",
+    "matrix",
+    "translate",
+    "translatex",
+    "translatey",
+    "scale",
+    "scalex",
+    "scaley",
+    "rotate",
+    "skewx",
+    "skewy",
+    "blur",
+    "brightness",
+    "contrast",
+    "drop-shadow",
+    "grayscale",
+    "hue-rotate",
+    "invert",
+    "opacity",
+    "saturate",
+    "sepia",
+];
+
+/// Sanitises an SVG document for inlining into the page
+///
+/// # Arguments
+///
+/// * `svg_data` - Raw SVG file contents
+///
+/// # Returns
+///
+/// * `Ok(String)` with the cleaned `<svg>` element
+/// * `Err(anyhow::Error)` if the data is not a well-formed SVG document
+pub fn sanitize_svg(svg_data: &[u8]) -> Result<String> {
+    let text = std::str::from_utf8(svg_data).context("Failed to parse SVG as UTF-8")?;
+    // Editors often emit a DOCTYPE; roxmltree limits entity expansion itself
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let document = Document::parse_with_options(text, options).context("Failed to parse SVG")?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "svg" || root.tag_name().namespace() != Some(SVG_NS) {
+        anyhow::bail!(
+            "Not an SVG document (root element is <{}>)",
+            root.tag_name().name()
+        );
+    }
+
+    let sanitizer = Sanitizer {
+        prefix: format!("g{:08x}-", fnv1a(svg_data) as u32),
+        ids: root
+            .descendants()
+            .filter_map(|n| n.attribute("id"))
+            .map(str::to_string)
+            .collect(),
+    };
+
+    let mut out = String::new();
+    sanitizer.write_element(&mut out, root, true);
+    Ok(out)
+}
+
+struct Sanitizer {
+    /// Prepended to every ID in the document
+    prefix: String,
+    /// IDs defined in the document, the only valid reference targets
+    ids: HashSet<String>,
+}
+
+impl Sanitizer {
+    fn write_element(&self, out: &mut String, node: Node, is_root: bool) {
+        let name = node.tag_name().name();
+        if node.tag_name().namespace() != Some(SVG_NS) {
+            return;
+        }
+        if UNWRAPPED_ELEMENTS.contains(&name) {
+            self.write_children(out, node);
+            return;
+        }
+        if !ALLOWED_ELEMENTS.contains(&name) {
+            return;
+        }
+
+        out.push('<');
+        out.push_str(name);
+        if is_root {
+            out.push_str(&format!(r#" xmlns="{}""#, SVG_NS));
+        }
+
+        for attribute in node.attributes() {
+            let attr_name = match attribute.namespace() {
+                None => attribute.name(),
+                // Serialised as the SVG 2 `href`, so no xlink namespace is needed
+                Some(XLINK_NS) if attribute.name() == "href" => "href",
+                Some(_) => continue,
+            };
+            if is_root && matches!(attr_name, "width" | "height" | "xmlns") {
+                continue;
+            }
+            if let Some(value) = self.sanitize_attribute(attr_name, attribute.value()) {
+                let _ = write!(out, r#" {}="{}""#, attr_name, escape(&value));
+            }
+        }
+
+        // Keep the aspect ratio once the fixed size is gone
+        if is_root
+            && node.attribute("viewBox").is_none()
+            && let (Some(width), Some(height)) = (
+                node.attribute("width").and_then(parse_length),
+                node.attribute("height").and_then(parse_length),
+            )
+        {
+            let _ = write!(out, r#" viewBox="0 0 {} {}""#, width, height);
+        }
+
+        out.push('>');
+        self.write_children(out, node);
+        let _ = write!(out, "</{}>", name);
+    }
+
+    fn write_children(&self, out: &mut String, node: Node) {
+        for child in node.children() {
+            match child.node_type() {
+                NodeType::Element => self.write_element(out, child, false),
+                NodeType::Text => out.push_str(&escape(child.text().unwrap_or(""))),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the cleaned attribute value, or `None` to drop the attribute
+    fn sanitize_attribute(&self, name: &str, value: &str) -> Option<String> {
+        let lowered: String = value
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if name.to_ascii_lowercase().starts_with("on") || lowered.contains("javascript:") {
+            return None;
+        }
+
+        match name {
+            "id" => Some(format!("{}{}", self.prefix, value)),
+            "href" => {
+                let target = value.trim().strip_prefix('#')?;
+                self.ids
+                    .contains(target)
+                    .then(|| format!("#{}{}", self.prefix, target))
+            }
+            "style" => self.sanitize_style(value),
+            _ if PAINT_ATTRIBUTES.contains(&name) => Some(self.paint(value)),
+            _ => self.rewrite_urls(value),
+        }
+    }
+
+    /// Rewrites `fill`/`stroke` values to `currentColor`
+    ///
+    /// `none` and references to gradients or patterns in the document are kept.
+    fn paint(&self, value: &str) -> String {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("currentColor") {
+            return value.to_string();
+        }
+        if value.to_ascii_lowercase().starts_with("url(") {
+            return self
+                .rewrite_urls(value)
+                .unwrap_or_else(|| "currentColor".to_string());
+        }
+        "currentColor".to_string()
+    }
+
+    /// Cleans each declaration of a `style` attribute
+    fn sanitize_style(&self, value: &str) -> Option<String> {
+        let mut declarations = Vec::new();
+        for declaration in value.split(';') {
+            let Some((property, property_value)) = declaration.split_once(':') else {
+                continue;
+            };
+            let property = property.trim().to_ascii_lowercase();
+            let lowered = property_value.to_ascii_lowercase();
+            if lowered.contains("expression(") || lowered.contains('@') || lowered.contains('\\') {
+                continue;
+            }
+            let cleaned = if PAINT_ATTRIBUTES.contains(&property.as_str()) {
+                Some(self.paint(property_value))
+            } else {
+                self.rewrite_urls(property_value.trim())
+            };
+            if let Some(cleaned) = cleaned {
+                declarations.push(format!("{}:{}", property, cleaned));
+            }
+        }
+        (!declarations.is_empty()).then(|| declarations.join(";"))
+    }
+
+    /// Prefixes `url(#id)` references, or returns `None` if a reference
+    /// points outside the document or the value uses a function outside
+    /// [`ALLOWED_FUNCTIONS`]
+    ///
+    /// CSS function names are case-insensitive, so `URL(...)` is a
+    /// reference too. Escapes could spell out any function name and are
+    /// rejected.
+    fn rewrite_urls(&self, value: &str) -> Option<String> {
+        if value.contains('\\') {
+            return None;
+        }
+        // ASCII lowercasing keeps byte offsets, so both strings share indices
+        let lowered = value.to_ascii_lowercase();
+        let mut result = String::new();
+        let mut pos = 0;
+        while let Some(offset) = lowered[pos..].find('(') {
+            let open = pos + offset;
+            let name_start = lowered[..open]
+                .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                .len();
+            let name = &lowered[name_start..open];
+            if name != "url" {
+                if !name.is_empty() && !ALLOWED_FUNCTIONS.contains(&name) {
+                    return None;
+                }
+                result.push_str(&value[pos..=open]);
+                pos = open + 1;
+                continue;
+            }
+            result.push_str(&value[pos..name_start]);
+            let after = &value[open + 1..];
+            let end = after.find(')')?;
+            let target = after[..end].trim().trim_matches(['\'', '"']);
+            let id = target.strip_prefix('#')?;
+            if !self.ids.contains(id) {
+                return None;
+            }
+            let _ = write!(result, "url(#{}{})", self.prefix, id);
+            pos = open + 1 + end + 1;
+        }
+        result.push_str(&value[pos..]);
+        Some(result)
+    }
+}
+
+/// Parses a plain or `px` length such as `24` or `24px`
+fn parse_length(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .trim_end_matches("px")
+        .parse()
+        .ok()
+        .filter(|v: &f64| *v > 0.0)
+}
+
+/// Escapes text for use in XML content and double-quoted attributes
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 64-bit FNV-1a hash, used for stable ID prefixes across builds
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
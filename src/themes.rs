@@ -0,0 +1,1006 @@
+//! Theme discovery and built-in themes
+//!
+//! Themes are looked up in this order:
+//! 1. The project's `themes/` directory (next to `config.toml`)
+//! 2. The user themes directory (`$GENKAN_THEMES_DIR`, or e.g. `~/.local/share/genkan/themes`)
+//! 3. Built-in themes bundled into the binary (`simple`, `doodle`)
+//! 4. The default remote repository
+//!
+//! Themes installed from git with `genkan theme install` are recorded with
+//! their resolved commit in `themes.lock`, and restored from it when missing.
+//!
+//! Built-in themes are written to a versioned cache directory on first use,
+//! so the rest of the pipeline can treat every theme as a plain directory.
+
+use crate::manifest::ThemeManifest;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default theme repository URL
+const DEFAULT_THEME_REPO: &str = "https://github.com/dephilia/genkan.git";
+
+/// Distinguishes temporary files written by concurrent extractions
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A theme bundled into the binary
+pub struct BuiltinTheme {
+    pub name: &'static str,
+    /// Relative file paths and their contents
+    pub files: &'static [(&'static str, &'static str)],
+}
+
+/// Themes shipped with Genkan
+pub const BUILTIN_THEMES: &[BuiltinTheme] = &[
+    BuiltinTheme {
+        name: "simple",
+        files: &[
+            (
+                "template.html",
+                include_str!("../themes/simple/template.html"),
+            ),
+            ("style.css", include_str!("../themes/simple/style.css")),
+            ("script.js", include_str!("../themes/simple/script.js")),
+            (
+                "partials/dark_mode_toggle.html",
+                include_str!("../themes/simple/partials/dark_mode_toggle.html"),
+            ),
+            (
+                "partials/share_modal.html",
+                include_str!("../themes/simple/partials/share_modal.html"),
+            ),
+            (
+                "partials/social_links.html",
+                include_str!("../themes/simple/partials/social_links.html"),
+            ),
+            (
+                "partials/link.html",
+                include_str!("../themes/simple/partials/link.html"),
+            ),
+            (
+                "partials/link_icon.html",
+                include_str!("../themes/simple/partials/link_icon.html"),
+            ),
+            (
+                "partials/section_header.html",
+                include_str!("../themes/simple/partials/section_header.html"),
+            ),
+            (
+                "partials/schedule_attributes.html",
+                include_str!("../themes/simple/partials/schedule_attributes.html"),
+            ),
+            (
+                "partials/footer.html",
+                include_str!("../themes/simple/partials/footer.html"),
+            ),
+            ("theme.toml", include_str!("../themes/simple/theme.toml")),
+        ],
+    },
+    BuiltinTheme {
+        name: "doodle",
+        files: &[
+            ("style.css", include_str!("../themes/doodle/style.css")),
+            ("script.js", include_str!("../themes/doodle/script.js")),
+            ("theme.toml", include_str!("../themes/doodle/theme.toml")),
+        ],
+    },
+];
+
+/// Returns the built-in theme with the given name, if any
+pub fn builtin_theme(name: &str) -> Option<&'static BuiltinTheme> {
+    BUILTIN_THEMES.iter().find(|t| t.name == name)
+}
+
+impl BuiltinTheme {
+    /// Writes the theme files into `dest`
+    ///
+    /// Each file is written to a temporary name and renamed into place, so
+    /// concurrent builds never observe a half-written file.
+    pub fn write_to(&self, dest: &Path) -> Result<()> {
+        for (relative, contents) in self.files {
+            let path = dest.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create theme directory: {}", parent.display())
+                })?;
+            }
+
+            if fs::read_to_string(&path).is_ok_and(|existing| existing == *contents) {
+                continue;
+            }
+
+            let temp_path = path.with_extension(format!(
+                "tmp-{}-{}",
+                std::process::id(),
+                TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::write(&temp_path, contents)
+                .with_context(|| format!("Failed to write theme file: {}", path.display()))?;
+            fs::rename(&temp_path, &path)
+                .with_context(|| format!("Failed to write theme file: {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns a directory containing this theme's files
+    ///
+    /// The files are extracted under the user cache directory, keyed by the
+    /// Genkan version so upgrades pick up theme changes.
+    pub fn materialize(&self) -> Result<PathBuf> {
+        let base = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        let dest = base
+            .join("genkan")
+            .join("builtin-themes")
+            .join(env!("CARGO_PKG_VERSION"))
+            .join(self.name);
+        self.write_to(&dest)
+            .with_context(|| format!("Failed to extract built-in theme '{}'", self.name))?;
+
+        // Drop files left behind by other builds of the same version
+        for entry in fs::read_dir(&dest).into_iter().flatten().flatten() {
+            let name = entry.file_name();
+            let is_temp = name.to_string_lossy().contains(".tmp-");
+            if entry.path().is_file() && !is_temp && !self.files.iter().any(|(f, _)| name == *f) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+
+        Ok(dest)
+    }
+}
+
+/// Returns the per-user themes directory
+///
+/// Uses `$GENKAN_THEMES_DIR` if set, otherwise `genkan/themes` under the
+/// platform data directory (e.g. `~/.local/share/genkan/themes` on Linux).
+pub fn user_themes_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("GENKAN_THEMES_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::data_dir().map(|d| d.join("genkan").join("themes"))
+}
+
+/// Returns the project's themes directory
+pub fn project_themes_dir(project_root: &Path) -> PathBuf {
+    project_root.join("themes")
+}
+
+/// Returns the project's template override directory
+pub fn project_templates_dir(project_root: &Path) -> PathBuf {
+    project_root.join("templates")
+}
+
+/// Copies a built-in theme into the project for customisation
+///
+/// # Arguments
+///
+/// * `project_root` - Directory containing `config.toml`
+/// * `theme_name` - Name of the built-in theme to eject
+/// * `force` - Replace an existing theme directory, including files the
+///   built-in theme doesn't have
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` with the path to the ejected theme
+/// * `Err(anyhow::Error)` if the theme isn't built in or the destination exists
+pub fn eject_theme(project_root: &Path, theme_name: &str, force: bool) -> Result<PathBuf> {
+    check_theme_name(theme_name)?;
+    let Some(theme) = builtin_theme(theme_name) else {
+        let names: Vec<&str> = BUILTIN_THEMES.iter().map(|t| t.name).collect();
+        anyhow::bail!(
+            "'{}' is not a built-in theme. Built-in themes: {}",
+            theme_name,
+            names.join(", ")
+        );
+    };
+
+    let dest = project_themes_dir(project_root).join(theme_name);
+    if dest.exists() {
+        if !force {
+            anyhow::bail!(
+                "{} already exists. Use --force to overwrite it.",
+                dest.display()
+            );
+        }
+        // Files removed from the built-in theme would otherwise linger and
+        // still be picked up as templates
+        fs::remove_dir_all(&dest)
+            .with_context(|| format!("Failed to remove {}", dest.display()))?;
+    }
+
+    theme.write_to(&dest)?;
+    Ok(dest)
+}
+
+/// Maximum depth of a theme inheritance chain
+const MAX_THEME_DEPTH: usize = 8;
+
+/// One theme in an inheritance chain
+#[derive(Debug, Clone)]
+pub struct ThemeLayer {
+    /// Name the theme was looked up by
+    pub name: String,
+    pub path: PathBuf,
+    /// The theme's own manifest, without anything inherited
+    pub manifest: ThemeManifest,
+}
+
+/// A theme together with every theme it extends
+///
+/// Templates are registered under their plain name (`template.html`,
+/// `partials/footer.html`), which resolves to the project's `templates/`
+/// directory or else the most derived theme that has the file, and under
+/// `<theme>/<file>` for every theme in the chain.
+/// A child theme can therefore start with `{% extends "simple/template.html" %}`
+/// and override individual blocks.
+#[derive(Debug, Clone)]
+pub struct ResolvedTheme {
+    /// The theme and its ancestors, most derived first
+    pub layers: Vec<ThemeLayer>,
+    /// Manifest with options and features merged from all ancestors
+    pub manifest: ThemeManifest,
+}
+
+impl ResolvedTheme {
+    /// Loads a theme and resolves its parents through [`find_theme_path`]
+    ///
+    /// # Arguments
+    ///
+    /// * `project_root` - Directory containing `config.toml`
+    /// * `name` - Name of the theme
+    /// * `theme_path` - Path to the theme directory
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ResolvedTheme)` with the full inheritance chain
+    /// * `Err(anyhow::Error)` if a parent can't be found, a manifest is
+    ///   invalid, or the chain is cyclic
+    pub fn resolve(project_root: &Path, name: &str, theme_path: &Path) -> Result<Self> {
+        let mut layers = vec![ThemeLayer {
+            name: name.to_string(),
+            path: theme_path.to_path_buf(),
+            manifest: ThemeManifest::load(theme_path)?,
+        }];
+
+        while let Some(parent) = layers.last().and_then(|l| l.manifest.extends.clone()) {
+            let child = &layers[layers.len() - 1].name;
+            if layers.iter().any(|l| l.name == parent) {
+                anyhow::bail!(
+                    "Theme '{}' extends '{}', which creates an inheritance cycle",
+                    child,
+                    parent
+                );
+            }
+            if layers.len() >= MAX_THEME_DEPTH {
+                anyhow::bail!(
+                    "Theme '{}' is nested more than {} levels deep",
+                    name,
+                    MAX_THEME_DEPTH
+                );
+            }
+
+            let parent_path = find_theme_path(project_root, &parent).with_context(|| {
+                format!("Failed to find theme '{}', extended by '{}'", parent, child)
+            })?;
+            layers.push(ThemeLayer {
+                manifest: ThemeManifest::load(&parent_path)?,
+                name: parent,
+                path: parent_path,
+            });
+        }
+
+        let mut manifest = layers[0].manifest.clone();
+        for layer in &layers[1..] {
+            manifest.inherit(&layer.manifest);
+        }
+
+        Ok(Self { layers, manifest })
+    }
+
+    /// Returns the directories of every theme in the chain
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.layers.iter().map(|l| l.path.clone()).collect()
+    }
+
+    /// Returns every HTML template in the chain as `(name, content)` pairs
+    ///
+    /// Files in the project's `templates/` directory take precedence over
+    /// the theme's, so a site can replace a single partial such as
+    /// `partials/footer.html` without forking the theme.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_root` - Directory containing `config.toml`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<(String, String)>)` ready for `Tera::add_raw_templates`
+    /// * `Err(anyhow::Error)` if no theme in the chain has a `template.html`
+    pub fn templates(&self, project_root: &Path) -> Result<Vec<(String, String)>> {
+        let mut templates = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+
+        let overrides = project_templates_dir(project_root);
+        if overrides.is_dir() {
+            for relative in theme_files(&overrides, "html")? {
+                let path = overrides.join(&relative);
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read template: {}", path.display()))?;
+                seen.insert(relative.clone());
+                templates.push((relative, content));
+            }
+        }
+
+        for layer in &self.layers {
+            for relative in theme_files(&layer.path, "html")? {
+                let path = layer.path.join(&relative);
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read theme file: {}", path.display()))?;
+                if seen.insert(relative.clone()) {
+                    templates.push((relative.clone(), content.clone()));
+                }
+                templates.push((format!("{}/{}", layer.name, relative), content));
+            }
+        }
+
+        if !seen.contains("template.html") {
+            anyhow::bail!("Theme '{}' has no template.html", self.layers[0].name);
+        }
+        Ok(templates)
+    }
+
+    /// Returns the stylesheets to render, parent first, as `(name, content)` pairs
+    pub fn stylesheets(&self) -> Result<Vec<(String, String)>> {
+        self.inherited_files("style.css", |m| m.inherit_css)
+    }
+
+    /// Returns the concatenated JavaScript of the chain, parent first
+    pub fn script(&self) -> Result<String> {
+        let scripts = self.inherited_files("script.js", |m| m.inherit_js)?;
+        Ok(scripts
+            .into_iter()
+            .map(|(_, content)| content)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Collects `filename` from the theme and the ancestors it inherits it from
+    fn inherited_files(
+        &self,
+        filename: &str,
+        inherits: impl Fn(&ThemeManifest) -> bool,
+    ) -> Result<Vec<(String, String)>> {
+        let mut files = Vec::new();
+        for layer in &self.layers {
+            let path = layer.path.join(filename);
+            if path.is_file() {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read theme file: {}", path.display()))?;
+                files.push((format!("{}/{}", layer.name, filename), content));
+            }
+            if !inherits(&layer.manifest) {
+                break;
+            }
+        }
+        files.reverse();
+        Ok(files)
+    }
+}
+
+/// Lists files with the given extension under a theme directory
+///
+/// Paths are relative to `root` and always use `/` as the separator.
+fn theme_files(root: &Path, extension: &str) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read theme directory: {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|e| e == extension)
+                && let Ok(relative) = path.strip_prefix(root)
+            {
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                files.push(parts.join("/"));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Name of the lock file recording installed theme revisions
+pub const LOCK_FILE: &str = "themes.lock";
+
+/// A theme installed from a git repository, as recorded in `themes.lock`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LockedTheme {
+    pub name: String,
+    /// Git URL or local path of the repository
+    pub source: String,
+    /// Branch, tag or commit requested at install time (remote HEAD if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// Commit the theme was installed from
+    pub commit: String,
+    /// Directory of the theme inside the repository (empty for the root)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+}
+
+impl LockedTheme {
+    /// Returns the abbreviated commit hash
+    pub fn short_commit(&self) -> &str {
+        &self.commit[..self.commit.len().min(7)]
+    }
+}
+
+/// Contents of a project's `themes.lock`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ThemeLock {
+    #[serde(default, rename = "theme")]
+    pub themes: Vec<LockedTheme>,
+}
+
+impl ThemeLock {
+    /// Loads `themes.lock` from the project, or an empty lock if it doesn't exist
+    pub fn load(project_root: &Path) -> Result<Self> {
+        let path = project_root.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Writes the lock file, removing it once no themes are locked
+    pub fn save(&self, project_root: &Path) -> Result<()> {
+        let path = project_root.join(LOCK_FILE);
+        if self.themes.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            return Ok(());
+        }
+
+        let content = format!(
+            "# Generated by `genkan theme`. Commit this file to pin theme revisions.\n\n{}",
+            toml::to_string(self).context("Failed to serialize theme lock")?
+        );
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedTheme> {
+        self.themes.iter().find(|t| t.name == name)
+    }
+
+    /// Adds or replaces the entry for `theme.name`
+    pub fn insert(&mut self, theme: LockedTheme) {
+        match self.themes.iter_mut().find(|t| t.name == theme.name) {
+            Some(existing) => *existing = theme,
+            None => {
+                self.themes.push(theme);
+                self.themes.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<LockedTheme> {
+        let index = self.themes.iter().position(|t| t.name == name)?;
+        Some(self.themes.remove(index))
+    }
+}
+
+/// Where a theme listed by [`list_themes`] comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeLocation {
+    Project,
+    User,
+    Builtin,
+}
+
+/// A theme available to a project
+#[derive(Debug, Clone)]
+pub struct AvailableTheme {
+    pub name: String,
+    pub location: ThemeLocation,
+    /// Lock entry, for project themes installed from git
+    pub locked: Option<LockedTheme>,
+}
+
+/// Lists the themes available to a project
+///
+/// Themes are returned in lookup order, so a name appearing more than once
+/// resolves to its first entry. Locked themes whose directory is missing are
+/// included, since they are restored on the next build.
+///
+/// # Arguments
+///
+/// * `project_root` - Directory containing `config.toml`
+///
+/// # Returns
+///
+/// * `Ok(Vec<AvailableTheme>)` with every project, user and built-in theme
+/// * `Err(anyhow::Error)` if `themes.lock` couldn't be read
+pub fn list_themes(project_root: &Path) -> Result<Vec<AvailableTheme>> {
+    let lock = ThemeLock::load(project_root)?;
+    let mut themes = Vec::new();
+
+    let mut project_names = theme_dirs(&project_themes_dir(project_root));
+    for locked in &lock.themes {
+        if !project_names.contains(&locked.name) {
+            project_names.push(locked.name.clone());
+        }
+    }
+    project_names.sort();
+    for name in project_names {
+        themes.push(AvailableTheme {
+            locked: lock.get(&name).cloned(),
+            name,
+            location: ThemeLocation::Project,
+        });
+    }
+
+    if let Some(dir) = user_themes_dir() {
+        for name in theme_dirs(&dir) {
+            themes.push(AvailableTheme {
+                name,
+                location: ThemeLocation::User,
+                locked: None,
+            });
+        }
+    }
+
+    for theme in BUILTIN_THEMES {
+        themes.push(AvailableTheme {
+            name: theme.name.to_string(),
+            location: ThemeLocation::Builtin,
+            locked: None,
+        });
+    }
+
+    Ok(themes)
+}
+
+/// Returns the sorted names of theme directories inside `dir`
+fn theme_dirs(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Installs a theme from a git repository into the project
+///
+/// `source` is a git URL or local repository path (bare repositories work
+/// too). A bare theme name such as `doodle` installs that theme from the
+/// default repository. The resolved commit is recorded in `themes.lock`.
+///
+/// # Arguments
+///
+/// * `project_root` - Directory containing `config.toml`
+/// * `source` - Git URL, repository path, or theme name in the default repository
+/// * `rev` - Branch, tag or commit to install (remote HEAD if `None`)
+/// * `name` - Name to install the theme as (derived from the source if `None`)
+/// * `path` - Theme directory inside the repository (detected if `None`)
+///
+/// # Returns
+///
+/// * `Ok(LockedTheme)` with the recorded lock entry
+/// * `Err(anyhow::Error)` if cloning, checkout or copying failed
+pub fn install_theme(
+    project_root: &Path,
+    source: &str,
+    rev: Option<&str>,
+    name: Option<&str>,
+    path: Option<&str>,
+) -> Result<LockedTheme> {
+    let (source, path) = if is_theme_name(source) {
+        (
+            DEFAULT_THEME_REPO.to_string(),
+            Some(path.map_or_else(|| format!("themes/{}", source), str::to_string)),
+        )
+    } else {
+        (
+            resolve_source(project_root, source),
+            path.map(str::to_string),
+        )
+    };
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => default_theme_name(&source, path.as_deref()),
+    };
+    if !is_valid_theme_name(&name) {
+        anyhow::bail!("Invalid theme name '{}'. Use --name to choose one.", name);
+    }
+
+    let (commit, path) = fetch_theme(project_root, &name, &source, rev, path.as_deref())?;
+    let locked = LockedTheme {
+        name,
+        source,
+        rev: rev.map(str::to_string),
+        commit,
+        path,
+    };
+
+    let mut lock = ThemeLock::load(project_root)?;
+    lock.insert(locked.clone());
+    lock.save(project_root)?;
+
+    Ok(locked)
+}
+
+/// Updates an installed theme to the latest commit of its locked revision
+///
+/// # Arguments
+///
+/// * `project_root` - Directory containing `config.toml`
+/// * `name` - Name of the installed theme
+/// * `rev` - Switch to this branch, tag or commit instead of the locked one
+///
+/// # Returns
+///
+/// * `Ok((previous, updated))` with the lock entries before and after the update
+/// * `Err(anyhow::Error)` if the theme isn't in `themes.lock` or fetching failed
+pub fn update_theme(
+    project_root: &Path,
+    name: &str,
+    rev: Option<&str>,
+) -> Result<(LockedTheme, LockedTheme)> {
+    check_theme_name(name)?;
+    let mut lock = ThemeLock::load(project_root)?;
+    let Some(previous) = lock.get(name).cloned() else {
+        anyhow::bail!(
+            "Theme '{}' is not listed in {}. Install it with `genkan theme install` first.",
+            name,
+            LOCK_FILE
+        );
+    };
+
+    let rev = rev.map(str::to_string).or_else(|| previous.rev.clone());
+    let (commit, path) = fetch_theme(
+        project_root,
+        name,
+        &previous.source,
+        rev.as_deref(),
+        Some(&previous.path),
+    )?;
+    let updated = LockedTheme {
+        rev,
+        commit,
+        path,
+        ..previous.clone()
+    };
+
+    lock.insert(updated.clone());
+    lock.save(project_root)?;
+
+    Ok((previous, updated))
+}
+
+/// Removes a theme from the project and from `themes.lock`
+///
+/// # Arguments
+///
+/// * `project_root` - Directory containing `config.toml`
+/// * `name` - Name of the theme to remove
+///
+/// # Returns
+///
+/// * `Ok(())` if the theme was removed
+/// * `Err(anyhow::Error)` if the theme isn't installed in the project
+pub fn remove_theme(project_root: &Path, name: &str) -> Result<()> {
+    check_theme_name(name)?;
+    let theme_dir = project_themes_dir(project_root).join(name);
+    let mut lock = ThemeLock::load(project_root)?;
+    let locked = lock.remove(name);
+
+    if locked.is_none() && !theme_dir.is_dir() {
+        if builtin_theme(name).is_some() {
+            anyhow::bail!("'{}' is a built-in theme and can't be removed", name);
+        }
+        anyhow::bail!("Theme '{}' is not installed in this project", name);
+    }
+
+    if theme_dir.is_dir() {
+        fs::remove_dir_all(&theme_dir)
+            .with_context(|| format!("Failed to remove {}", theme_dir.display()))?;
+    }
+    lock.save(project_root)
+}
+
+/// Returns true if `name` can only refer to a directory directly inside
+/// `themes/`: not empty, no path separators, and no leading dot (which also
+/// rules out `.` and `..`)
+fn is_valid_theme_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.starts_with('.')
+}
+
+/// Fails for names rejected by [`is_valid_theme_name`], before anything in
+/// `themes/` is written or deleted
+fn check_theme_name(name: &str) -> Result<()> {
+    if !is_valid_theme_name(name) {
+        anyhow::bail!(
+            "Invalid theme name '{}': theme names can't contain path separators or start with a dot",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Returns true if `dir` looks like a theme directory
+fn is_theme_dir(dir: &Path) -> bool {
+    dir.join("template.html").is_file() || dir.join(crate::manifest::MANIFEST_FILE).is_file()
+}
+
+/// Returns true if `source` is a plain theme name rather than a repository
+fn is_theme_name(source: &str) -> bool {
+    !source.is_empty()
+        && source
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Resolves relative repository paths against the project root
+///
+/// URLs are returned unchanged. Relative paths are stored relative to the
+/// project so `themes.lock` stays valid when the project is moved.
+fn resolve_source(project_root: &Path, source: &str) -> String {
+    if is_remote_source(source) {
+        return source.to_string();
+    }
+    let path = Path::new(source);
+    if path.is_relative() && !project_root.join(path).exists() && path.exists() {
+        // Path given relative to the working directory
+        return std::path::absolute(path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| source.to_string());
+    }
+    source.to_string()
+}
+
+/// Returns true for repository URLs, including scp-style SSH sources such
+/// as `git@github.com:user/theme.git`, as opposed to local paths
+fn is_remote_source(source: &str) -> bool {
+    source.contains("://") || source.contains('@')
+}
+
+/// Derives a theme name from the repository path or URL
+fn default_theme_name(source: &str, path: Option<&str>) -> String {
+    let last = path
+        .map(|p| p.trim_end_matches('/'))
+        .filter(|p| !p.is_empty())
+        .unwrap_or(source.trim_end_matches('/'));
+    let last = last.rsplit(['/', '\\', ':']).next().unwrap_or(last);
+    let last = last.strip_suffix(".git").unwrap_or(last);
+    last.strip_prefix("genkan-theme-")
+        .unwrap_or(last)
+        .to_string()
+}
+
+/// Clones `source` at `rev` and copies the theme into the project
+///
+/// The repository is cloned into a temporary directory. The theme replaces
+/// any existing copy in `themes/<name>` only once it was fetched successfully.
+///
+/// # Returns
+///
+/// * `Ok((commit, path))` with the checked out commit and the theme's path in the repository
+/// * `Err(anyhow::Error)` if cloning, checkout or copying failed
+fn fetch_theme(
+    project_root: &Path,
+    name: &str,
+    source: &str,
+    rev: Option<&str>,
+    path: Option<&str>,
+) -> Result<(String, String)> {
+    // Create themes directory if it doesn't exist
+    let themes_dir = project_themes_dir(project_root);
+    fs::create_dir_all(&themes_dir).context("Failed to create themes directory")?;
+
+    // Create a temporary directory for cloning
+    let temp_dir =
+        std::env::temp_dir().join(format!("genkan-theme-{}-{}", name, std::process::id()));
+
+    // Remove temp dir if it exists from a previous run
+    let _ = fs::remove_dir_all(&temp_dir);
+
+    let result = checkout_theme(&temp_dir, project_root, name, source, rev, path).and_then(
+        |(commit, path, theme_source_path)| {
+            // Copy next to the destination first so a failed copy keeps the old theme
+            let theme_dest_path = themes_dir.join(name);
+            let staging_path = themes_dir.join(format!(".{}.tmp", name));
+            let _ = fs::remove_dir_all(&staging_path);
+            copy_dir_recursive(&theme_source_path, &staging_path)
+                .context("Failed to copy theme files")?;
+            if theme_dest_path.exists() {
+                fs::remove_dir_all(&theme_dest_path)
+                    .context("Failed to replace existing theme files")?;
+            }
+            fs::rename(&staging_path, &theme_dest_path).context("Failed to copy theme files")?;
+            Ok((commit, path))
+        },
+    );
+
+    // Clean up temporary directory
+    let _ = fs::remove_dir_all(&temp_dir);
+
+    result
+}
+
+/// Clones the repository into `temp_dir` and checks out the requested revision
+fn checkout_theme(
+    temp_dir: &Path,
+    project_root: &Path,
+    name: &str,
+    source: &str,
+    rev: Option<&str>,
+    path: Option<&str>,
+) -> Result<(String, String, PathBuf)> {
+    // Relative local paths are relative to the project
+    let clone_source = if !is_remote_source(source) && Path::new(source).is_relative() {
+        project_root.join(source).display().to_string()
+    } else {
+        source.to_string()
+    };
+
+    println!("Cloning {}...", source);
+    let repo = git2::Repository::clone(&clone_source, temp_dir)
+        .with_context(|| format!("Failed to clone theme repository '{}'", source))?;
+
+    let commit = match rev {
+        Some(rev) => resolve_revision(&repo, rev)?,
+        None => repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .context("Theme repository has no commits")?,
+    };
+    repo.checkout_tree(
+        commit.as_object(),
+        Some(git2::build::CheckoutBuilder::new().force()),
+    )
+    .context("Failed to check out theme revision")?;
+    repo.set_head_detached(commit.id())
+        .context("Failed to check out theme revision")?;
+
+    // Use the repository root if it is a theme, otherwise themes/<name>
+    let path = match path {
+        Some(path) => path.trim_matches('/').to_string(),
+        None if is_theme_dir(temp_dir) => String::new(),
+        None => format!("themes/{}", name),
+    };
+    let theme_source_path = temp_dir.join(&path);
+    if !is_theme_dir(&theme_source_path) {
+        anyhow::bail!(
+            "No theme found at '{}' in {} (missing template.html or theme.toml). Use --path to select the theme directory.",
+            if path.is_empty() { "/" } else { &path },
+            source
+        );
+    }
+
+    Ok((commit.id().to_string(), path, theme_source_path))
+}
+
+/// Resolves a branch, tag or commit in a freshly cloned repository
+fn resolve_revision<'r>(repo: &'r git2::Repository, rev: &str) -> Result<git2::Commit<'r>> {
+    let candidates = [
+        format!("refs/remotes/origin/{}", rev),
+        format!("refs/tags/{}", rev),
+        rev.to_string(),
+    ];
+    for candidate in &candidates {
+        if let Ok(object) = repo.revparse_single(candidate)
+            && let Ok(commit) = object.peel_to_commit()
+        {
+            return Ok(commit);
+        }
+    }
+    anyhow::bail!("Revision '{}' not found in theme repository", rev)
+}
+
+/// Recursively copies a directory
+fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        let dest_path = dst.join(entry.file_name());
+
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        if path.is_dir() {
+            copy_dir_recursive(&path, &dest_path)?;
+        } else {
+            fs::copy(&path, &dest_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the path to a theme directory
+///
+/// Searches for the theme in this order:
+/// 1. `{project_root}/themes/{theme_name}`, restored from `themes.lock` if missing
+/// 2. `{user_themes_dir}/{theme_name}`
+/// 3. Built-in themes
+///
+/// If the theme is not found in any of these, it is installed from the
+/// default repository and recorded in `themes.lock`.
+///
+/// # Arguments
+///
+/// * `project_root` - Directory containing `config.toml`
+/// * `theme_name` - Name of the theme to find
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` with the path to the theme directory
+/// * `Err(anyhow::Error)` if the theme was not found
+pub fn find_theme_path(project_root: &Path, theme_name: &str) -> Result<PathBuf> {
+    let project_path = project_themes_dir(project_root).join(theme_name);
+    if project_path.is_dir() {
+        return Ok(project_path);
+    }
+
+    // Restore themes that are locked but not checked in
+    if let Some(locked) = ThemeLock::load(project_root)?.get(theme_name) {
+        println!(
+            "Restoring theme '{}' from {} ({})",
+            theme_name,
+            LOCK_FILE,
+            locked.short_commit()
+        );
+        fetch_theme(
+            project_root,
+            theme_name,
+            &locked.source,
+            Some(&locked.commit),
+            Some(&locked.path),
+        )?;
+        return Ok(project_path);
+    }
+
+    if let Some(path) = user_themes_dir().map(|d| d.join(theme_name))
+        && path.is_dir()
+    {
+        return Ok(path);
+    }
+
+    if let Some(theme) = builtin_theme(theme_name) {
+        return theme.materialize();
+    }
+
+    if !is_theme_name(theme_name) {
+        anyhow::bail!("Theme '{}' not found", theme_name);
+    }
+
+    // Theme not found locally, attempt to download it
+    println!(
+        "Theme '{}' not found locally. Downloading from repository...",
+        theme_name
+    );
+    install_theme(project_root, theme_name, None, None, None)
+        .with_context(|| format!("Theme '{}' not found in the default repository", theme_name))?;
+    println!("Theme '{}' downloaded successfully!", theme_name);
+    Ok(project_path)
+}
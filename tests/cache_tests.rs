@@ -0,0 +1,59 @@
+use genkan::cache::AssetCache;
+use genkan::jobs::JobLog;
+use std::sync::{Arc, Mutex};
+use tiny_http::{Header, Response, Server};
+
+#[test]
+fn test_downloads_are_revalidated_and_available_offline() {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/icon.png", server.server_addr());
+    let seen_etags = Arc::new(Mutex::new(Vec::new()));
+
+    let handler = {
+        let seen_etags = Arc::clone(&seen_etags);
+        std::thread::spawn(move || {
+            for _ in 0..2 {
+                let request = server.recv().unwrap();
+                let etag = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("If-None-Match"))
+                    .map(|h| h.value.to_string());
+                seen_etags.lock().unwrap().push(etag.clone());
+                let response = match etag {
+                    Some(_) => Response::from_data(Vec::new()).with_status_code(304),
+                    None => Response::from_data(b"png".to_vec()),
+                };
+                let header = Header::from_bytes("ETag", "\"v1\"").unwrap();
+                request.respond(response.with_header(header)).unwrap();
+            }
+        })
+    };
+
+    let dir = std::env::temp_dir().join(format!("genkan-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = AssetCache::new(dir.clone());
+    let mut log = JobLog::default();
+
+    assert_eq!(cache.fetch(&url, &mut log).unwrap(), b"png");
+    assert_eq!(cache.fetch(&url, &mut log).unwrap(), b"png");
+    handler.join().unwrap();
+    assert_eq!(
+        *seen_etags.lock().unwrap(),
+        vec![None, Some("\"v1\"".to_string())]
+    );
+
+    // Offline builds never touch the network
+    let offline = cache.clone().offline(true);
+    assert_eq!(offline.fetch(&url, &mut log).unwrap(), b"png");
+    assert!(
+        offline
+            .fetch("http://127.0.0.1:9/missing.png", &mut log)
+            .is_err()
+    );
+
+    assert_eq!(cache.downloads().len(), 1);
+    assert!(cache.clean().unwrap() > 0);
+    assert!(cache.downloads().is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -28,3 +28,317 @@ fn test_config_parsing() {
     assert_eq!(config.image.link_icon_size, 128);
     assert_eq!(config.image.favicon_size, 64);
 }
+
+#[test]
+fn test_diagnose_reports_all_problems_with_locations() {
+    use genkan::diagnostics::SourceFile;
+
+    let toml_str = r#"[profile]
+name = ""
+bio = "Test bio"
+
+[theme]
+name = "simple"
+
+[meta]
+title = "Test"
+description = "Test description"
+
+[dark_mode]
+mode = "night"
+
+[[links]]
+title = "Test Link"
+link_type = "buttn"
+
+[[links]]
+link_type = "space"
+"#;
+
+    let source = SourceFile::new("config.toml", toml_str);
+    let (config, _) = Config::from_source(&source).unwrap();
+    let diagnostics = config.diagnose();
+
+    assert_eq!(diagnostics.errors().count(), 3);
+    assert_eq!(diagnostics.warnings().count(), 1);
+    assert!(config.validate().is_err());
+
+    let rendered = diagnostics.render(&source);
+    assert!(rendered.contains("--> config.toml:13:8"));
+    assert!(rendered.contains("--> config.toml:17:13"));
+    assert!(rendered.contains("link_type = \"buttn\""));
+}
+
+#[test]
+fn test_unknown_and_legacy_keys_are_reported() {
+    use genkan::diagnostics::SourceFile;
+
+    let toml_str = r##"[profile]
+name = "Test User"
+bio = "Test bio"
+avatar = "test.png"
+
+[theme]
+name = "simple"
+primary_colour = "#ff0000"
+
+[meta]
+title = "Test"
+description = "Test description"
+
+[[links]]
+title = "Test Link"
+url = "https://example.com"
+icn = "x"
+"##;
+
+    let source = SourceFile::new("config.toml", toml_str);
+    let (_, diagnostics) = Config::from_source(&source).unwrap();
+    let paths: Vec<_> = diagnostics
+        .iter()
+        .filter_map(|d| d.path.as_deref())
+        .collect();
+
+    assert_eq!(
+        paths,
+        vec!["profile.avatar", "theme.primary_colour", "links[0].icn"]
+    );
+    assert!(!diagnostics.has_errors());
+
+    let rendered = diagnostics.render(&source);
+    assert!(rendered.contains("profile.light.avatar"));
+    assert!(rendered.contains("--> config.toml:17:1"));
+}
+
+#[test]
+fn test_unsafe_urls_and_css_values_are_rejected() {
+    let toml_str = r##"
+        [profile]
+        name = "Test User"
+        bio = "Test bio"
+
+        [[profile.social_links]]
+        url = "mailto:me@example.com"
+        icon = "📧"
+
+        [theme]
+        name = "simple"
+        link_spacing = "1rem;}body{display:none"
+
+        [theme.light]
+        primary_color = "red;background:url(x)"
+
+        [meta]
+        title = "Test"
+        description = "Test description"
+
+        [[links]]
+        title = "Script"
+        url = "java\tscript:alert(1)"
+
+        [[links]]
+        title = "Data"
+        url = "data:text/html,<script>alert(1)</script>"
+
+        [[links]]
+        title = "Spacer"
+        link_type = "space"
+        height = "10px\" onmouseover=\"alert(1)"
+    "##;
+
+    let config: Config = toml::from_str(toml_str).unwrap();
+    let diagnostics = config.diagnose();
+    let mut paths: Vec<_> = diagnostics
+        .errors()
+        .filter_map(|d| d.path.as_deref())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            "links[0].url",
+            "links[1].url",
+            "links[2].height",
+            "profile.social_links[0].url",
+            "theme.light.primary_color",
+            "theme.link_spacing",
+        ]
+    );
+
+    // mailto: is opt-in; javascript: can't be allowed
+    let toml_str = toml_str.replace(
+        "[meta]",
+        "[urls]\nallow_schemes = [\"mailto\", \"javascript\"]\n\n[meta]",
+    );
+    let config: Config = toml::from_str(&toml_str).unwrap();
+    let diagnostics = config.diagnose();
+    let paths: Vec<_> = diagnostics
+        .errors()
+        .filter_map(|d| d.path.as_deref())
+        .collect();
+    assert!(!paths.contains(&"profile.social_links[0].url"));
+    assert!(paths.contains(&"links[0].url"));
+    assert!(paths.contains(&"urls.allow_schemes"));
+}
+
+#[test]
+fn test_link_headers_are_validated() {
+    use genkan::diagnostics::Severity;
+
+    let toml_str = r#"
+        [profile]
+        name = "Test User"
+        bio = "Test bio"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test description"
+
+        [[links]]
+        title = "Projects"
+        link_type = "header"
+        collapsed = true
+
+        [[links]]
+        link_type = "header"
+
+        [[links]]
+        title = "Blog"
+        url = "https://example.com"
+        link_type = "header"
+
+        [[links]]
+        title = "GitHub"
+        url = "https://github.com"
+        collapsible = true
+    "#;
+
+    let config: Config = toml::from_str(toml_str).unwrap();
+    let diagnostics = config.diagnose();
+    let paths: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.severity, d.path.as_deref().unwrap_or("")))
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            (Severity::Error, "links[1].title"),
+            (Severity::Warning, "links[2].url"),
+            (Severity::Warning, "links[3].collapsible"),
+        ]
+    );
+}
+
+#[test]
+fn test_link_schedules_are_validated() {
+    let toml_str = r#"
+        [profile]
+        name = "Test User"
+        bio = "Test bio"
+
+        [[profile.social_links]]
+        icon = "🐘"
+        url = "https://example.com"
+        visible_from = "next week"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Launch"
+        url = "https://example.com"
+        visible_from = 2025-12-24T18:00:00+01:00
+        visible_until = "2025-12-24"
+    "#;
+
+    let config: Config = toml::from_str(toml_str).unwrap();
+    // Unquoted TOML date-times are kept as written
+    assert_eq!(
+        config.links[0].visible_from.as_deref(),
+        Some("2025-12-24T18:00:00+01:00")
+    );
+    let diagnostics = config.diagnose();
+    let paths: Vec<_> = diagnostics
+        .iter()
+        .map(|d| d.path.as_deref().unwrap_or(""))
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "links[0].visible_until",
+            "profile.social_links[0].visible_from"
+        ]
+    );
+}
+
+#[test]
+fn test_qr_size_and_quiet_zone_are_bounded() {
+    let toml_str = r#"
+        [profile]
+        name = "Test User"
+        bio = "Test bio"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+
+        [qr]
+        size = 4000000000
+        quiet_zone = 3000000000
+    "#;
+
+    let config: Config = toml::from_str(toml_str).unwrap();
+    let diagnostics = config.diagnose();
+    let paths: Vec<_> = diagnostics
+        .errors()
+        .map(|d| d.path.as_deref().unwrap_or(""))
+        .collect();
+    assert_eq!(paths, ["qr.size", "qr.quiet_zone"]);
+}
+
+#[test]
+fn test_modes_are_matched_case_insensitively() {
+    let toml_str = r#"
+        [profile]
+        name = "Test User"
+        bio = "Test bio"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+
+        [output]
+        mode = "Multi"
+
+        [build]
+        upcoming_links = "REVEAL"
+    "#;
+
+    let config: Config = toml::from_str(toml_str).unwrap();
+    assert_eq!(config.diagnose().errors().count(), 0);
+    assert_eq!(
+        genkan::generator::OutputMode::parse(&config.output.mode),
+        Some(genkan::generator::OutputMode::Multi)
+    );
+}
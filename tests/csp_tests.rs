@@ -0,0 +1,53 @@
+use genkan::csp::{ContentSecurityPolicy, extend_page_policy, hash_source, insert_meta};
+
+#[test]
+fn test_policy_allows_exactly_the_page_content() {
+    let html = concat!(
+        "<!doctypehtml><html lang=en><meta charset=UTF-8><title>Links</title>",
+        "<style>@import url('https://fonts.example.com/css?a=1;b=2');body{color:red}</style>",
+        "<div style=height:30px></div><img src=data:image/png;base64,AAAA alt=\"\">",
+        "<a href=https://example.com onclick=alert(1)>x</a>",
+        "<script>console.log(1)</script>",
+        "<script defer src=https://stats.example.com/js/script.js></script>",
+        "<script type=application/ld+json>{}</script>",
+    );
+    let policy = ContentSecurityPolicy::for_page(html);
+
+    assert_eq!(
+        policy.sources("script-src"),
+        [
+            hash_source("console.log(1)"),
+            "https://stats.example.com".to_string()
+        ]
+    );
+    assert_eq!(
+        policy.sources("style-src"),
+        [
+            hash_source("@import url('https://fonts.example.com/css?a=1;b=2');body{color:red}"),
+            "https://fonts.example.com".to_string(),
+            "'unsafe-hashes'".to_string(),
+            hash_source("height:30px"),
+        ]
+    );
+    assert_eq!(policy.sources("img-src"), ["data:"]);
+    assert_eq!(policy.blocked.len(), 1);
+
+    let page = insert_meta(html, &policy);
+    let meta = page.find("http-equiv=Content-Security-Policy").unwrap();
+    assert!(page.find("<meta charset").unwrap() < meta);
+    assert!(meta < page.find("<style>").unwrap());
+    assert!(page.contains("default-src 'none'"));
+}
+
+#[test]
+fn test_extend_page_policy_for_live_reload() {
+    let html = "<meta charset=UTF-8><style>p{}</style>";
+    let page = insert_meta(html, &ContentSecurityPolicy::for_page(html));
+
+    let extended = extend_page_policy(&page, &[("connect-src", "'self'")]);
+    assert!(extended.contains("; connect-src 'self'; base-uri 'none'"));
+    assert!(extended.ends_with("\"><style>p{}</style>"));
+
+    // Pages without a policy are left alone
+    assert_eq!(extend_page_policy(html, &[("connect-src", "'self'")]), html);
+}
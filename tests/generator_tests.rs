@@ -2,8 +2,58 @@ use genkan::config::{
     Config, DarkMode, ImageSettings, Link, Meta, Profile, ProfileAssets, Theme, ThemeColors,
     Typography,
 };
-use genkan::generator::Generator;
-use std::path::PathBuf;
+use genkan::generator::{Generator, OutputMode};
+use std::path::{Path, PathBuf};
+
+/// Project directory for a test, removed again when dropped
+struct TempProject {
+    root: PathBuf,
+}
+
+impl TempProject {
+    /// Creates an empty project, replacing one left over from an earlier run
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("genkan-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Writes `config.toml` and returns a generator for `output/index.html`
+    fn generator(&self, config: &str) -> Generator {
+        self.generator_for(config, "output/index.html")
+    }
+
+    /// Writes `config.toml` and returns a generator for a page in the project
+    fn generator_for(&self, config: &str, page: &str) -> Generator {
+        let config_path = self.join("config.toml");
+        std::fs::write(&config_path, config).unwrap();
+        let config = Config::from_file(&config_path).unwrap();
+        let theme_path =
+            genkan::generator::find_theme_path(&self.root, &config.theme.name).unwrap();
+        Generator::new(config, theme_path, self.join(page)).with_project_root(self.root.clone())
+    }
+
+    /// Builds the page and returns it
+    fn build(&self, config: &str) -> String {
+        self.generator(config).generate().unwrap();
+        self.html()
+    }
+
+    fn html(&self) -> String {
+        std::fs::read_to_string(self.join("output/index.html")).unwrap()
+    }
+}
+
+impl Drop for TempProject {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
 
 #[test]
 fn test_generator_creation() {
@@ -27,6 +77,7 @@ fn test_generator_creation() {
             typography: Typography::default(),
             light: ThemeColors::default(),
             dark: ThemeColors::default(),
+            options: Default::default(),
         },
         meta: Meta {
             title: "Test".to_string(),
@@ -37,6 +88,8 @@ fn test_generator_creation() {
             analytics: None,
             show_footer: true,
             share_title: None,
+            og_image: None,
+            twitter_handle: None,
         },
         links: vec![Link {
             title: Some("Test".to_string()),
@@ -45,9 +98,20 @@ fn test_generator_creation() {
             description: None,
             link_type: "block".to_string(),
             height: None,
+            collapsible: false,
+            collapsed: false,
+            visible_from: None,
+            visible_until: None,
         }],
         dark_mode: DarkMode::default(),
         image: ImageSettings::default(),
+        urls: Default::default(),
+        csp: Default::default(),
+        build: Default::default(),
+        output: Default::default(),
+        qr: Default::default(),
+        icons: Default::default(),
+        pwa: Default::default(),
     };
 
     let generator = Generator::new(
@@ -58,3 +122,872 @@ fn test_generator_creation() {
 
     assert_eq!(generator.theme_path, PathBuf::from("themes/simple"));
 }
+
+#[test]
+fn test_local_assets_resolve_relative_to_project_root() {
+    let project = TempProject::new("root");
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]))
+        .save(project.join("avatar.png"))
+        .unwrap();
+
+    let html = project.build(
+        r#"
+        [profile]
+        name = "Test"
+        bio = "Bio"
+
+        [profile.light]
+        avatar = "./avatar.png"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "#,
+    );
+
+    let project_root = genkan::config::project_root(project.join("config.toml"));
+    assert_eq!(project_root, project.root);
+    assert!(html.contains("data:image/png;base64,"));
+}
+
+#[test]
+fn test_missing_local_images_are_watched() {
+    let project = TempProject::new("watch");
+    let generator = project.generator(
+        r#"
+        [profile]
+        name = "Test"
+        bio = "Bio"
+
+        [profile.light]
+        avatar = "./avatar.png"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "#,
+    );
+
+    // Adding the avatar later has to trigger a rebuild
+    assert!(
+        generator
+            .watched_paths()
+            .contains(&project.join("avatar.png"))
+    );
+}
+
+#[test]
+fn test_child_theme_overrides_parent_blocks() {
+    let project = TempProject::new("extends");
+    let theme_dir = project.join("themes").join("fancy");
+    std::fs::create_dir_all(&theme_dir).unwrap();
+
+    std::fs::write(
+        theme_dir.join("theme.toml"),
+        "name = \"fancy\"\nextends = \"simple\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        theme_dir.join("template.html"),
+        r#"{% extends "simple/template.html" %}
+{% block footer %}<footer class="fancy-footer">Fancy {{ theme_options.avatar_radius }}</footer>{% endblock footer %}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        theme_dir.join("style.css"),
+        ".fancy-footer { color: #123456; }",
+    )
+    .unwrap();
+
+    let html = project.build(
+        r#"
+        [profile]
+        name = "Test"
+        bio = "Bio"
+
+        [theme]
+        name = "fancy"
+
+        [theme.options]
+        avatar_radius = "13px"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "#,
+    );
+
+    assert!(html.contains("Fancy 13px"));
+    assert!(!html.contains("Made with"));
+    // Parent CSS (with the inherited option) comes before the child's
+    let parent_css = html.find("border-radius:13px").unwrap();
+    assert!(parent_css < html.find("#123456").unwrap());
+    assert!(html.contains("link-button"));
+}
+
+#[test]
+fn test_social_links_left_out_by_theme_are_not_in_json_ld() {
+    let project = TempProject::new("same-as");
+    let theme_dir = project.join("themes").join("plain");
+    std::fs::create_dir_all(&theme_dir).unwrap();
+
+    std::fs::write(
+        theme_dir.join("theme.toml"),
+        r#"
+        name = "plain"
+        extends = "simple"
+
+        [features]
+        qr_share = true
+        dark_mode = true
+        social_links = false
+        link_sections = true
+        "#,
+    )
+    .unwrap();
+    std::fs::write(
+        theme_dir.join("template.html"),
+        r#"{% extends "simple/template.html" %}"#,
+    )
+    .unwrap();
+
+    let html = project.build(
+        r#"
+        [profile]
+        name = "Test"
+        bio = "Bio"
+
+        [[profile.social_links]]
+        url = "https://github.com/alice"
+        icon = "🐙"
+
+        [theme]
+        name = "plain"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "#,
+    );
+
+    assert!(html.contains("application/ld+json"));
+    assert!(!html.contains("sameAs"));
+}
+
+#[test]
+fn test_build_report_lists_asset_warnings_and_hotlinks() {
+    let project = TempProject::new("report");
+    // An empty project cache, so the offline build can't find the icon
+    std::fs::create_dir_all(project.join(genkan::cache::PROJECT_CACHE_DIR)).unwrap();
+
+    let report = project
+        .generator(
+            r#"
+            [profile]
+            name = "Test"
+            bio = "Bio"
+
+            [theme]
+            name = "simple"
+
+            [meta]
+            title = "Test"
+            description = "Test"
+
+            [[links]]
+            title = "Test"
+            url = "https://example.com"
+            icon = "https://example.com/icon.png"
+            "#,
+        )
+        .with_offline(true)
+        .generate()
+        .unwrap();
+
+    let warning = report.warnings.warnings().next().unwrap();
+    assert_eq!(warning.path.as_deref(), Some("links[0].icon"));
+    assert!(warning.message.contains("offline mode"));
+    assert_eq!(report.hotlinked.len(), 1);
+    assert_eq!(report.hotlinked[0].url, "https://example.com/icon.png");
+}
+
+#[test]
+fn test_failed_check_leaves_no_output() {
+    let project = TempProject::new("check");
+    // An empty project cache, so the offline build can't find the icon
+    std::fs::create_dir_all(project.join(genkan::cache::PROJECT_CACHE_DIR)).unwrap();
+    image::RgbImage::from_pixel(64, 64, image::Rgb([0, 128, 255]))
+        .save(project.join("logo.png"))
+        .unwrap();
+
+    let result = project
+        .generator(
+            r#"
+            [profile]
+            name = "Test"
+            bio = "Bio"
+
+            [theme]
+            name = "simple"
+
+            [meta]
+            title = "Test"
+            description = "Test"
+            favicon = "logo.png"
+
+            [[links]]
+            title = "Test"
+            url = "https://example.com"
+            icon = "https://example.com/icon.png"
+            "#,
+        )
+        .with_offline(true)
+        .with_output_mode(Some(OutputMode::Multi))
+        .with_check(Box::new(|report| {
+            anyhow::ensure!(report.hotlinked.is_empty(), "hot-linked images");
+            Ok(())
+        }))
+        .generate();
+
+    assert!(result.is_err());
+    assert!(!project.join("output").exists());
+}
+
+#[test]
+fn test_avatar_densities_are_written_as_hashed_files() {
+    let project = TempProject::new("srcset");
+    std::fs::create_dir_all(project.join("output/assets")).unwrap();
+    // Left over from an earlier build, which listed it in its manifest
+    std::fs::write(project.join("output/assets/0123456789abcdef.png"), b"old").unwrap();
+    std::fs::write(
+        project.join("output/asset-manifest.json"),
+        r#"{"profile.light.avatar": "assets/0123456789abcdef.png"}"#,
+    )
+    .unwrap();
+
+    image::RgbImage::from_pixel(250, 250, image::Rgb([0, 128, 255]))
+        .save(project.join("avatar.png"))
+        .unwrap();
+    let html = project.build(
+        r#"
+        [profile]
+        name = "Test"
+        bio = "Bio"
+
+        [profile.light]
+        avatar = "./avatar.png"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [image]
+        avatar_size = 100
+        densities = [1, 2, 3, 4]
+        embed = false
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "#,
+    );
+
+    // 4x would be no sharper than 3x, which already uses the full 250px source
+    assert!(html.contains(" 3x\""));
+    assert!(!html.contains(" 4x"));
+    assert!(!html.contains("data:image"));
+    let files: Vec<_> = std::fs::read_dir(project.join("output/assets"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(files.len(), 3);
+    for file in &files {
+        assert!(html.contains(&format!("assets/{}", file)));
+    }
+}
+
+#[test]
+fn test_background_image_is_scaled_with_placeholder() {
+    let project = TempProject::new("background");
+    image::RgbImage::from_fn(400, 100, |x, _| image::Rgb([(x % 256) as u8, 64, 128]))
+        .save(project.join("background.png"))
+        .unwrap();
+
+    let html = project.build(
+        r#"
+        [profile]
+        name = "Test"
+        bio = "Bio"
+
+        [profile.light]
+        avatar = ""
+        background_image = "background.png"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [image]
+        embed = false
+        background_max_width = 200
+        background_format = "png"
+        background_placeholder = true
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "#,
+    );
+
+    let entry = std::fs::read_dir(project.join("output/assets"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let scaled = image::open(entry.path()).unwrap();
+    assert_eq!((scaled.width(), scaled.height()), (200, 50));
+
+    // The placeholder is inlined behind the full image
+    let file_name = entry.file_name().into_string().unwrap();
+    assert!(html.contains(&format!(
+        "url(assets/{}),url(data:image/jpeg;base64,",
+        file_name
+    )));
+}
+
+#[test]
+fn test_multi_output_mode_writes_hashed_assets_and_manifest() {
+    let project = TempProject::new("multi");
+    image::RgbImage::from_pixel(100, 100, image::Rgb([0, 128, 255]))
+        .save(project.join("avatar.png"))
+        .unwrap();
+
+    project
+        .generator(
+            r#"
+            [profile]
+            name = "Test"
+            bio = "Bio"
+
+            [profile.light]
+            avatar = "avatar.png"
+
+            [theme]
+            name = "simple"
+
+            [meta]
+            title = "Test"
+            description = "Test"
+
+            [[links]]
+            title = "Test"
+            url = "https://example.com"
+            "#,
+        )
+        .with_output_mode(Some(OutputMode::Multi))
+        .generate()
+        .unwrap();
+
+    let manifest: std::collections::BTreeMap<String, String> = serde_json::from_str(
+        &std::fs::read_to_string(project.join("output/asset-manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let html = project.html();
+    for name in ["style.css", "script.js", "profile.light.avatar"] {
+        let url = &manifest[name];
+        assert!(url.starts_with("assets/"));
+        assert!(project.join("output").join(url).is_file());
+        assert!(html.contains(url.as_str()));
+    }
+    assert!(!html.contains("<style>"));
+    assert!(!html.contains("data:image"));
+}
+
+#[test]
+fn test_building_into_project_keeps_users_assets_dir() {
+    let project = TempProject::new("in-place");
+    std::fs::create_dir_all(project.join("assets")).unwrap();
+    image::RgbImage::from_pixel(100, 100, image::Rgb([0, 128, 255]))
+        .save(project.join("assets/avatar.png"))
+        .unwrap();
+    std::fs::write(project.join("assets/notes.txt"), "mine").unwrap();
+    // Named like a Genkan asset, but written by another tool
+    std::fs::write(project.join("assets/0123456789abcdef.js"), "theirs").unwrap();
+
+    // `genkan build -o .`: the page is written next to the config
+    let build = |mode| {
+        project
+            .generator_for(
+                r#"
+            [profile]
+            name = "Test"
+            bio = "Bio"
+
+            [profile.light]
+            avatar = "assets/avatar.png"
+
+            [theme]
+            name = "simple"
+
+            [meta]
+            title = "Test"
+            description = "Test"
+
+            [[links]]
+            title = "Test"
+            url = "https://example.com"
+            "#,
+                "index.html",
+            )
+            .with_output_mode(Some(mode))
+            .generate()
+            .unwrap();
+    };
+    build(OutputMode::Single);
+    build(OutputMode::Multi);
+    let manifest: std::collections::BTreeMap<String, String> = serde_json::from_str(
+        &std::fs::read_to_string(project.join("asset-manifest.json")).unwrap(),
+    )
+    .unwrap();
+    build(OutputMode::Single);
+
+    // Files from the multi-file build are gone, the user's files are not
+    assert!(!project.join(&manifest["style.css"]).exists());
+    assert!(project.join("assets/avatar.png").is_file());
+    assert_eq!(
+        std::fs::read_to_string(project.join("assets/notes.txt")).unwrap(),
+        "mine"
+    );
+    assert!(project.join("assets/0123456789abcdef.js").is_file());
+}
+
+#[test]
+fn test_link_preview_metadata_points_at_emitted_share_image() {
+    let project = TempProject::new("og");
+    image::RgbImage::from_pixel(1600, 800, image::Rgb([0, 128, 255]))
+        .save(project.join("banner.png"))
+        .unwrap();
+
+    let html = project.build(
+        r#"
+        [profile]
+        name = "Alice"
+        bio = "Bio"
+        social_links = [
+            { icon = "🐙", url = "https://github.com/alice" },
+            { icon = "✉️", url = "mailto:alice@example.com" },
+        ]
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+        page_url = "https://example.com/links/index.html"
+        og_image = "banner.png"
+        twitter_handle = "@alice"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+
+        [urls]
+        allow_schemes = ["mailto"]
+        "#,
+    );
+
+    // The share image is a file even though everything else is embedded
+    let entry = std::fs::read_dir(project.join("output/assets"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let scaled = image::open(entry.path()).unwrap();
+    assert_eq!((scaled.width(), scaled.height()), (1200, 600));
+
+    let image_url = format!(
+        "https://example.com/links/assets/{}",
+        entry.file_name().into_string().unwrap()
+    );
+    assert!(html.contains(&format!("content={} property=og:image>", image_url)));
+    assert!(html.contains("content=summary_large_image"));
+    assert!(html.contains("<link href=https://example.com/links/index.html rel=canonical>"));
+    assert!(html.contains(r#""sameAs":["https://github.com/alice"]"#));
+    assert!(html.contains(&format!(r#""image":"{}""#, image_url)));
+}
+
+#[test]
+fn test_favicon_is_turned_into_icon_set_and_web_manifest() {
+    let project = TempProject::new("icons");
+    // Not square and partly transparent
+    image::RgbaImage::from_fn(300, 200, |x, _| {
+        image::Rgba([255, 0, 0, if x < 150 { 255 } else { 0 }])
+    })
+    .save(project.join("logo.png"))
+    .unwrap();
+    let config = r##"
+        [profile]
+        name = "Alice"
+        bio = "Bio"
+
+        [theme]
+        name = "simple"
+
+        [theme.light]
+        background_color = "#00ff00"
+
+        [meta]
+        title = "Alice's links"
+        description = "Test"
+        favicon = "logo.png"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "##;
+
+    let html = project.build(config);
+
+    let output = project.join("output");
+    for (name, size) in [
+        ("favicon-16x16.png", 16),
+        ("favicon-32x32.png", 32),
+        ("icon-192.png", 192),
+        ("icon-512.png", 512),
+    ] {
+        let icon = image::open(output.join(name)).unwrap().to_rgba8();
+        assert_eq!(icon.dimensions(), (size, size), "{}", name);
+        assert_eq!(
+            icon.get_pixel(0, 0)[3],
+            0,
+            "{} is padded transparently",
+            name
+        );
+    }
+    // iOS shows transparency as black, so the touch icon gets the background
+    let apple = image::open(output.join("apple-touch-icon.png"))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(apple.dimensions(), (180, 180));
+    assert_eq!(apple.get_pixel(0, 0), &image::Rgba([0, 255, 0, 255]));
+    let ico = image::open(output.join("favicon.ico")).unwrap();
+    assert_eq!((ico.width(), ico.height()), (48, 48));
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output.join("site.webmanifest")).unwrap())
+            .unwrap();
+    assert_eq!(manifest["name"], "Alice's links");
+    assert_eq!(manifest["icons"][0]["src"], "icon-192.png");
+    assert_eq!(manifest["icons"][1]["sizes"], "512x512");
+
+    // The icon files replace the embedded favicon
+    assert!(html.contains("href=favicon.ico"));
+    assert!(html.contains("<link href=apple-touch-icon.png rel=apple-touch-icon>"));
+    assert!(html.contains("<link href=site.webmanifest rel=manifest>"));
+    assert!(!html.contains("href=data:"));
+
+    // Turning the icons off removes the files written for them
+    project.build(&format!("{}\n[icons]\nenabled = false\n", config));
+    for name in genkan::icons::file_names() {
+        assert!(!output.join(&name).exists(), "{} is left behind", name);
+    }
+    assert!(!output.join("site.webmanifest").exists());
+}
+
+#[test]
+fn test_pwa_writes_manifest_and_service_worker_until_disabled() {
+    let project = TempProject::new("pwa");
+    image::RgbImage::from_pixel(64, 64, image::Rgb([0, 128, 255]))
+        .save(project.join("avatar.png"))
+        .unwrap();
+    let config_text = r#"
+        [profile]
+        name = "Alice"
+        bio = "Bio"
+
+        [profile.light]
+        avatar = "avatar.png"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Alice's links"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+
+        [pwa]
+        enabled = true
+        short_name = "Alice"
+        "#;
+    // A manifest Genkan didn't write is left alone
+    let output = project.join("output");
+    std::fs::create_dir_all(&output).unwrap();
+    std::fs::write(output.join("site.webmanifest"), r#"{"name": "Other"}"#).unwrap();
+    let html = project.build(config_text);
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(output.join("manifest.webmanifest")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(manifest["display"], "standalone");
+    assert_eq!(manifest["short_name"], "Alice");
+    assert_eq!(manifest["icons"][1]["src"], "icon-512.png");
+    assert!(output.join("site.webmanifest").exists());
+
+    let worker = std::fs::read_to_string(output.join("sw.js")).unwrap();
+    assert!(worker.contains(r#"const PRECACHE = ["./","manifest.webmanifest","favicon.ico","#));
+    assert!(html.contains("<link href=manifest.webmanifest rel=manifest>"));
+    assert!(html.contains("navigator.serviceWorker.register(`sw.js`)"));
+    assert!(html.contains("worker-src 'self'"));
+
+    // Installed workers only go away when replaced
+    let html = project.build(&config_text.replace("enabled = true", "enabled = false"));
+    let worker = std::fs::read_to_string(output.join("sw.js")).unwrap();
+    assert!(worker.contains("self.registration.unregister()"));
+    assert!(!output.join("manifest.webmanifest").exists());
+    assert!(html.contains("<link href=site.webmanifest rel=manifest>"));
+    assert!(!html.contains("serviceWorker"));
+}
+
+#[test]
+fn test_header_links_group_the_links_after_them() {
+    let project = TempProject::new("sections");
+    let html = project.build(
+        r#"
+        [profile]
+        name = "Alice"
+        bio = "Bio"
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Home"
+        url = "https://example.com"
+
+        [[links]]
+        title = "Projects"
+        description = "Things I build"
+        link_type = "header"
+        collapsed = true
+
+        [[links]]
+        title = "Genkan"
+        url = "https://example.com/genkan"
+
+        [[links]]
+        title = "Projects"
+        link_type = "header"
+        "#,
+    );
+
+    // Links before the first header aren't in a section
+    assert!(html.contains("<div class=links><div class=link-section-links><a "));
+    // Collapsed sections start closed and can be remembered by their id
+    assert!(html.contains(
+        r#"<details class="link-section link-section-collapsible"data-section=projects><summary"#
+    ));
+    assert!(html.contains("<section class=link-section><div class=link-section-header>"));
+    let genkan = html.find("example.com/genkan").unwrap();
+    assert!(html.find("Things I build").unwrap() < genkan);
+    assert!(genkan < html.find("</details>").unwrap());
+}
+
+#[test]
+fn test_scheduled_links_are_left_out_or_revealed() {
+    let project = TempProject::new("schedule");
+    let config_toml = r#"
+        [profile]
+        name = "Alice"
+        bio = "Bio"
+
+        [[profile.social_links]]
+        icon = "🐘"
+        url = "https://example.com/old-social"
+        visible_until = 2000-01-01
+
+        [theme]
+        name = "simple"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Ended"
+        url = "https://example.com/ended"
+        visible_until = "2000-01-01T00:00:00Z"
+
+        [[links]]
+        title = "Sale"
+        url = "https://example.com/sale"
+        visible_until = 2999-01-01T00:00:00Z
+
+        [[links]]
+        title = "Launch"
+        url = "https://example.com/launch"
+        visible_from = "2999-01-01T00:00:00Z"
+        "#;
+    let build = |config: &str, at: Option<&str>| {
+        project
+            .generator(config)
+            .with_at(at.map(|at| genkan::schedule::parse_datetime(at).unwrap()))
+            .generate()
+            .unwrap();
+        project.html()
+    };
+
+    // Expired and upcoming links are left out by default
+    let html = build(config_toml, None);
+    assert!(!html.contains("example.com/ended"));
+    assert!(!html.contains("example.com/old-social"));
+    assert!(!html.contains("example.com/launch"));
+    assert!(html.contains("data-visible-until=2999-01-01T00:00:00Z href=https://example.com/sale"));
+
+    // Revealed links are written hidden, for the theme script to show
+    let reveal_toml = format!(
+        "{}
+[build]
+upcoming_links = \"reveal\"\n",
+        config_toml
+    );
+    let html = build(&reveal_toml, None);
+    assert!(!html.contains("example.com/ended"));
+    assert!(
+        html.contains(
+            "data-visible-from=2999-01-01T00:00:00Z hidden href=https://example.com/launch"
+        )
+    );
+
+    // A page built for another moment doesn't change in the browser
+    let html = build(&reveal_toml, Some("2999-06-01"));
+    assert!(!html.contains("example.com/sale"));
+    assert!(html.contains("class=link-button href=https://example.com/launch"));
+    assert!(!html.contains(" data-visible-"));
+}
+
+#[test]
+fn test_builtin_themes_need_no_inline_styles_outside_the_policy() {
+    let project = TempProject::new("theme-csp");
+
+    for theme in genkan::themes::BUILTIN_THEMES {
+        let html = project.build(&format!(
+            r#"
+            [profile]
+            name = "Alice"
+            bio = "Bio"
+
+            [theme]
+            name = "{}"
+
+            [meta]
+            title = "Test"
+            description = "Test"
+
+            [[links]]
+            title = "Test"
+            url = "https://example.com"
+            "#,
+            theme.name
+        ));
+
+        let meta = &html[html.find("http-equiv=Content-Security-Policy").unwrap()..];
+        let policy = meta.split('"').nth(1).unwrap();
+        let style_src = policy
+            .split(';')
+            .find(|directive| directive.trim().starts_with("style-src"))
+            .unwrap();
+        let mut rest = html.as_str();
+        while let Some(start) = rest.find("<style>") {
+            let css = &rest[start + "<style>".len()..];
+            let end = css.find("</style>").unwrap();
+            let hash = genkan::csp::hash_source(&css[..end]);
+            assert!(
+                style_src.contains(&hash),
+                "{}: unhashed <style>",
+                theme.name
+            );
+            rest = &css[end..];
+        }
+
+        // Styles added by scripts can't be hashed, so themes must not add any
+        let theme_path = genkan::generator::find_theme_path(&project.root, theme.name).unwrap();
+        let script: String = std::fs::read_to_string(theme_path.join("script.js"))
+            .unwrap()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        for quote in ['\'', '"', '`'] {
+            assert!(
+                !script.contains(&format!("createElement({0}style{0})", quote)),
+                "{}: script.js creates <style> elements",
+                theme.name
+            );
+        }
+    }
+}
+
+#[test]
+fn test_theme_option_csp_sources_follow_the_option() {
+    let project = TempProject::new("option-csp");
+    let config = r#"
+        [profile]
+        name = "Alice"
+        bio = "Bio"
+
+        [theme]
+        name = "doodle"
+
+        [meta]
+        title = "Test"
+        description = "Test"
+
+        [[links]]
+        title = "Test"
+        url = "https://example.com"
+        "#;
+
+    let html = project.build(config);
+    assert!(html.contains("https://fonts.gstatic.com"));
+
+    let html = project.build(&format!("{}\n[theme.options]\nweb_fonts = false\n", config));
+    assert!(!html.contains("fonts.googleapis.com"));
+    assert!(!html.contains("fonts.gstatic.com"));
+}
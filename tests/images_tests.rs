@@ -0,0 +1,46 @@
+use genkan::images::{OutputFormat, mime_type, optimize};
+use image::{ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+use std::io::Cursor;
+
+/// A noisy opaque image, which compresses like a photo
+fn photo(size: u32) -> Vec<u8> {
+    let mut seed = 12345u32;
+    let img = RgbImage::from_fn(size, size, |_, _| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let [r, g, b, _] = seed.to_le_bytes();
+        Rgb([r, g, b])
+    });
+    let mut data = Vec::new();
+    img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .unwrap();
+    data
+}
+
+#[test]
+fn test_auto_format_picks_smallest_encoding() {
+    let original = photo(256);
+    let compressed = optimize(&original, Some(128), OutputFormat::Auto, 80).unwrap();
+    assert_eq!(mime_type(&compressed), Some("image/jpeg"));
+    assert!(compressed.len() < original.len());
+    let img = image::load_from_memory(&compressed).unwrap();
+    assert_eq!((img.width(), img.height()), (128, 128));
+
+    // JPEG can't store transparency
+    let mut data = Vec::new();
+    RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 128]))
+        .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .unwrap();
+    let compressed = optimize(&data, None, OutputFormat::Jpeg, 80).unwrap();
+    assert_ne!(mime_type(&compressed), Some("image/jpeg"));
+}
+
+#[test]
+fn test_original_is_kept_when_reencoding_does_not_help() {
+    // A tiny JPEG that PNG can only make bigger
+    let mut original = Vec::new();
+    image::DynamicImage::ImageRgb8(image::load_from_memory(&photo(32)).unwrap().to_rgb8())
+        .write_to(&mut Cursor::new(&mut original), ImageFormat::Jpeg)
+        .unwrap();
+    let compressed = optimize(&original, Some(64), OutputFormat::Png, 80).unwrap();
+    assert_eq!(compressed, original);
+}
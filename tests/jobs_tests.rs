@@ -0,0 +1,52 @@
+use genkan::jobs::{JobLog, run_parallel};
+use std::time::Duration;
+
+#[test]
+fn test_run_parallel_keeps_input_order() {
+    let items: Vec<u64> = (0..16).collect();
+    let results = run_parallel(&items, 4, |&n| {
+        // Later items finish first
+        std::thread::sleep(Duration::from_millis(16 - n));
+        let mut log = JobLog::default();
+        log.info(format!("done {}", n));
+        if n % 5 == 0 {
+            log.warn(format!("{} is a multiple of 5", n));
+        }
+        (n * 2, log)
+    });
+
+    let doubled: Vec<u64> = results.iter().map(|(n, _)| *n).collect();
+    assert_eq!(doubled, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    let warnings: Vec<&str> = results.iter().flat_map(|(_, log)| log.warnings()).collect();
+    assert_eq!(
+        warnings,
+        [
+            "0 is a multiple of 5",
+            "5 is a multiple of 5",
+            "10 is a multiple of 5",
+            "15 is a multiple of 5"
+        ]
+    );
+}
+
+#[test]
+fn test_run_parallel_handles_worker_counts_outside_the_items() {
+    let items = [1, 2, 3];
+    // No workers still runs every job, and extra workers aren't needed
+    assert_eq!(run_parallel(&items, 0, |n| n * 10), [10, 20, 30]);
+    assert_eq!(run_parallel(&items, 64, |n| n * 10), [10, 20, 30]);
+
+    let empty: [u32; 0] = [];
+    assert!(run_parallel(&empty, 8, |n| *n).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "job failed")]
+fn test_run_parallel_passes_on_panics() {
+    run_parallel(&[1, 2, 3, 4], 2, |&n| {
+        if n == 3 {
+            panic!("job failed");
+        }
+        n
+    });
+}
@@ -0,0 +1,129 @@
+use genkan::config::Config;
+use genkan::manifest::ThemeManifest;
+use std::fs;
+
+#[test]
+fn test_theme_options_are_merged_and_checked() {
+    let theme_dir = std::env::temp_dir().join(format!("genkan-manifest-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&theme_dir);
+    fs::create_dir_all(&theme_dir).unwrap();
+    fs::write(
+        theme_dir.join("theme.toml"),
+        r#"
+        name = "neon"
+        version = "2.1.0"
+        min_genkan_version = "0.1.0"
+
+        [features]
+        dark_mode = true
+
+        [options.glow]
+        default = true
+
+        [options.accent]
+        default = "magenta"
+        "#,
+    )
+    .unwrap();
+
+    let config: Config = toml::from_str(
+        r#"
+        [profile]
+        name = "Test User"
+        bio = "Test bio"
+
+        [theme]
+        name = "neon"
+
+        [theme.options]
+        accent = "cyan"
+        glow = "yes"
+        sparkle = true
+
+        [meta]
+        title = "Test"
+        description = "Test description"
+        page_url = "https://example.com"
+
+        [[links]]
+        title = "Test Link"
+        url = "https://example.com"
+        "#,
+    )
+    .unwrap();
+
+    let manifest = ThemeManifest::load(&theme_dir).unwrap();
+    assert_eq!(manifest.version.as_deref(), Some("2.1.0"));
+
+    let options = manifest.resolve_options(&config);
+    assert_eq!(options["accent"].as_str(), Some("cyan"));
+
+    let diagnostics = manifest.diagnose(&config);
+    let errors: Vec<_> = diagnostics
+        .errors()
+        .filter_map(|d| d.path.as_deref())
+        .collect();
+    assert_eq!(errors, ["theme.options.glow", "theme.options.sparkle"]);
+    // The theme doesn't declare QR sharing, so page_url only warns
+    let warnings: Vec<_> = diagnostics
+        .warnings()
+        .filter_map(|d| d.path.as_deref())
+        .collect();
+    assert_eq!(warnings, ["meta.page_url"]);
+    assert!(manifest.validate(&config).is_err());
+
+    // Themes without a manifest accept no options
+    fs::remove_file(theme_dir.join("theme.toml")).unwrap();
+    let manifest = ThemeManifest::load(&theme_dir).unwrap();
+    assert!(manifest.features().qr_share);
+    assert_eq!(manifest.diagnose(&config).errors().count(), 3);
+
+    let _ = fs::remove_dir_all(&theme_dir);
+}
+
+#[test]
+fn test_disabled_dark_mode_is_matched_case_insensitively() {
+    let theme_dir =
+        std::env::temp_dir().join(format!("genkan-manifest-dark-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&theme_dir);
+    fs::create_dir_all(&theme_dir).unwrap();
+    fs::write(
+        theme_dir.join("theme.toml"),
+        r#"
+        name = "plain"
+
+        [features]
+        dark_mode = false
+        "#,
+    )
+    .unwrap();
+
+    let config: Config = toml::from_str(
+        r#"
+        [profile]
+        name = "Test User"
+        bio = "Test bio"
+
+        [theme]
+        name = "plain"
+
+        [dark_mode]
+        mode = "Disable"
+
+        [meta]
+        title = "Test"
+        description = "Test description"
+
+        [[links]]
+        title = "Test Link"
+        url = "https://example.com"
+        "#,
+    )
+    .unwrap();
+
+    let manifest = ThemeManifest::load(&theme_dir).unwrap();
+    let diagnostics = manifest.diagnose(&config);
+    assert_eq!(diagnostics.warnings().count(), 0);
+
+    let _ = fs::remove_dir_all(&theme_dir);
+}
@@ -0,0 +1,99 @@
+use genkan::config::Config;
+use genkan::diagnostics::SourceFile;
+use genkan::migrate::migrate_config;
+
+#[test]
+fn test_migrate_moves_legacy_keys_and_keeps_comments() {
+    let legacy = r##"[profile]
+name = "Test User"
+bio = "Test bio"
+# Avatar shown above the name
+avatar = "avatar.png"
+
+[theme]
+name = "simple"
+primary_color = "#667eea" # brand color
+
+[theme.light]
+background_color = "#fafafa"
+primary_color = "#000000"
+
+[meta]
+title = "Test"
+description = "Test description"
+
+[[links]]
+title = "Test Link"
+url = "https://example.com"
+"##;
+
+    let migration = migrate_config(legacy).unwrap();
+    assert_eq!(migration.changes.len(), 2);
+    assert!(
+        migration
+            .content
+            .contains("# Avatar shown above the name\navatar")
+    );
+
+    let source = SourceFile::new("config.toml", migration.content.as_str());
+    let (config, diagnostics) = Config::from_source(&source).unwrap();
+    assert!(diagnostics.is_empty());
+    assert_eq!(config.profile.light.avatar, "avatar.png");
+    // An existing new-style value wins over the legacy one
+    assert_eq!(config.theme.light.primary_color, "#000000");
+    assert_eq!(config.theme.light.background_color, "#fafafa");
+
+    assert!(migrate_config(&migration.content).unwrap().is_noop());
+}
+
+#[test]
+fn test_migrate_allows_schemes_used_by_links() {
+    let config = r#"[profile]
+name = "Test User"
+bio = "Test bio"
+social_links = [{ icon = "📞", url = "tel:+123456" }]
+
+[theme]
+name = "simple"
+
+[meta]
+title = "Test"
+description = "Test description"
+
+[[links]]
+title = "Email"
+url = "mailto:hello@example.com"
+
+[[links]]
+title = "Site"
+url = "https://example.com"
+"#;
+
+    let migration = migrate_config(config).unwrap();
+    assert_eq!(migration.changes.len(), 2);
+    assert!(
+        migration
+            .content
+            .ends_with("[urls]\nallow_schemes = [\"mailto\", \"tel\"]\n")
+    );
+
+    let source = SourceFile::new("config.toml", migration.content.as_str());
+    let (config, diagnostics) = Config::from_source(&source).unwrap();
+    assert!(diagnostics.is_empty());
+    assert!(config.diagnose().is_empty());
+    assert!(migrate_config(&migration.content).unwrap().is_noop());
+}
+
+#[test]
+fn test_migrate_with_only_skipped_keys_is_noop() {
+    let config = r#"[profile]
+name = "Test User"
+avatar = "avatar.png"
+light = "not a table"
+"#;
+
+    let migration = migrate_config(config).unwrap();
+    assert!(migration.is_noop());
+    assert_eq!(migration.skipped.len(), 1);
+    assert_eq!(migration.content, config);
+}
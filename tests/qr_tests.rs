@@ -0,0 +1,69 @@
+use genkan::qr::{EcLevel, QrFormat, QrStyle, render};
+use image::{Rgb, RgbImage};
+use std::io::Cursor;
+
+const URL: &str = "https://links.example.com/alice/";
+
+/// Returns the width in modules from the `viewBox` of an SVG code
+fn svg_width(svg: &str) -> u32 {
+    let view_box = svg.split("viewBox=\"0 0 ").nth(1).unwrap();
+    view_box.split(' ').next().unwrap().parse().unwrap()
+}
+
+#[test]
+fn test_png_uses_colours_and_quiet_zone() {
+    let style = QrStyle {
+        foreground: [0x1a, 0x1a, 0x2e],
+        background: [0xee, 0xee, 0xee],
+        quiet_zone: 2,
+        error_correction: EcLevel::M,
+    };
+    let png = render(URL, &style, QrFormat::Png, 100, None).unwrap();
+    let img = image::load_from_memory(&png).unwrap().to_rgb8();
+
+    // Modules are whole pixels, so the image is at least the requested size
+    let modules = svg_width(
+        &String::from_utf8(render(URL, &style, QrFormat::Svg, 100, None).unwrap()).unwrap(),
+    );
+    let scale = img.width() / modules;
+    assert!(img.width() >= 100);
+    assert_eq!(img.width(), modules * scale);
+
+    // The quiet zone is background, then the top-left finder pattern starts
+    assert_eq!(*img.get_pixel(0, 0), Rgb([0xee, 0xee, 0xee]));
+    assert_eq!(
+        *img.get_pixel(2 * scale, 2 * scale),
+        Rgb([0x1a, 0x1a, 0x2e])
+    );
+}
+
+#[test]
+fn test_logo_raises_error_correction() {
+    let style = QrStyle {
+        error_correction: EcLevel::L,
+        ..QrStyle::default()
+    };
+    let mut logo = Vec::new();
+    RgbImage::from_pixel(64, 64, Rgb([255, 0, 0]))
+        .write_to(&mut Cursor::new(&mut logo), image::ImageFormat::Png)
+        .unwrap();
+
+    let plain = String::from_utf8(render(URL, &style, QrFormat::Svg, 200, None).unwrap()).unwrap();
+    let with_logo =
+        String::from_utf8(render(URL, &style, QrFormat::Svg, 200, Some(&logo)).unwrap()).unwrap();
+
+    // The `high` level needs more modules for the same URL
+    assert!(!plain.contains("<image"));
+    assert!(with_logo.contains("<image href=\"data:image/"));
+    assert!(svg_width(&with_logo) > svg_width(&plain));
+}
+
+#[test]
+fn test_oversized_quiet_zone_is_an_error() {
+    let style = QrStyle {
+        quiet_zone: u32::MAX / 2,
+        ..QrStyle::default()
+    };
+    assert!(render(URL, &style, QrFormat::Svg, 200, None).is_err());
+    assert!(render(URL, &style, QrFormat::Png, 200, None).is_err());
+}
@@ -0,0 +1,54 @@
+use genkan::csp::{self, ContentSecurityPolicy};
+use genkan::server::{WatchSnapshot, inject_reload_script};
+use std::fs;
+
+#[test]
+fn test_inject_reload_script() {
+    let html = "<html><body><p>Hi</p></body></html>";
+    let injected = inject_reload_script(html);
+    assert!(injected.contains("/__genkan/reload"));
+    assert!(injected.find("/__genkan/reload") < injected.find("</body>"));
+
+    // Minified output omits the closing body tag
+    let minified = inject_reload_script("<p>Hi");
+    assert!(minified.starts_with("<p>Hi<script>"));
+}
+
+#[test]
+fn test_reload_script_is_allowed_by_the_page_policy() {
+    let page = "<html><head><title>Hi</title></head><body><p>Hi</p></body></html>";
+    let page = csp::insert_meta(page, &ContentSecurityPolicy::for_page(page));
+    let injected = inject_reload_script(&page);
+
+    let start = injected.find("<script>").unwrap() + "<script>".len();
+    let end = injected[start..].find("</script>").unwrap() + start;
+    let meta = &injected[injected.find("Content-Security-Policy").unwrap()..];
+    let policy = meta.split('"').nth(1).unwrap();
+    assert!(policy.contains(&csp::hash_source(&injected[start..end])));
+    assert!(policy.contains("connect-src 'self'"));
+}
+
+#[test]
+fn test_watch_snapshot_detects_changes() {
+    let dir = std::env::temp_dir().join(format!("genkan-watch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let missing = dir.join("avatar.png");
+
+    let watched = vec![dir.clone(), missing.clone()];
+    let before = WatchSnapshot::capture(&watched);
+    assert_eq!(before, WatchSnapshot::capture(&watched));
+
+    fs::write(&missing, b"png").unwrap();
+    assert_ne!(before, WatchSnapshot::capture(&watched));
+
+    // Files added to or removed from a watched directory count too
+    let created = WatchSnapshot::capture(&watched);
+    fs::write(dir.join("style.css"), b"body {}").unwrap();
+    let added = WatchSnapshot::capture(&watched);
+    assert_ne!(created, added);
+    fs::remove_file(dir.join("style.css")).unwrap();
+    assert_ne!(added, WatchSnapshot::capture(&watched));
+
+    let _ = fs::remove_dir_all(&dir);
+}
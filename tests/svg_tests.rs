@@ -0,0 +1,84 @@
+use genkan::svg::sanitize_svg;
+
+#[test]
+fn test_sanitize_svg_removes_active_content() {
+    let svg = br##"<?xml version="1.0"?>
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="24" height="24" onload="alert(1)">
+  <script>alert(1)</script>
+  <foreignObject><div xmlns="http://www.w3.org/1999/xhtml">hi</div></foreignObject>
+  <style>body { display: none }</style>
+  <a href="javascript:alert(1)"><path d="M0 0h24v24H0z" fill="#ff0000" onclick="alert(1)"/></a>
+  <use xlink:href="https://evil.example/sprite.svg#icon"/>
+  <rect width="10" height="10" style="fill: red; background: url(https://evil.example/x.png)"/>
+</svg>"##;
+
+    let clean = sanitize_svg(svg).unwrap();
+    for forbidden in [
+        "script",
+        "alert",
+        "foreignObject",
+        "style>",
+        "evil.example",
+        "javascript",
+        "#ff0000",
+    ] {
+        assert!(
+            !clean.contains(forbidden),
+            "{} left in {}",
+            forbidden,
+            clean
+        );
+    }
+    // Only the root loses its fixed size, and keeps its aspect ratio
+    assert!(clean.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24">"#));
+    assert!(clean.contains(r#"<rect width="10" height="10" style="fill:currentColor">"#));
+    assert!(clean.contains(r#"fill="currentColor""#));
+}
+
+#[test]
+fn test_sanitize_svg_namespaces_ids() {
+    let icon = |color: &str| {
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24">
+  <defs><linearGradient id="g"><stop offset="0" stop-color="{}"/></linearGradient><clipPath id="c"><rect width="24" height="24"/></clipPath></defs>
+  <path id="p" d="M0 0h24v24H0z" fill="url(#g)" clip-path="url(#c)" stroke="none"/>
+  <use href="#p"/>
+</svg>"##,
+            color
+        )
+    };
+
+    let first = sanitize_svg(icon("red").as_bytes()).unwrap();
+    let second = sanitize_svg(icon("blue").as_bytes()).unwrap();
+    assert_eq!(first, sanitize_svg(icon("red").as_bytes()).unwrap());
+
+    let prefix = &first[first.find(r#"id=""#).unwrap() + 4..first.find("g\">").unwrap()];
+    assert!(first.contains(&format!(r#"fill="url(#{}g)""#, prefix)));
+    assert!(first.contains(&format!(r#"clip-path="url(#{}c)""#, prefix)));
+    assert!(first.contains(&format!(r##"href="#{}p""##, prefix)));
+    assert!(first.contains(r#"stroke="none""#));
+    assert!(!second.contains(prefix));
+}
+
+#[test]
+fn test_sanitize_svg_matches_css_functions_case_insensitively() {
+    let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24">
+  <defs><filter id="f"><feGaussianBlur stdDeviation="1"/></filter></defs>
+  <rect width="10" height="10" style="background:URL(https://evil.example/x.png)"/>
+  <rect width="10" height="10" filter="URL(https://evil.example/f.svg#a)"/>
+  <rect width="10" height="10" fill="Url(https://evil.example/p.svg#a)"/>
+  <rect width="10" height="10" style="mask:image-set(&quot;https://evil.example/m.png&quot; 1x)"/>
+  <rect width="10" height="10" style="background:u\72l(https://evil.example/e.png)"/>
+  <rect width="10" height="10" filter="URL(#f)" transform="Rotate(45) translate(1 2)"/>
+</svg>"##;
+
+    let clean = sanitize_svg(svg).unwrap();
+    assert!(!clean.contains("evil.example"), "{}", clean);
+    assert!(clean.contains(r#"fill="currentColor""#));
+    let prefix = &clean[clean.find(r#"id=""#).unwrap() + 4..clean.find("f\">").unwrap()];
+    assert!(clean.contains(&format!(
+        r#"filter="url(#{}f)" transform="Rotate(45) translate(1 2)""#,
+        prefix
+    )));
+}
@@ -0,0 +1,180 @@
+use genkan::themes::{
+    BUILTIN_THEMES, ResolvedTheme, ThemeLock, eject_theme, find_theme_path, install_theme,
+    remove_theme, update_theme,
+};
+use std::collections::HashMap;
+use std::fs;
+
+#[test]
+fn test_builtin_themes_are_found_without_network() {
+    let project = std::env::temp_dir().join(format!("genkan-builtin-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&project);
+    fs::create_dir_all(&project).unwrap();
+
+    for theme in BUILTIN_THEMES {
+        let path = find_theme_path(&project, theme.name).unwrap();
+        assert!(path.join("theme.toml").is_file());
+        assert!(path.join("style.css").is_file());
+    }
+
+    let _ = fs::remove_dir_all(&project);
+}
+
+#[test]
+fn test_eject_theme_takes_precedence() {
+    let project = std::env::temp_dir().join(format!("genkan-eject-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&project);
+    fs::create_dir_all(&project).unwrap();
+
+    let ejected = eject_theme(&project, "simple", false).unwrap();
+    assert_eq!(ejected, project.join("themes").join("simple"));
+    assert!(ejected.join("script.js").is_file());
+    assert_eq!(find_theme_path(&project, "simple").unwrap(), ejected);
+
+    // Refuses to overwrite local changes unless forced
+    assert!(eject_theme(&project, "simple", false).is_err());
+    let stale = ejected.join("partials").join("stale.html");
+    fs::write(&stale, "").unwrap();
+    assert!(eject_theme(&project, "simple", true).is_ok());
+    assert!(!stale.exists());
+    assert!(ejected.join("script.js").is_file());
+    assert!(eject_theme(&project, "nonexistent", false).is_err());
+
+    let _ = fs::remove_dir_all(&project);
+}
+
+#[test]
+fn test_theme_commands_reject_names_outside_themes_dir() {
+    let root = std::env::temp_dir().join(format!("genkan-theme-names-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let project = root.join("site");
+    fs::create_dir_all(project.join("themes")).unwrap();
+    fs::write(project.join("config.toml"), "").unwrap();
+    fs::write(root.join("keep.txt"), "").unwrap();
+
+    for name in ["..", ".", "../site", "a/b", "a\\b", ".hidden", ""] {
+        assert!(remove_theme(&project, name).is_err(), "{}", name);
+        assert!(update_theme(&project, name, None).is_err(), "{}", name);
+        assert!(eject_theme(&project, name, true).is_err(), "{}", name);
+    }
+    assert!(project.join("config.toml").is_file());
+    assert!(project.join("themes").is_dir());
+    assert!(root.join("keep.txt").is_file());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Commits `files` to the repository and returns the new commit id
+fn commit_files(repo: &git2::Repository, files: &[(&str, &str)], message: &str) -> git2::Oid {
+    let workdir = repo.workdir().unwrap();
+    let mut index = repo.index().unwrap();
+    for (path, contents) in files {
+        fs::write(workdir.join(path), contents).unwrap();
+        index.add_path(std::path::Path::new(path)).unwrap();
+    }
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    let parents: Vec<git2::Commit> = repo
+        .head()
+        .ok()
+        .and_then(|h| h.peel_to_commit().ok())
+        .into_iter()
+        .collect();
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_install_update_remove_from_local_bare_repo() {
+    let root = std::env::temp_dir().join(format!("genkan-lock-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let work = root.join("work");
+    let bare = root.join("my-theme.git");
+    let project = root.join("project");
+    fs::create_dir_all(&project).unwrap();
+
+    let repo = git2::Repository::init(&work).unwrap();
+    let first = commit_files(&repo, &[("template.html", "v1"), ("style.css", "")], "v1");
+    repo.tag_lightweight("v1", &repo.find_object(first, None).unwrap(), false)
+        .unwrap();
+    let second = commit_files(&repo, &[("template.html", "v2")], "v2");
+    git2::build::RepoBuilder::new()
+        .bare(true)
+        .clone(work.to_str().unwrap(), &bare)
+        .unwrap();
+
+    // Pinned to a tag
+    let source = bare.to_str().unwrap();
+    let locked = install_theme(&project, source, Some("v1"), None, None).unwrap();
+    assert_eq!(locked.name, "my-theme");
+    assert_eq!(locked.commit, first.to_string());
+    let theme_dir = project.join("themes").join("my-theme");
+    assert_eq!(
+        fs::read_to_string(theme_dir.join("template.html")).unwrap(),
+        "v1"
+    );
+    assert!(!theme_dir.join(".git").exists());
+
+    // A missing theme is restored at the locked commit
+    fs::remove_dir_all(&theme_dir).unwrap();
+    assert_eq!(find_theme_path(&project, "my-theme").unwrap(), theme_dir);
+    assert_eq!(
+        fs::read_to_string(theme_dir.join("template.html")).unwrap(),
+        "v1"
+    );
+
+    // Updating to a branch records the new commit
+    let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+    let (previous, updated) = update_theme(&project, "my-theme", Some(&branch)).unwrap();
+    assert_eq!(previous.commit, first.to_string());
+    assert_eq!(updated.commit, second.to_string());
+    assert_eq!(
+        ThemeLock::load(&project).unwrap().get("my-theme"),
+        Some(&updated)
+    );
+    assert_eq!(
+        fs::read_to_string(theme_dir.join("template.html")).unwrap(),
+        "v2"
+    );
+
+    remove_theme(&project, "my-theme").unwrap();
+    assert!(!theme_dir.exists());
+    assert!(!project.join("themes.lock").exists());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_project_templates_override_theme_partials() {
+    let project = std::env::temp_dir().join(format!("genkan-overrides-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&project);
+    fs::create_dir_all(project.join("templates").join("partials")).unwrap();
+    fs::write(
+        project.join("templates/partials/footer.html"),
+        "<footer>Custom</footer>",
+    )
+    .unwrap();
+
+    let theme_path = find_theme_path(&project, "doodle").unwrap();
+    let theme = ResolvedTheme::resolve(&project, "doodle", &theme_path).unwrap();
+    let templates: HashMap<_, _> = theme.templates(&project).unwrap().into_iter().collect();
+
+    assert_eq!(templates["partials/footer.html"], "<footer>Custom</footer>");
+    assert!(templates["simple/partials/footer.html"].contains("Made with"));
+    // doodle has no template.html of its own and uses simple's
+    assert_eq!(
+        templates["template.html"],
+        templates["simple/template.html"]
+    );
+
+    let _ = fs::remove_dir_all(&project);
+}
@@ -9,6 +9,9 @@
         // Initialize dark mode first (before fade in)
         initializeDarkMode();
 
+        // Show and hide scheduled links before they are animated in
+        initializeScheduledLinks();
+
         // Fade in effect (no transform on body to preserve fixed positioning)
         document.body.style.opacity = '0';
         setTimeout(function() {
@@ -59,6 +62,9 @@
         // Initialize share functionality
         initializeShare();
 
+        // Restore collapsed link sections
+        initializeLinkSections();
+
         // Add doodle cursor trail (optional fun effect)
         addDoodleTrail();
 
@@ -129,51 +135,6 @@
         });
     });
 
-    // Add CSS for squiggle effect
-    const style = document.createElement('style');
-    style.textContent = `
-        @keyframes squiggle-expand {
-            0% {
-                width: 0;
-                height: 0;
-                margin-left: 0;
-                margin-top: 0;
-                opacity: 0.6;
-            }
-            100% {
-                width: 200px;
-                height: 200px;
-                margin-left: -100px;
-                margin-top: -100px;
-                opacity: 0;
-                transform: rotate(360deg);
-            }
-        }
-
-        @keyframes celebrate-pop {
-            0% {
-                transform: translate(-50%, -50%) scale(0) rotate(0deg);
-                opacity: 1;
-            }
-            50% {
-                opacity: 1;
-            }
-            100% {
-                transform: translate(-50%, calc(-50% - 100px)) scale(1.5) rotate(720deg);
-                opacity: 0;
-            }
-        }
-
-        .celebrate-star {
-            position: fixed;
-            pointer-events: none;
-            z-index: 9999;
-            font-size: 24px;
-            animation: celebrate-pop 1s ease-out forwards;
-        }
-    `;
-    document.head.appendChild(style);
-
     // Create celebration effect
     function createCelebration(x, y) {
         const emojis = ['⭐', '✨', '🌟', '💫', '🎨', '🎪', '🎭'];
@@ -232,18 +193,57 @@
                 dot.remove();
             }, 1000);
         });
+    }
 
-        // Add fade-out animation
-        const trailStyle = document.createElement('style');
-        trailStyle.textContent = `
-            @keyframes fade-out {
-                to {
-                    opacity: 0;
-                    transform: scale(2);
+    // Scheduled links are shown between their data-visible-from and
+    // data-visible-until times, without rebuilding the page
+    function initializeScheduledLinks() {
+        const scheduled = document.querySelectorAll('[data-visible-from], [data-visible-until]');
+        if (scheduled.length === 0) {
+            return;
+        }
+        function update() {
+            const now = Date.now();
+            let next = Infinity;
+            scheduled.forEach(function(element) {
+                // Missing times parse as NaN, which never compares true
+                const from = Date.parse(element.dataset.visibleFrom);
+                const until = Date.parse(element.dataset.visibleUntil);
+                element.hidden = from > now || until <= now;
+                [from, until].forEach(function(time) {
+                    if (time > now && time < next) {
+                        next = time;
+                    }
+                });
+            });
+            if (next !== Infinity) {
+                // Timers longer than about 24 days fire at once, so wait in steps
+                setTimeout(update, Math.min(next - now, 0x7fffffff));
+            }
+        }
+        update();
+    }
+
+    // Collapsible link sections remember whether they were open
+    function initializeLinkSections() {
+        document.querySelectorAll('details.link-section[data-section]').forEach(function(section) {
+            const key = 'genkan-section:' + section.dataset.section;
+            try {
+                const saved = localStorage.getItem(key);
+                if (saved !== null) {
+                    section.open = saved === 'open';
                 }
+            } catch (e) {
+                // Storage may be unavailable, e.g. in private browsing
             }
-        `;
-        document.head.appendChild(trailStyle);
+            section.addEventListener('toggle', function() {
+                try {
+                    localStorage.setItem(key, section.open ? 'open' : 'closed');
+                } catch (e) {
+                    // Ignore; the section just won't be remembered
+                }
+            });
+        });
     }
 
     // Dark mode functionality
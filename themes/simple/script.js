@@ -9,6 +9,9 @@
         // Initialize dark mode first (before fade in)
         initializeDarkMode();
 
+        // Show and hide scheduled links before they are animated in
+        initializeScheduledLinks();
+
         // Fade in effect
         document.body.style.opacity = '0';
         setTimeout(function() {
@@ -31,6 +34,9 @@
         // Initialize share functionality
         initializeShare();
 
+        // Restore collapsed link sections
+        initializeLinkSections();
+
         // Initialize scroll-based dark mode toggle fade
         initializeDarkModeToggleFade();
     });
@@ -90,29 +96,56 @@
         });
     });
 
-    // Add CSS for ripple effect
-    const style = document.createElement('style');
-    style.textContent = `
-        .link-button {
-            position: relative;
-            overflow: hidden;
-        }
-        .ripple {
-            position: absolute;
-            border-radius: 50%;
-            background: rgba(0, 0, 0, 0.1);
-            transform: scale(0);
-            animation: ripple-animation 0.6s ease-out;
-            pointer-events: none;
+    // Scheduled links are shown between their data-visible-from and
+    // data-visible-until times, without rebuilding the page
+    function initializeScheduledLinks() {
+        const scheduled = document.querySelectorAll('[data-visible-from], [data-visible-until]');
+        if (scheduled.length === 0) {
+            return;
         }
-        @keyframes ripple-animation {
-            to {
-                transform: scale(4);
-                opacity: 0;
+        function update() {
+            const now = Date.now();
+            let next = Infinity;
+            scheduled.forEach(function(element) {
+                // Missing times parse as NaN, which never compares true
+                const from = Date.parse(element.dataset.visibleFrom);
+                const until = Date.parse(element.dataset.visibleUntil);
+                element.hidden = from > now || until <= now;
+                [from, until].forEach(function(time) {
+                    if (time > now && time < next) {
+                        next = time;
+                    }
+                });
+            });
+            if (next !== Infinity) {
+                // Timers longer than about 24 days fire at once, so wait in steps
+                setTimeout(update, Math.min(next - now, 0x7fffffff));
             }
         }
-    `;
-    document.head.appendChild(style);
+        update();
+    }
+
+    // Collapsible link sections remember whether they were open
+    function initializeLinkSections() {
+        document.querySelectorAll('details.link-section[data-section]').forEach(function(section) {
+            const key = 'genkan-section:' + section.dataset.section;
+            try {
+                const saved = localStorage.getItem(key);
+                if (saved !== null) {
+                    section.open = saved === 'open';
+                }
+            } catch (e) {
+                // Storage may be unavailable, e.g. in private browsing
+            }
+            section.addEventListener('toggle', function() {
+                try {
+                    localStorage.setItem(key, section.open ? 'open' : 'closed');
+                } catch (e) {
+                    // Ignore; the section just won't be remembered
+                }
+            });
+        });
+    }
 
     // Dark mode functionality
     function initializeDarkMode() {